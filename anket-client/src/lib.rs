@@ -0,0 +1,274 @@
+//! Client of anket polls, for bots, load tests and integrations.
+//!
+//! Actions are sent to the JSON API under `/api/v1`; changes of the poll are received from the
+//! websocket that browsers use as well.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), anket_client::ClientError> {
+//! use anket_client::{PollClient, PollSettings};
+//!
+//! let owner = PollClient::create_poll("http://localhost:3000", &PollSettings::new("Lunch?")).await?;
+//! let participant = PollClient::join("http://localhost:3000", owner.poll_id(), None).await?;
+//! let item = participant.add_item(0, "Pizza").await?;
+//! owner.vote(item.item_id, 1).await?;
+//!
+//! let mut subscription = owner.subscribe().await?;
+//! while let Some(update) = subscription.next().await {
+//!     update?;
+//!     println!("{:?}", subscription.state().questions[0].top_items);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+mod types;
+
+pub use anket_shared;
+pub use types::*;
+
+use futures_util::{stream::Stream, StreamExt};
+use hyper::{
+    body::Bytes,
+    client::HttpConnector,
+    header::{self, HeaderValue},
+    Body, Client, Method, Request,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{self, client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+/// A user of a poll; the poll owner if the poll is created by this client.
+pub struct PollClient {
+    http: Client<HttpsConnector<HttpConnector>>,
+    // root of the anket instance without a trailing slash, like `https://example.com/anket`
+    base_url: String,
+    poll_id: String,
+    // every request with this token is made as the same user
+    token: Uuid,
+}
+
+impl PollClient {
+    /// Creates a poll on the instance at `base_url`; this client becomes its owner.
+    pub async fn create_poll(base_url: &str, settings: &PollSettings) -> Result<Self, ClientError> {
+        let http = http_client();
+        let base_url = base_url.trim_end_matches('/').to_owned();
+        let url = format!("{}/api/v1/polls", base_url);
+        let body = Some(serde_json::to_vec(settings)?);
+        let created: PollCreated =
+            serde_json::from_slice(&send(&http, Method::POST, &url, None, body).await?)?;
+        Ok(Self {
+            http,
+            base_url,
+            poll_id: created.poll_id,
+            token: created.token,
+        })
+    }
+
+    /// Joins a poll as a new participant, or as the user of `token` if it's given.
+    pub async fn join(
+        base_url: &str,
+        poll_id: &str,
+        token: Option<Uuid>,
+    ) -> Result<Self, ClientError> {
+        let http = http_client();
+        let base_url = base_url.trim_end_matches('/').to_owned();
+        let url = format!("{}/api/v1/polls/{}", base_url, poll_id);
+        let res: PollRes =
+            serde_json::from_slice(&send(&http, Method::GET, &url, token, None).await?)?;
+        Ok(Self {
+            http,
+            base_url,
+            poll_id: poll_id.to_owned(),
+            token: res.token,
+        })
+    }
+
+    pub fn poll_id(&self) -> &str {
+        &self.poll_id
+    }
+
+    // keep this to act as the same user later, with `PollClient::join`
+    pub fn token(&self) -> Uuid {
+        self.token
+    }
+
+    pub async fn state(&self) -> Result<PollState, ClientError> {
+        let res: PollRes = self.request(Method::GET, "", None::<()>).await?;
+        Ok(res.state)
+    }
+
+    pub async fn add_item(
+        &self,
+        question_id: usize,
+        text: &str,
+    ) -> Result<ItemCreated, ClientError> {
+        let body = serde_json::json!({ "question_id": question_id, "text": text });
+        self.request(Method::POST, "/items", Some(body)).await
+    }
+
+    // zero `value` takes the vote back
+    pub async fn vote(&self, item_id: usize, value: isize) -> Result<(), ClientError> {
+        let path = format!("/items/{}/vote", item_id);
+        let body = serde_json::json!({ "value": value });
+        self.send(Method::PUT, &path, Some(serde_json::to_vec(&body)?))
+            .await
+            .map(|_| ())
+    }
+
+    // only poll owner can close the poll
+    pub async fn close(&self) -> Result<(), ClientError> {
+        self.send(Method::POST, "/close", None).await.map(|_| ())
+    }
+
+    /// Connects to the websocket of the poll, to follow its changes.
+    pub async fn subscribe(&self) -> Result<Subscription, ClientError> {
+        let state = self.state().await?;
+        let ws_url = match self.base_url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}/p/{}/ws", rest, self.poll_id),
+            Some(("http", rest)) => format!("ws://{}/p/{}/ws", rest, self.poll_id),
+            _ => return Err(ClientError::Url(self.base_url.clone())),
+        };
+        let mut request = ws_url.into_client_request()?;
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, bearer(self.token));
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(Subscription { socket, state })
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<impl Serialize>,
+    ) -> Result<T, ClientError> {
+        let body = body.map(|body| serde_json::to_vec(&body)).transpose()?;
+        Ok(serde_json::from_slice(
+            &self.send(method, path, body).await?,
+        )?)
+    }
+
+    // `path` is relative to the poll
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Bytes, ClientError> {
+        let url = format!("{}/api/v1/polls/{}{}", self.base_url, self.poll_id, path);
+        send(&self.http, method, &url, Some(self.token), body).await
+    }
+}
+
+/// Websocket connection of a poll; keeps the latest state of the poll up to date.
+pub struct Subscription {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    state: PollState,
+}
+
+impl Subscription {
+    pub fn state(&self) -> &PollState {
+        &self.state
+    }
+
+    /// Waits for the next message of the server and applies it to the state; `None` after the
+    /// connection is closed.
+    pub async fn next(&mut self) -> Option<Result<Update, ClientError>> {
+        loop {
+            let message = match self.socket.next().await? {
+                Ok(message) => message,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if let Message::Text(text) = message {
+                match parse_update(&text) {
+                    Ok(Some(update)) => {
+                        types::apply(&mut self.state, &update);
+                        return Some(Ok(update));
+                    }
+                    // messages of a newer server are skipped
+                    Ok(None) => {}
+                    Err(err) => return Some(Err(err.into())),
+                }
+            }
+        }
+    }
+
+    /// Stream of poll states; a state is yielded after every message of the server.
+    pub fn into_states(self) -> impl Stream<Item = Result<PollState, ClientError>> {
+        futures_util::stream::unfold(self, |mut subscription| async move {
+            let result = subscription.next().await?;
+            let state = result.map(|_| subscription.state.clone());
+            Some((state, subscription))
+        })
+    }
+}
+
+fn http_client() -> Client<HttpsConnector<HttpConnector>> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Client::builder().build(connector)
+}
+
+fn bearer(token: Uuid) -> HeaderValue {
+    HeaderValue::from_str(&format!("Bearer {}", token)).expect("tokens are valid header values")
+}
+
+async fn send(
+    http: &Client<HttpsConnector<HttpConnector>>,
+    method: Method,
+    url: &str,
+    token: Option<Uuid>,
+    body: Option<Vec<u8>>,
+) -> Result<Bytes, ClientError> {
+    let url = url
+        .parse::<hyper::Uri>()
+        .map_err(|_| ClientError::Url(url.to_owned()))?;
+    let mut request = Request::builder().method(method).uri(url);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, bearer(token));
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body)),
+        None => request.body(Body::empty()),
+    }
+    .expect("request parts are valid");
+
+    let response = http.request(request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if !status.is_success() {
+        let message = serde_json::from_slice::<ApiError>(&body)
+            .map(|err| err.error)
+            .unwrap_or_else(|_| status.to_string());
+        return Err(ClientError::Api {
+            status: status.as_u16(),
+            message,
+        });
+    }
+    Ok(body)
+}
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("{0:?} is not a valid URL")]
+    Url(String),
+    #[error("request failed: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("websocket failed: {0}")]
+    WebSocket(#[from] tungstenite::Error),
+    // server refused the request
+    #[error("{message} ({status})")]
+    Api { status: u16, message: String },
+    #[error("unexpected message: {0}")]
+    Decode(#[from] serde_json::Error),
+}
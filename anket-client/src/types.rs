@@ -0,0 +1,148 @@
+// responses of the JSON API; poll state, settings and messages of the websocket are the types of
+// the server, from `anket-shared`
+use anket_shared::protocol::UserResponse;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use anket_shared::protocol::ActionError;
+pub use anket_shared::{
+    AddItemPermit, ClockHint, DuplicateVoteCheck, ItemState, PollMeta, PollSettings, PollState,
+    QuestionState, RankingMethod, RetentionPolicy, UserLookupMethod, UserVoteState, VoteMode,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PollCreated {
+    pub poll_id: String,
+    // token of the owner
+    pub token: Uuid,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PollRes {
+    pub token: Uuid,
+    pub state: PollState,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemCreated {
+    pub item_id: usize,
+    // item is only listed after poll owner approves it
+    pub pending: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub error: String,
+}
+
+/// Messages that the server sends on websocket.
+pub type Update = UserResponse;
+
+/// Decodes a message of the server; `None` for the ones that this version of the protocol doesn't
+/// have, like the ones of a newer server.
+pub fn parse_update(text: &str) -> Result<Option<Update>, serde_json::Error> {
+    serde_json::from_str(text).map(Some).or_else(|err| {
+        if err.to_string().starts_with("unknown variant") {
+            Ok(None)
+        } else {
+            Err(err)
+        }
+    })
+}
+
+// sections of the state are sent separately on websocket; this puts them in place
+pub(crate) fn apply(state: &mut PollState, update: &Update) {
+    match update {
+        Update::State { state: latest, .. } => *state = latest.clone(),
+        Update::Meta(meta) => state.meta = meta.clone(),
+        Update::Question(question) => {
+            match state.questions.iter_mut().find(|q| q.id == question.id) {
+                Some(current) => *current = question.clone(),
+                None => {
+                    state.questions.push(question.clone());
+                    state.questions.sort_by_key(|q| q.id);
+                }
+            }
+        }
+        Update::PinnedItems(items) => state.pinned_items = items.clone(),
+        Update::UserItems(items) => state.user_items = items.clone(),
+        Update::PendingItems(items) => state.pending_items = items.clone(),
+        Update::UserVotes(votes) => state.user_votes = votes.clone(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anket_shared::protocol::{MessageId, Reply};
+    use anket_shared::PollEvent;
+
+    fn item(id: usize, score: isize) -> ItemState {
+        ItemState {
+            id,
+            question_id: 0,
+            is_author: false,
+            edited: false,
+            author: None,
+            created_at: 0,
+            comment_count: 0,
+            reports: None,
+            text: "an item".to_string(),
+            html: None,
+            attachment: None,
+            section: None,
+            link_preview: None,
+            score: Some(score),
+            user_vote: 0,
+            approval_percentage: None,
+            rating: None,
+            stats: None,
+            references: vec![],
+            reactions: vec![],
+        }
+    }
+
+    // messages are decoded from what the server sends, as it serializes them
+    #[test]
+    fn server_messages_are_applied_to_state() {
+        let mut state: PollState = serde_json::from_value(serde_json::json!({
+            "poll_title": "poll", "description": null, "sections": [], "closed": false,
+            "is_owner": false, "min_item_length": 1, "max_item_length": 300,
+            "results_hidden": false, "shuffled": false, "can_undo": false,
+            "draft_token": Uuid::nil(), "questions": [], "pinned_items": [], "user_items": [],
+            "pending_items": [], "user_votes": [],
+        }))
+        .unwrap();
+
+        let question = QuestionState {
+            id: 0,
+            title: "poll".to_string(),
+            vote_mode: VoteMode::UpDown,
+            allow_downvotes: true,
+            top_items: vec![item(1, 3), item(0, 1)],
+            sections: vec![],
+            latest_items: vec![item(1, 3), item(0, 1)],
+        };
+        let event = serde_json::to_string(&PollEvent::Question(question.clone())).unwrap();
+        let update = parse_update(&event).unwrap().unwrap();
+        assert_eq!(update, Update::Question(question));
+        apply(&mut state, &update);
+        assert_eq!(state.questions.len(), 1);
+        assert_eq!(state.questions[0].top_items[0].score, Some(3));
+
+        // responses come with the id of the message they answer
+        let reply = serde_json::to_string(&Reply {
+            id: Some(MessageId::Number(1)),
+            response: UserResponse::ItemAdded { item_id: 1 },
+        })
+        .unwrap();
+        assert_eq!(
+            parse_update(&reply).unwrap(),
+            Some(Update::ItemAdded { item_id: 1 })
+        );
+
+        let unknown = serde_json::json!({"type": "Fireworks", "content": {}}).to_string();
+        assert_eq!(parse_update(&unknown).unwrap(), None);
+    }
+}
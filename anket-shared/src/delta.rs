@@ -0,0 +1,98 @@
+use crate::{ItemState, QuestionState};
+
+use std::collections::{HashMap, HashSet};
+
+// item level changes of a question since the last state a client got; lists are given by item
+// ids, and states of items are only sent when they're new or changed
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QuestionDelta {
+    pub id: usize,
+    pub upserts: Vec<ItemState>,
+    // ids of items that are not on any list of the question anymore
+    pub removes: Vec<usize>,
+    // `None` when order of the list is the same
+    pub top_items: Option<Vec<usize>>,
+    pub latest_items: Option<Vec<usize>>,
+    // only the sections whose order changed
+    pub sections: Vec<SectionOrder>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SectionOrder {
+    pub id: usize,
+    pub items: Vec<usize>,
+}
+
+fn item_ids(items: &[ItemState]) -> Vec<usize> {
+    items.iter().map(|item| item.id).collect()
+}
+
+impl QuestionState {
+    fn listed_items(&self) -> impl Iterator<Item = &ItemState> {
+        self.top_items.iter().chain(self.latest_items.iter()).chain(
+            self.sections
+                .iter()
+                .flat_map(|section| section.items.iter()),
+        )
+    }
+
+    // `None` when the question itself changed, like its title or sections; then the whole
+    // question has to be sent
+    pub fn delta_since(&self, previous: &QuestionState) -> Option<QuestionDelta> {
+        let same_sections = self.sections.len() == previous.sections.len()
+            && self
+                .sections
+                .iter()
+                .zip(previous.sections.iter())
+                .all(|(section, prev)| section.id == prev.id && section.name == prev.name);
+        if self.id != previous.id
+            || self.title != previous.title
+            || self.vote_mode != previous.vote_mode
+            || self.allow_downvotes != previous.allow_downvotes
+            || !same_sections
+        {
+            return None;
+        }
+
+        let previous_items: HashMap<usize, &ItemState> = previous
+            .listed_items()
+            .map(|item| (item.id, item))
+            .collect();
+        let mut seen = HashSet::new();
+        let upserts = self
+            .listed_items()
+            .filter(|item| seen.insert(item.id))
+            .filter(|item| previous_items.get(&item.id) != Some(item))
+            .cloned()
+            .collect();
+        let mut removes: Vec<usize> = previous_items
+            .keys()
+            .filter(|item_id| !seen.contains(item_id))
+            .copied()
+            .collect();
+        removes.sort();
+
+        let changed_order = |items: &[ItemState], prev: &[ItemState]| {
+            let ids = item_ids(items);
+            (ids != item_ids(prev)).then_some(ids)
+        };
+        Some(QuestionDelta {
+            id: self.id,
+            upserts,
+            removes,
+            top_items: changed_order(&self.top_items, &previous.top_items),
+            latest_items: changed_order(&self.latest_items, &previous.latest_items),
+            sections: self
+                .sections
+                .iter()
+                .zip(previous.sections.iter())
+                .filter_map(|(section, prev)| {
+                    changed_order(&section.items, &prev.items).map(|items| SectionOrder {
+                        id: section.id,
+                        items,
+                    })
+                })
+                .collect(),
+        })
+    }
+}
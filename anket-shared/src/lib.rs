@@ -0,0 +1,14 @@
+//! Wire types of anket polls, shared by the server and its clients.
+//!
+//! Poll state is sent in sections as [`PollEvent`]s; [`protocol`] has the rest of the messages of
+//! the websocket, both ways.
+pub mod delta;
+pub mod protocol;
+pub mod settings;
+pub mod state;
+pub mod subscription;
+
+pub use delta::*;
+pub use settings::*;
+pub use state::*;
+pub use subscription::*;
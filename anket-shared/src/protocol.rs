@@ -0,0 +1,386 @@
+// messages of the websocket at `/p/<poll id>/ws`, both ways; changes that break older clients bump
+// `PROTOCOL_VERSION`
+
+use crate::{
+    ClockHint, CommentState, ItemOrder, ItemOverlay, ItemPage, ItemState, PollMeta, PollState,
+    QuestionDelta, QuestionState, SimilarItem, Subscription, UserVoteState, VoteMode,
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// version of the websocket protocol; it changes when messages change in a way that older clients
+// can't handle
+pub const PROTOCOL_VERSION: u32 = 2;
+// clients older than this are rejected; version 1 had free-text errors
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+// `None` when the client is too old to be served; otherwise the version and the features that
+// both sides support, newer clients are downgraded to the version of the server
+pub fn negotiate_hello(
+    protocol_version: u32,
+    client_features: &[String],
+    server_features: &[String],
+) -> Option<UserResponse> {
+    if protocol_version < MIN_PROTOCOL_VERSION {
+        return None;
+    }
+    Some(UserResponse::Hello {
+        protocol_version: protocol_version.min(PROTOCOL_VERSION),
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        features: server_features
+            .iter()
+            .filter(|feature| client_features.contains(feature))
+            .cloned()
+            .collect(),
+    })
+}
+
+// chosen by the client and attached to a message as `id`; the response of the message has it too
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MessageId {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "content")]
+pub enum UserMessage {
+    // first message of the client; it's answered with `Hello` of the agreed version and features,
+    // or the connection is closed if the client is too old. Clients that don't send it are served
+    // with the current version
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    // items are added to the first question if `question_id` is missing
+    AddItem {
+        text: String,
+        #[serde(default)]
+        question_id: usize,
+        // id of an image uploaded by this user
+        #[serde(default)]
+        attachment: Option<Uuid>,
+        #[serde(default)]
+        section: Option<usize>,
+    },
+    // existing items that look like `text`; answered with `SimilarItems`, only to this user
+    SuggestSimilar {
+        text: String,
+        #[serde(default)]
+        question_id: usize,
+    },
+    VoteItem {
+        item_id: usize,
+        vote: isize,
+    },
+    // authors can edit for a while after adding the item, poll owner at any time
+    EditItem {
+        item_id: usize,
+        text: String,
+    },
+    // authors can delete their items; poll owner deletes others' items with `OwnerMessage`
+    DeleteItem {
+        item_id: usize,
+    },
+    // any participant except the author can report an item once; poll owner sees the reports
+    ReportItem {
+        item_id: usize,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    // privileged actions; they're rejected unless the sender is poll owner
+    Owner(OwnerMessage),
+    // `reply_to` is id of the comment that this one answers
+    CommentItem {
+        item_id: usize,
+        #[serde(default)]
+        reply_to: Option<usize>,
+        text: String,
+    },
+    // comments are delivered as `Comments` response, only to this user
+    GetComments {
+        item_id: usize,
+    },
+    // name of the user in this poll; an empty name removes it
+    SetName {
+        name: String,
+    },
+    // reverts latest vote change of the user
+    UndoLastAction,
+    // reacting again with the same emoji takes the reaction back
+    ReactItem {
+        item_id: usize,
+        emoji: String,
+    },
+    // translation is delivered as `TranslateItem` response, only to this user
+    TranslateItem {
+        item_id: usize,
+        lang: String,
+    },
+    // client got `Clock` of this version last; if it missed changes after that, every section of
+    // state is sent again, otherwise it's answered with `Synced`
+    Resync {
+        have_version: u64,
+    },
+    // answered with `State` right away, without waiting for the next change of the poll
+    GetState,
+    // only this part of the state is sent to the connection from now on; it's sent whole first
+    Subscribe(Subscription),
+    // a page of every item of the question; answered with `Items`, only to this user
+    ListItems {
+        #[serde(default)]
+        question_id: usize,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default = "default_item_page_size")]
+        limit: usize,
+        #[serde(default)]
+        order: ItemOrder,
+    },
+}
+
+// items that are listed at once when every item is browsed
+pub const ITEM_PAGE_SIZE: usize = 20;
+
+pub fn default_item_page_size() -> usize {
+    ITEM_PAGE_SIZE
+}
+
+// actions that only poll owner can take; each one is acknowledged with `OwnerDone` response
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "content")]
+pub enum OwnerMessage {
+    AddQuestion {
+        title: String,
+        vote_mode: VoteMode,
+    },
+    // names are given in their order
+    SetSections {
+        sections: Vec<String>,
+    },
+    // items of any participant can be deleted
+    DeleteItem {
+        item_id: usize,
+    },
+    // for items that wait for approval
+    ApproveItem {
+        item_id: usize,
+    },
+    RejectItem {
+        item_id: usize,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    DismissReports {
+        item_id: usize,
+    },
+    PinItem {
+        item_id: usize,
+        pinned: bool,
+    },
+    // votes on the source are moved to the target
+    MergeItems {
+        source_id: usize,
+        target_id: usize,
+    },
+    ClosePoll,
+    ResetVotes,
+    // `participant_id` is the id in participant list
+    SetUserWeight {
+        participant_id: Uuid,
+        weight: u32,
+    },
+    // `banned` is false to lift the ban
+    ShadowBan {
+        participant_id: Uuid,
+        banned: bool,
+    },
+    // kicked participants can join again, banned ones can't
+    KickUser {
+        participant_id: Uuid,
+    },
+    BanUser {
+        participant_id: Uuid,
+        #[serde(default)]
+        remove_content: bool,
+    },
+}
+
+impl OwnerMessage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OwnerMessage::AddQuestion { .. } => "AddQuestion",
+            OwnerMessage::SetSections { .. } => "SetSections",
+            OwnerMessage::DeleteItem { .. } => "DeleteItem",
+            OwnerMessage::ApproveItem { .. } => "ApproveItem",
+            OwnerMessage::RejectItem { .. } => "RejectItem",
+            OwnerMessage::DismissReports { .. } => "DismissReports",
+            OwnerMessage::PinItem { .. } => "PinItem",
+            OwnerMessage::MergeItems { .. } => "MergeItems",
+            OwnerMessage::ClosePoll => "ClosePoll",
+            OwnerMessage::ResetVotes => "ResetVotes",
+            OwnerMessage::SetUserWeight { .. } => "SetUserWeight",
+            OwnerMessage::ShadowBan { .. } => "ShadowBan",
+            OwnerMessage::KickUser { .. } => "KickUser",
+            OwnerMessage::BanUser { .. } => "BanUser",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", content = "content")]
+pub enum UserResponse {
+    // first message of the server, with every feature it supports; also the answer to `Hello` of
+    // the client
+    Hello {
+        protocol_version: u32,
+        min_protocol_version: u32,
+        features: Vec<String>,
+    },
+    // message is handled without any other response; only sent for messages with an `id`
+    Ack,
+    // message of the client failed
+    Error(ActionError),
+    TranslateItem {
+        item_id: usize,
+        lang: String,
+        text: String,
+    },
+    // `text` is the text of the query; so clients can ignore answers to older queries
+    SimilarItems {
+        text: String,
+        items: Vec<SimilarItem>,
+    },
+    Comments {
+        item_id: usize,
+        comments: Vec<CommentState>,
+    },
+    Items(ItemPage),
+    // whole state of the poll as this user sees it, at `version`; events of older batches may still
+    // arrive after it, clients skip them by the tick of their `Clock`
+    State {
+        version: u64,
+        state: PollState,
+    },
+    // client has the latest state after `Resync`
+    Synced {
+        version: u64,
+    },
+    // `command` is the type of `OwnerMessage` that is done
+    OwnerDone {
+        command: String,
+    },
+    // item is added and listed; clients can match it with the item in the next update
+    ItemAdded {
+        item_id: usize,
+    },
+    // item is added, but it's only listed after poll owner approves it
+    ItemPending {
+        item_id: usize,
+    },
+    // report is sent to poll owner
+    ItemReported {
+        item_id: usize,
+    },
+    // vote of this user on the item is now `value`
+    VoteRecorded {
+        item_id: usize,
+        value: isize,
+    },
+    // poll events are serialized as `PollEvent`s, which have the same shape as these
+    Clock(ClockHint),
+    // sections of poll state; only the changed ones are sent after the first time
+    Meta(PollMeta),
+    Question(QuestionState),
+    // changes of a question since the last `Question` or `QuestionDelta`; `version` is the tick
+    // of the broadcast
+    QuestionDelta {
+        version: u64,
+        delta: QuestionDelta,
+    },
+    PinnedItems(Vec<ItemState>),
+    UserItems(Vec<ItemState>),
+    PendingItems(Vec<ItemState>),
+    UserVotes(Vec<UserVoteState>),
+    // votes, reactions and items of the user on questions of `view=shared` connections
+    ItemOverlay(ItemOverlay),
+    Nudge(String),
+}
+
+// codes of errors that are about the input of a message rather than the state of the poll
+const INPUT_ERROR_CODES: &[&str] = &[
+    "EmptyText",
+    "Empty",
+    "TooShort",
+    "TooLong",
+    "Rejected",
+    "ReasonTooLong",
+    "InvalidName",
+    "NameTaken",
+    "InvalidValue",
+    "InvalidReaction",
+    "InvalidLanguage",
+    "EmptyName",
+    "NameTooLong",
+    "DuplicateName",
+];
+
+// error of a message of the client; clients react to `code`, like `NotOwner` or `PollClosed`, and
+// show `message` to the user
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ActionError {
+    pub code: String,
+    pub message: String,
+    // input of the message that the error is about, like `text`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl ActionError {
+    pub fn new(code: &str, message: &str) -> Self {
+        Self {
+            code: code.to_owned(),
+            message: message.to_owned(),
+            field: None,
+        }
+    }
+
+    // errors of poll are coded by their variant names
+    pub fn from_error<E>(err: E) -> Self
+    where
+        E: std::fmt::Display + Into<&'static str>,
+    {
+        let message = err.to_string();
+        Self {
+            code: Into::<&'static str>::into(err).to_owned(),
+            message,
+            field: None,
+        }
+    }
+
+    // `field` is only set if the error is about the input, not for ones like `PollClosed`
+    pub fn input(mut self, field: &str) -> Self {
+        if INPUT_ERROR_CODES.contains(&self.code.as_str()) {
+            self.field = Some(field.to_owned());
+        }
+        self
+    }
+}
+
+impl From<ActionError> for UserResponse {
+    fn from(err: ActionError) -> Self {
+        UserResponse::Error(err)
+    }
+}
+
+// response to a message of the client
+#[derive(Serialize, Clone, Debug)]
+pub struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<MessageId>,
+    #[serde(flatten)]
+    pub response: UserResponse,
+}
@@ -0,0 +1,227 @@
+// settings of polls; they're given while creating a poll, from the JSON API or the new poll form
+use serde::{Deserialize, Deserializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum UserLookupMethod {
+    IPBased,
+    SessionBased,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum AddItemPermit {
+    Anyone,
+    OwnerOnly,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum VoteMode {
+    // users can upvote or downvote items, score is sum of votes
+    UpDown,
+    // users can only approve items, score is count of approvals
+    Approval,
+    // users spend credits from their budget, casting k votes on an item costs k² credits
+    Quadratic,
+    // users rate items from 1 to 5, items are ranked by their average rating
+    Rating,
+}
+
+// how every item of a question is listed when they're browsed page by page
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ItemOrder {
+    // same order as top items
+    #[default]
+    Ranked,
+    Newest,
+    Oldest,
+}
+
+// how items are ordered in top items list
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum RankingMethod {
+    // by score of item, or its average rating on rating mode
+    #[default]
+    Score,
+    // by sum of votes where recent votes weigh more
+    Hot,
+    // by lower bound of Wilson score confidence interval of upvote ratio
+    // an item with few votes can't outrank an item with a lot of mostly positive votes
+    Wilson,
+}
+
+// how votes from same IP address are treated on session based polls
+// users of same network share an IP address; so this is not enabled by default
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum DuplicateVoteCheck {
+    #[default]
+    Off,
+    // owner sees the participants that share an IP address
+    Flag,
+    // only one user from an IP address can vote an item
+    Reject,
+}
+
+// what happens to the poll data after poll closes
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum RetentionPolicy {
+    // everything is removed as soon as poll closes
+    Purge,
+    // votes are detached from users on close, results are kept for retention period
+    Anonymize,
+    // results are kept as is for retention period
+    Keep,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PollSettings {
+    pub title: String,
+    // question, rules and context of the poll; shown under its title, blank means there is none
+    #[serde(default)]
+    pub description: Option<String>,
+    pub user_lookup_method: UserLookupMethod,
+    pub add_item_permit: AddItemPermit,
+    pub vote_mode: VoteMode,
+    pub retention_policy: RetentionPolicy,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub retention_days: u32,
+    // credit budget of every user on quadratic voting
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub vote_credits: u32,
+    // only owner can see scores while poll is open
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub hide_results_until_close: bool,
+    #[serde(default)]
+    pub ranking_method: RankingMethod,
+    // remind idle users about items they didn't see yet
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub nudges: bool,
+    // negative votes are rejected when this is false
+    #[serde(
+        default = "default_allow_downvotes",
+        deserialize_with = "from_str_or_value"
+    )]
+    pub allow_downvotes: bool,
+
+    // only effective on session based polls
+    #[serde(default)]
+    pub duplicate_vote_check: DuplicateVoteCheck,
+    // items are listed in a random order for each user until poll closes
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub shuffle_items: bool,
+    // users can vote at most this many items at once; 0 means there is no limit
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub max_votes_per_user: u32,
+    // users can add at most this many items; 0 means there is no limit
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub max_items_per_user: u32,
+    // poll can have at most this many items, including the ones waiting for approval; 0 means
+    // there is no limit
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub max_items_total: u32,
+    // external systems can add items and vote on behalf of their users with signed requests
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub allow_ingestion: bool,
+    // authors can edit their items for this many minutes; 0 means they can't edit at all
+    #[serde(
+        default = "default_edit_window_minutes",
+        deserialize_with = "from_str_or_value"
+    )]
+    pub edit_window_minutes: u32,
+    // items show who added them; users without a name are shown by their participant number
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub show_authors: bool,
+    // items of participants are only listed after poll owner approves them
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub moderate_items: bool,
+    // item texts are rendered as markdown; clients get the sanitized HTML along with the text
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub markdown: bool,
+    // limits of item texts, in characters; 0 as maximum means the limit of this instance
+    #[serde(
+        default = "default_min_item_length",
+        deserialize_with = "from_str_or_value"
+    )]
+    pub min_item_length: usize,
+    #[serde(default, deserialize_with = "from_str_or_value")]
+    pub max_item_length: usize,
+    // changes made in this many milliseconds are broadcast together; 0 broadcasts every change
+    // right away, `None` means the delay of this instance
+    #[serde(default)]
+    pub broadcast_delay_ms: Option<u32>,
+}
+
+fn default_min_item_length() -> usize {
+    1
+}
+
+fn default_allow_downvotes() -> bool {
+    true
+}
+
+fn default_edit_window_minutes() -> u32 {
+    5
+}
+
+impl PollSettings {
+    // defaults of the new poll form
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: None,
+            user_lookup_method: UserLookupMethod::SessionBased,
+            add_item_permit: AddItemPermit::Anyone,
+            vote_mode: VoteMode::UpDown,
+            retention_policy: RetentionPolicy::Keep,
+            retention_days: 7,
+            vote_credits: 100,
+            hide_results_until_close: false,
+            ranking_method: RankingMethod::default(),
+            nudges: false,
+            allow_downvotes: default_allow_downvotes(),
+            duplicate_vote_check: DuplicateVoteCheck::default(),
+            shuffle_items: false,
+            max_votes_per_user: 0,
+            max_items_per_user: 0,
+            max_items_total: 0,
+            allow_ingestion: false,
+            edit_window_minutes: default_edit_window_minutes(),
+            show_authors: false,
+            moderate_items: false,
+            markdown: false,
+            min_item_length: default_min_item_length(),
+            max_item_length: 0,
+            broadcast_delay_ms: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOr<T> {
+    String(String),
+    Value(T),
+}
+
+// deserializes a value either from itself or from its string representation; HTML forms send
+// every field as string, and `serde_urlencoded` can't parse them into numbers when the struct is
+// `#[serde(flatten)]`ed
+fn from_str_or_value<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Deserialize<'de>,
+    T::Err: Display,
+{
+    match StringOr::<T>::deserialize(deserializer)? {
+        StringOr::String(string) => string.trim().parse().map_err(serde::de::Error::custom),
+        StringOr::Value(value) => Ok(value),
+    }
+}
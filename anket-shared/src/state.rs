@@ -0,0 +1,371 @@
+// state of a poll as a user sees it; it's sent to clients in sections, as `PollEvent`s
+use crate::VoteMode;
+
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ItemState {
+    pub id: usize,
+    pub question_id: usize,
+    // item is added by requesting user
+    pub is_author: bool,
+    // text is changed after item is added
+    pub edited: bool,
+    // only available if poll shows authors
+    pub author: Option<String>,
+    // unix timestamp
+    pub created_at: i64,
+    // comments are fetched separately when they're shown
+    pub comment_count: usize,
+    // only available to poll owner, if the item is reported
+    pub reports: Option<ReportState>,
+    pub text: String,
+    // sanitized HTML of `text`; only available if poll uses markdown
+    pub html: Option<String>,
+    // image of item; served under `/p/<poll id>/attachments/<attachment id>`
+    pub attachment: Option<Uuid>,
+    // position of its section in `PollMeta.sections`
+    pub section: Option<usize>,
+    // only available after the linked page is fetched
+    pub link_preview: Option<LinkPreview>,
+    // `None` when results are hidden from the user
+    pub score: Option<isize>,
+    pub user_vote: isize,
+    // share of the users that approved any item of the question who approved this one; only
+    // available on approval voting
+    pub approval_percentage: Option<f64>,
+    // only available on rating mode
+    pub rating: Option<RatingState>,
+    // `None` when results are hidden from the user
+    #[serde(flatten)]
+    pub stats: Option<VoteStats>,
+    pub references: Vec<ItemPreview>,
+    // listed in the order that the server offers them
+    pub reactions: Vec<ReactionState>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReportState {
+    pub count: usize,
+    // reasons that reporters gave; reports without a reason are only counted
+    pub reasons: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReactionState {
+    pub emoji: String,
+    pub count: usize,
+    // whether requesting user reacted with this emoji
+    pub reacted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VoteStats {
+    // count of users voted this item, regardless of their vote values and weights
+    pub vote_count: usize,
+    pub upvotes: usize,
+    pub downvotes: usize,
+    // share of this item from the votes on every item of the poll
+    pub share_percentage: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CommentState {
+    pub id: usize,
+    // `None` for comments on the item itself
+    pub reply_to: Option<usize>,
+    pub text: String,
+    // unix timestamp
+    pub at: i64,
+    // comment is written by requesting user
+    pub is_author: bool,
+    pub by_owner: bool,
+    // only available if poll shows authors
+    pub author: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SimilarItem {
+    pub id: usize,
+    pub text: String,
+    // between 0 and 1, where 1 is the same text
+    pub similarity: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ItemPreview {
+    pub poll_id: String,
+    pub poll_title: String,
+    pub item_id: usize,
+    pub text: String,
+    // `None` if results of referenced poll are hidden
+    pub score: Option<isize>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RatingState {
+    pub average: f64,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PollMeta {
+    pub poll_title: String,
+    pub description: Option<String>,
+    // names of sections; items refer to them by their positions
+    pub sections: Vec<String>,
+    pub closed: bool,
+    pub is_owner: bool,
+    // name that user chose for this poll
+    pub name: Option<String>,
+    // limits of item texts, in characters
+    pub min_item_length: usize,
+    pub max_item_length: usize,
+    // only available to poll owner
+    pub participants: Option<Vec<ParticipantState>>,
+    // latest first; only available to poll owner
+    pub moderation_log: Option<Vec<ModerationEntry>>,
+    // secret that requests of external systems are signed with; only available to poll owner
+    pub ingest_secret: Option<String>,
+    // scores are hidden until poll closes, items are not ordered by their scores
+    pub results_hidden: bool,
+    // items are listed in a random order, true ranking is revealed when poll closes
+    pub shuffled: bool,
+    // user has vote changes that can be undone
+    pub can_undo: bool,
+    // browsers keep unsent item drafts under this; it's same for the user across reconnects
+    pub draft_token: Uuid,
+    // only available if a question uses quadratic voting
+    pub remaining_credits: Option<u32>,
+    // count of items user can vote more; only available if votes per user are limited
+    pub remaining_votes: Option<u32>,
+    // count of items user can add more; only available if items are limited
+    pub remaining_items: Option<u32>,
+    // TODO add AddItemPermit
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ParticipantState {
+    // public id of the user, not its session id
+    pub id: Uuid,
+    pub name: String,
+    pub weight: u32,
+    pub shadow_banned: bool,
+    // count of items added by this participant; helps owner to tell participants apart
+    pub item_count: usize,
+    pub is_owner: bool,
+    // another participant uses same IP address; only flagged if poll checks duplicate votes
+    pub shared_ip: bool,
+    // external system this participant comes from
+    pub source: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ModerationEntry {
+    // unix timestamp
+    pub at: i64,
+    // public id of the user
+    pub actor: Uuid,
+    pub action: ModerationAction,
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum ModerationAction {
+    // `target` is public id of the user
+    SetUserWeight {
+        target: Uuid,
+        weight: u32,
+    },
+    // `target` is public id of the author, `text` is the text before edit
+    EditItem {
+        target: Uuid,
+        text: String,
+    },
+    // `target` is public id of the user; `banned` is false when ban is lifted
+    ShadowBan {
+        target: Uuid,
+        banned: bool,
+    },
+    // `target` is public id of the user
+    KickUser {
+        target: Uuid,
+    },
+    // `target` is public id of the user; their items and votes are removed if `remove_content`
+    BanUser {
+        target: Uuid,
+        remove_content: bool,
+    },
+    // `target` is public id of the author
+    DeleteItem {
+        target: Uuid,
+        text: String,
+    },
+    // `target` is public id of the author, `count` is how many times item was reported
+    DismissReports {
+        target: Uuid,
+        text: String,
+        count: usize,
+    },
+    // `target` is public id of the author; item was waiting for approval
+    RejectItem {
+        target: Uuid,
+        text: String,
+    },
+    // `target` is public id of the author of merged item, `into` is id of the remaining item
+    MergeItems {
+        target: Uuid,
+        text: String,
+        into: usize,
+    },
+    ResetVotes,
+    ClosePoll,
+}
+
+impl ModerationAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModerationAction::SetUserWeight { .. } => "set_user_weight",
+            ModerationAction::ShadowBan { .. } => "shadow_ban",
+            ModerationAction::KickUser { .. } => "kick_user",
+            ModerationAction::BanUser { .. } => "ban_user",
+            ModerationAction::EditItem { .. } => "edit_item",
+            ModerationAction::DeleteItem { .. } => "delete_item",
+            ModerationAction::RejectItem { .. } => "reject_item",
+            ModerationAction::DismissReports { .. } => "dismiss_reports",
+            ModerationAction::MergeItems { .. } => "merge_items",
+            ModerationAction::ResetVotes => "reset_votes",
+            ModerationAction::ClosePoll => "close_poll",
+        }
+    }
+    // user that is affected by this action, if it's about a single user
+    pub fn target(&self) -> Option<Uuid> {
+        match self {
+            ModerationAction::SetUserWeight { target, .. }
+            | ModerationAction::ShadowBan { target, .. }
+            | ModerationAction::KickUser { target }
+            | ModerationAction::BanUser { target, .. }
+            | ModerationAction::EditItem { target, .. }
+            | ModerationAction::DeleteItem { target, .. }
+            | ModerationAction::RejectItem { target, .. }
+            | ModerationAction::DismissReports { target, .. }
+            | ModerationAction::MergeItems { target, .. } => Some(*target),
+            ModerationAction::ResetVotes | ModerationAction::ClosePoll => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PollState {
+    #[serde(flatten)]
+    pub meta: PollMeta,
+    // ordered by question id
+    pub questions: Vec<QuestionState>,
+    // items that poll owner pinned, from every question; shown above everything else
+    pub pinned_items: Vec<ItemState>,
+    // items of the user from every question
+    pub user_items: Vec<ItemState>,
+    // items that wait for approval, oldest first; only available to poll owner
+    pub pending_items: Vec<ItemState>,
+    // current votes of the user, latest first
+    pub user_votes: Vec<UserVoteState>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QuestionState {
+    pub id: usize,
+    pub title: String,
+    pub vote_mode: VoteMode,
+    pub allow_downvotes: bool,
+    pub top_items: Vec<ItemState>,
+    // every item of each section, ranked; empty if poll doesn't have sections
+    pub sections: Vec<SectionState>,
+    pub latest_items: Vec<ItemState>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SectionState {
+    pub id: usize,
+    pub name: String,
+    pub items: Vec<ItemState>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct UserVoteState {
+    pub item_id: usize,
+    pub text: String,
+    pub value: isize,
+}
+
+// parts of items that are about the user, for connections that get shared questions; items of
+// those questions are as if the user didn't vote, react or add any of them
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ItemOverlay {
+    // BTreeMap<item id, vote value> of every vote of the user
+    pub votes: BTreeMap<usize, isize>,
+    // ids of items that the user added
+    pub authored: Vec<usize>,
+    // BTreeMap<item id, emojis that the user reacted with>
+    pub reactions: BTreeMap<usize, Vec<String>>,
+}
+
+impl PollState {
+    // every section as an event, in the order they're sent
+    pub fn into_events(self) -> Vec<PollEvent> {
+        let mut events = vec![PollEvent::Meta(self.meta)];
+        events.extend(self.questions.into_iter().map(PollEvent::Question));
+        events.push(PollEvent::PinnedItems(self.pinned_items));
+        events.push(PollEvent::UserItems(self.user_items));
+        events.push(PollEvent::PendingItems(self.pending_items));
+        events.push(PollEvent::UserVotes(self.user_votes));
+        events
+    }
+}
+
+// sent before state updates; clients calibrate their countdowns by this instead of their clocks
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClockHint {
+    // unix timestamp in milliseconds
+    pub server_time: i64,
+    // increases on every broadcast of the poll
+    pub tick: u64,
+}
+
+// events that are delivered to connected users; they're shared by every connection that gets
+// them, and sent to clients as they are
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "type", content = "content")]
+pub enum PollEvent {
+    Clock(ClockHint),
+    // sections of `PollState`
+    Meta(PollMeta),
+    Question(QuestionState),
+    PinnedItems(Vec<ItemState>),
+    UserItems(Vec<ItemState>),
+    PendingItems(Vec<ItemState>),
+    UserVotes(Vec<UserVoteState>),
+    // only sent to shared connections
+    ItemOverlay(ItemOverlay),
+    // transient reminder for idle users
+    Nudge(String),
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ItemPage {
+    pub question_id: usize,
+    pub offset: usize,
+    // count of every listed item of the question
+    pub total: usize,
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub items: Vec<ItemState>,
+}
@@ -0,0 +1,175 @@
+use crate::{ItemOverlay, ItemState, PollEvent};
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// sections of poll state that a connection can subscribe to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateSection {
+    Meta,
+    Questions,
+    PinnedItems,
+    UserItems,
+    PendingItems,
+    UserVotes,
+    Nudges,
+}
+
+// how questions are sent to a connection; clients choose it when they connect
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateView {
+    // items have the votes and reactions of the user
+    #[default]
+    Personal,
+    // questions are the same for every participant; what's about the user comes in
+    // `ItemOverlay`, and clients put it on the items
+    Shared,
+}
+
+// part of the state that a connection wants; broadcasts only send that part to it. Default one
+// has everything, and `Clock` events are always sent
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    // `None` for every section
+    #[serde(default)]
+    pub sections: Option<Vec<StateSection>>,
+    // ids of questions that are sent; `None` for every question
+    #[serde(default)]
+    pub questions: Option<Vec<usize>>,
+    // lists of items only keep these items, like for the detail view of an item; `None` for
+    // every item
+    #[serde(default)]
+    pub items: Option<Vec<usize>>,
+    // questions are sent without their latest items and sections, like for a projector
+    #[serde(default)]
+    pub top_items_only: bool,
+    // comes from the URL of the connection; `Subscribe` can't change it
+    #[serde(skip)]
+    pub view: StateView,
+}
+
+impl Subscription {
+    fn has_section(&self, section: StateSection) -> bool {
+        self.sections
+            .as_ref()
+            .is_none_or(|sections| sections.contains(&section))
+    }
+
+    fn keep_overlay(&self, overlay: &mut ItemOverlay) {
+        if let Some(item_ids) = &self.items {
+            overlay
+                .votes
+                .retain(|item_id, _| item_ids.contains(item_id));
+            overlay
+                .authored
+                .retain(|item_id| item_ids.contains(item_id));
+            overlay
+                .reactions
+                .retain(|item_id, _| item_ids.contains(item_id));
+        }
+    }
+
+    fn keep_items(&self, items: &mut Vec<ItemState>) {
+        if let Some(item_ids) = &self.items {
+            items.retain(|item| item_ids.contains(&item.id));
+        }
+    }
+
+    // `None` when the event isn't wanted at all; event is only copied when a part of it is left out
+    pub fn filter(&self, event: Arc<PollEvent>) -> Option<Arc<PollEvent>> {
+        let section = match &*event {
+            PollEvent::Clock(_) => return Some(event),
+            PollEvent::Meta(_) => StateSection::Meta,
+            PollEvent::Question(_) => StateSection::Questions,
+            PollEvent::PinnedItems(_) => StateSection::PinnedItems,
+            PollEvent::UserItems(_) => StateSection::UserItems,
+            PollEvent::PendingItems(_) => StateSection::PendingItems,
+            PollEvent::UserVotes(_) => StateSection::UserVotes,
+            PollEvent::ItemOverlay(_) if self.view == StateView::Personal => return None,
+            PollEvent::ItemOverlay(_) => StateSection::Questions,
+            PollEvent::Nudge(_) => StateSection::Nudges,
+        };
+        if !self.has_section(section) {
+            return None;
+        }
+        if let (PollEvent::Question(question), Some(question_ids)) = (&*event, &self.questions) {
+            if !question_ids.contains(&question.id) {
+                return None;
+            }
+        }
+        if self.items.is_none() && !self.top_items_only {
+            return Some(event);
+        }
+        Some(Arc::new(match Arc::unwrap_or_clone(event) {
+            PollEvent::Question(mut question) => {
+                if self.top_items_only {
+                    question.latest_items.clear();
+                    question.sections.clear();
+                }
+                self.keep_items(&mut question.top_items);
+                self.keep_items(&mut question.latest_items);
+                for section in question.sections.iter_mut() {
+                    self.keep_items(&mut section.items);
+                }
+                PollEvent::Question(question)
+            }
+            PollEvent::PinnedItems(mut items) => {
+                self.keep_items(&mut items);
+                PollEvent::PinnedItems(items)
+            }
+            PollEvent::UserItems(mut items) => {
+                self.keep_items(&mut items);
+                PollEvent::UserItems(items)
+            }
+            PollEvent::PendingItems(mut items) => {
+                self.keep_items(&mut items);
+                PollEvent::PendingItems(items)
+            }
+            PollEvent::UserVotes(mut votes) => {
+                if let Some(item_ids) = &self.items {
+                    votes.retain(|vote| item_ids.contains(&vote.item_id));
+                }
+                PollEvent::UserVotes(votes)
+            }
+            PollEvent::ItemOverlay(mut overlay) => {
+                self.keep_overlay(&mut overlay);
+                PollEvent::ItemOverlay(overlay)
+            }
+            event => event,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QuestionState, VoteMode};
+
+    #[test]
+    fn events_are_only_copied_when_trimmed() {
+        let event = Arc::new(PollEvent::Question(QuestionState {
+            id: 0,
+            title: "question".to_string(),
+            vote_mode: VoteMode::UpDown,
+            allow_downvotes: true,
+            top_items: vec![],
+            latest_items: vec![],
+            sections: vec![],
+        }));
+        let everything = Subscription::default();
+        assert!(Arc::ptr_eq(
+            &everything.filter(event.clone()).unwrap(),
+            &event
+        ));
+
+        let projector = Subscription {
+            top_items_only: true,
+            ..Default::default()
+        };
+        assert!(!Arc::ptr_eq(
+            &projector.filter(event.clone()).unwrap(),
+            &event
+        ));
+    }
+}
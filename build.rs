@@ -1,3 +1,10 @@
 fn main() {
     minijinja_embed::embed_templates!("src/templates");
+
+    // protoc is bundled; so building with gRPC doesn't need it to be installed
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_build::compile_protos("proto/anket.proto").unwrap();
+    }
 }
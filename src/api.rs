@@ -0,0 +1,470 @@
+use crate::{models, views, AppState};
+
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        Extension, Path, Query, State,
+    },
+    http::StatusCode,
+    middleware,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing, Json,
+};
+use minijinja::context;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Server,
+    },
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+use uuid::Uuid;
+
+/// JSON API for scripts and bots; mounted under `/api/v1`.
+///
+/// Users are identified like in browsers, but with an `Authorization: Bearer <token>` header
+/// instead of the session cookie. Token of a user is returned when they create or get a poll.
+/// Every error is answered with an `ApiError` body.
+pub fn router(state: AppState) -> routing::Router<AppState> {
+    routing::Router::new()
+        .route("/polls", routing::post(create_poll))
+        .route("/polls/:id", routing::get(get_poll))
+        .route("/polls/:id/items", routing::get(list_items).post(add_item))
+        .route("/polls/:id/items/:item_id/vote", routing::put(vote_item))
+        .route("/polls/:id/close", routing::post(close_poll))
+        .route_layer(middleware::from_fn_with_state(state, views::identify_user))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "anket", description = "JSON API of anket polls."),
+    paths(create_poll, get_poll, list_items, add_item, vote_item, close_poll),
+    components(schemas(
+        ApiError,
+        views::FieldError,
+        PollCreated,
+        PollRes,
+        AddItemReq,
+        ItemCreated,
+        VoteReq,
+        models::ItemPage,
+        models::ItemOrder,
+        models::PollSettings,
+        models::UserLookupMethod,
+        models::AddItemPermit,
+        models::VoteMode,
+        models::RetentionPolicy,
+        models::RankingMethod,
+        models::DuplicateVoteCheck,
+    )),
+    modifiers(&BearerToken)
+)]
+struct ApiDoc;
+
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+// OpenAPI document of the routes above; paths are relative to the server it lists
+pub async fn openapi_document(State(state): State<AppState>) -> Response {
+    let mut document = ApiDoc::openapi();
+    document.servers = Some(vec![Server::new(state.urls.path("/api/v1"))]);
+    Json(document).into_response()
+}
+
+// Swagger UI for the document; it's loaded from a CDN, so it can be turned off
+pub async fn api_docs(State(state): State<AppState>) -> Response {
+    if !state.config.swagger_ui {
+        return Redirect::to(&state.urls.path("/api/docs/openapi.json")).into_response();
+    }
+    Html(
+        state
+            .templates
+            .get_template("api-docs.jinja")
+            .unwrap()
+            .render(context!())
+            .unwrap(),
+    )
+    .into_response()
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: String,
+    // problems of poll settings, by their fields
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<views::FieldError>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, error: impl ToString) -> Self {
+        Self {
+            status,
+            error: error.to_string(),
+            fields: vec![],
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<models::UserCreateError> for ApiError {
+    fn from(err: models::UserCreateError) -> Self {
+        let status = match err {
+            models::UserCreateError::Banned => StatusCode::FORBIDDEN,
+            models::UserCreateError::UserAlreadyExists => StatusCode::CONFLICT,
+        };
+        ApiError::new(status, err)
+    }
+}
+
+impl From<models::AddPollItemError> for ApiError {
+    fn from(err: models::AddPollItemError) -> Self {
+        use models::AddPollItemError::*;
+        let status = match err {
+            NotOwner | Banned => StatusCode::FORBIDDEN,
+            QuestionNotFound | AttachmentNotFound | SectionNotFound => StatusCode::NOT_FOUND,
+            PollClosed | AttachmentInUse | ItemLimitReached | PollFull => StatusCode::CONFLICT,
+            TooShort(_) | TooLong(_) | Rejected => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        ApiError::new(status, err)
+    }
+}
+
+impl From<models::VotePollItemError> for ApiError {
+    fn from(err: models::VotePollItemError) -> Self {
+        use models::VotePollItemError::*;
+        let status = match err {
+            Banned => StatusCode::FORBIDDEN,
+            ItemNotFound => StatusCode::NOT_FOUND,
+            PollClosed | NotEnoughCredits | VoteLimitReached | DuplicateVote => {
+                StatusCode::CONFLICT
+            }
+            VoteCooldown => StatusCode::TOO_MANY_REQUESTS,
+            InvalidValue => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        ApiError::new(status, err)
+    }
+}
+
+impl From<models::ListItemsError> for ApiError {
+    fn from(err: models::ListItemsError) -> Self {
+        ApiError::new(StatusCode::NOT_FOUND, err)
+    }
+}
+
+impl From<models::ClosePollError> for ApiError {
+    fn from(err: models::ClosePollError) -> Self {
+        let status = match err {
+            models::ClosePollError::NotOwner => StatusCode::FORBIDDEN,
+            models::ClosePollError::AlreadyClosed => StatusCode::CONFLICT,
+        };
+        ApiError::new(status, err)
+    }
+}
+
+// poll of the request and the user that sent it; users are created on their first request
+fn enter_poll(
+    state: &AppState,
+    poll_id: &str,
+    user: models::UserDetails,
+) -> Result<(Arc<Mutex<models::Poll>>, Uuid), ApiError> {
+    let poll = state
+        .polls
+        .lock()
+        .unwrap()
+        .get_poll(poll_id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "No such poll exists."))?;
+    let user_id = poll.lock().unwrap().enter(user)?;
+    Ok((poll, user_id))
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct PollCreated {
+    poll_id: String,
+    // token of the owner
+    token: Uuid,
+    // joins the websocket as the owner, with `?token=`
+    ws_token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls",
+    request_body = models::PollSettings,
+    responses(
+        (status = 201, description = "Poll is created; token is the owner's", body = PollCreated),
+        (status = 422, description = "Poll settings are invalid", body = ApiError),
+    ),
+    security((), ("token" = []))
+)]
+pub async fn create_poll(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    settings: Result<Json<models::PollSettings>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(mut settings) = settings?;
+    if settings.max_item_length == 0 {
+        settings.max_item_length = state.config.max_item_length;
+    }
+    let fields = views::validate_poll_settings(&settings, &state.config);
+    if !fields.is_empty() {
+        return Err(ApiError {
+            fields,
+            ..ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Poll settings are invalid.",
+            )
+        });
+    }
+    let (token, poll) = state.polls.lock().unwrap().add_poll(settings, user);
+    let poll_id = poll.lock().unwrap().get_id().to_owned();
+    let ws_token = views::ws_token(&state.token_secret, &poll_id, &token);
+    Ok((
+        StatusCode::CREATED,
+        Json(PollCreated {
+            poll_id,
+            token,
+            ws_token,
+        }),
+    )
+        .into_response())
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct PollRes {
+    // send this back to act as the same user
+    token: Uuid,
+    // joins the websocket as the same user, with `?token=`
+    ws_token: String,
+    #[schema(value_type = Object)]
+    state: models::PollState,
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{id}",
+    params(("id" = String, Path, description = "Poll id")),
+    responses(
+        (status = 200, description = "State of the poll as the user sees it", body = PollRes),
+        (status = 403, description = "User is banned from the poll", body = ApiError),
+        (status = 404, description = "Poll doesn't exist", body = ApiError),
+    ),
+    security((), ("token" = []))
+)]
+pub async fn get_poll(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+) -> Result<Json<PollRes>, ApiError> {
+    let (poll, token) = enter_poll(&state, &poll_id, user)?;
+    let ws_token = views::ws_token(&state.token_secret, &poll_id, &token);
+    let state = poll.lock().unwrap().get_state(&token);
+    Ok(Json(PollRes {
+        token,
+        ws_token,
+        state,
+    }))
+}
+
+#[derive(Deserialize, Clone, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListItemsReq {
+    // first question if this is missing
+    #[serde(default)]
+    question_id: usize,
+    #[serde(default)]
+    offset: usize,
+    // at most 100; 20 if this is missing
+    #[serde(default = "anket_shared::protocol::default_item_page_size")]
+    limit: usize,
+    #[serde(default)]
+    order: models::ItemOrder,
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{id}/items",
+    params(("id" = String, Path, description = "Poll id"), ListItemsReq),
+    responses(
+        (status = 200, description = "A page of every item of the question", body = models::ItemPage),
+        (status = 403, description = "User is banned from the poll", body = ApiError),
+        (status = 404, description = "Poll or question doesn't exist", body = ApiError),
+    ),
+    security((), ("token" = []))
+)]
+pub async fn list_items(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+    query: Result<Query<ListItemsReq>, QueryRejection>,
+) -> Result<Json<models::ItemPage>, ApiError> {
+    let Query(query) = query?;
+    let (poll, user_id) = enter_poll(&state, &poll_id, user)?;
+    let page = poll.lock().unwrap().list_items(
+        &user_id,
+        query.question_id,
+        query.offset,
+        query.limit,
+        query.order,
+    )?;
+    Ok(Json(page))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct AddItemReq {
+    text: String,
+    // items are added to the first question if this is missing
+    #[serde(default)]
+    question_id: usize,
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct ItemCreated {
+    item_id: usize,
+    // item is only listed after poll owner approves it
+    pending: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{id}/items",
+    params(("id" = String, Path, description = "Poll id")),
+    request_body = AddItemReq,
+    responses(
+        (status = 201, description = "Item is added", body = ItemCreated),
+        (status = 403, description = "User can't add items to the poll", body = ApiError),
+        (status = 404, description = "Poll or question doesn't exist", body = ApiError),
+        (status = 409, description = "Poll is closed or full", body = ApiError),
+        (status = 422, description = "Item text is rejected", body = ApiError),
+    ),
+    security((), ("token" = []))
+)]
+pub async fn add_item(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+    req: Result<Json<AddItemReq>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(req) = req?;
+    // referenced polls are locked here; so this must be done before locking this poll
+    let references = models::resolve_references(&state.polls, &req.text);
+    let (poll, user_id) = enter_poll(&state, &poll_id, user)?;
+    let mut poll = poll.lock().unwrap();
+    let item_id = poll.add_item(user_id, req.question_id, req.text, references)?;
+    let pending = poll.is_pending(item_id);
+    Ok((StatusCode::CREATED, Json(ItemCreated { item_id, pending })).into_response())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct VoteReq {
+    // zero takes the vote back
+    value: isize,
+}
+
+#[utoipa::path(
+    put,
+    path = "/polls/{id}/items/{item_id}/vote",
+    params(
+        ("id" = String, Path, description = "Poll id"),
+        ("item_id" = usize, Path, description = "Item id"),
+    ),
+    request_body = VoteReq,
+    responses(
+        (status = 204, description = "Vote is cast"),
+        (status = 403, description = "User is banned from the poll", body = ApiError),
+        (status = 404, description = "Poll or item doesn't exist", body = ApiError),
+        (status = 409, description = "Poll is closed or user can't vote more", body = ApiError),
+        (status = 422, description = "Vote value is invalid", body = ApiError),
+        (status = 429, description = "User votes too fast", body = ApiError),
+    ),
+    security((), ("token" = []))
+)]
+pub async fn vote_item(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path((poll_id, item_id)): Path<(String, usize)>,
+    req: Result<Json<VoteReq>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Json(req) = req?;
+    let (poll, user_id) = enter_poll(&state, &poll_id, user)?;
+    poll.lock()
+        .unwrap()
+        .vote_item(user_id, item_id, req.value)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{id}/close",
+    params(("id" = String, Path, description = "Poll id")),
+    responses(
+        (status = 204, description = "Poll is closed"),
+        (status = 403, description = "User is not the poll owner", body = ApiError),
+        (status = 404, description = "Poll doesn't exist", body = ApiError),
+        (status = 409, description = "Poll is already closed", body = ApiError),
+    ),
+    security(("token" = []))
+)]
+pub async fn close_poll(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let (poll, user_id) = enter_poll(&state, &poll_id, user)?;
+    poll.lock().unwrap().close_by(user_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_document_lists_every_route() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let paths = document["paths"].as_object().unwrap();
+        for path in [
+            "/polls",
+            "/polls/{id}",
+            "/polls/{id}/items",
+            "/polls/{id}/items/{item_id}/vote",
+            "/polls/{id}/close",
+        ] {
+            assert!(paths.contains_key(path), "{} is not documented", path);
+        }
+        assert!(paths["/polls/{id}/items"]["get"].is_object());
+        let schemas = &document["components"]["schemas"];
+        assert!(schemas["PollSettings"]["properties"]["vote_mode"].is_object());
+        assert!(document["components"]["securitySchemes"]["token"].is_object());
+    }
+}
@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Release {
+    pub version: &'static str,
+    pub changes: &'static [&'static str],
+}
+
+// newest release comes first; frontend stops reading when it reaches the version it has seen
+pub const CHANGELOG: &[Release] = &[
+    Release {
+        version: "0.3.0",
+        changes: &[
+            "Polls can use approval, quadratic or star rating votes, and rank items by score, by recent votes or by confidence.",
+            "Polls can have several questions, sections, a description and pinned items.",
+            "Poll owners can hide scores until the poll closes, shuffle items and limit votes and items of participants.",
+            "Items can be edited, deleted, merged, commented on, reacted to and translated.",
+            "Items can have images, markdown, link previews and references to items of other polls.",
+            "Similar items are suggested while an item is typed, and unsent drafts are kept.",
+            "Votes can be undone, and poll owners can reset every vote or weigh votes of participants.",
+            "Poll owners can moderate new items, review reports, and shadow ban, kick or ban participants.",
+            "Polls keep an audit log, and their data can be exported or kept anonymized after they close.",
+            "Results are served as standings, badges, an Atom feed and public state.",
+            "Polls can be used through a JSON API, GraphQL, gRPC, Server-Sent Events or long polling.",
+            "A Rust client is available for bots and integrations.",
+            "Changes are sent as soon as they happen, and only the parts of the poll that changed.",
+        ],
+    },
+    Release {
+        version: "0.2.0",
+        changes: &[
+            "Poll owners can choose who is allowed to add items.",
+            "Users can be identified by their session or by their IP address.",
+        ],
+    },
+];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub changelog: &'static [Release],
+}
+
+pub fn version_info() -> VersionInfo {
+    VersionInfo {
+        version: VERSION,
+        changelog: CHANGELOG,
+    }
+}
@@ -0,0 +1,49 @@
+use std::net::SocketAddr;
+use thiserror::Error;
+
+// everything that stops anket from starting or serving; each kind exits with its own code
+#[derive(Debug, Error)]
+pub enum AnketError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("couldn't listen on {addr}: {source}")]
+    Bind {
+        addr: SocketAddr,
+        source: hyper::Error,
+    },
+    #[error("server stopped unexpectedly: {0}")]
+    Serve(hyper::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC server stopped: {0}")]
+    Grpc(tonic::transport::Error),
+}
+
+impl AnketError {
+    // codes follow sysexits.h; so service managers can tell configuration mistakes apart
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AnketError::Config(_) => 78,   // EX_CONFIG
+            AnketError::Bind { .. } => 69, // EX_UNAVAILABLE
+            AnketError::Serve(_) => 70,    // EX_SOFTWARE
+            #[cfg(feature = "grpc")]
+            AnketError::Grpc(_) => 70,
+        }
+    }
+}
+
+// a setting that has an invalid value; `name` is the environment variable
+#[derive(Debug, Error)]
+#[error("{name} {problem}")]
+pub struct ConfigError {
+    pub name: &'static str,
+    pub problem: String,
+}
+
+impl ConfigError {
+    pub fn new(name: &'static str, problem: impl Into<String>) -> Self {
+        Self {
+            name,
+            problem: problem.into(),
+        }
+    }
+}
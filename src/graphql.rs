@@ -0,0 +1,314 @@
+use crate::{models, views, AppState};
+
+use async_graphql::{
+    http::{WebSocket, WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS},
+    Context, Data, Enum, Object, Schema, SimpleObject, Subscription,
+};
+use axum::{
+    extract::{ws, Extension, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{future, sink::SinkExt, stream::Stream, StreamExt};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// GraphQL schema of polls; served at `/api/graphql`, subscriptions at `/api/graphql/ws`.
+///
+/// Users are identified like on the JSON API, by `Authorization: Bearer <token>` headers; tokens
+/// are returned by `createPoll` and `poll` queries. Resolvers expect `AppState` and
+/// `UserDetails` of the requester in the request data.
+pub type AnketSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub fn schema() -> AnketSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot).finish()
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "models::VoteMode")]
+pub enum VoteMode {
+    UpDown,
+    Approval,
+    Quadratic,
+    Rating,
+}
+
+#[derive(SimpleObject)]
+pub struct Item {
+    id: usize,
+    question_id: usize,
+    text: String,
+    // only available if poll shows authors
+    author: Option<String>,
+    is_author: bool,
+    // unix timestamp
+    created_at: i64,
+    // `None` when results are hidden from the user
+    score: Option<isize>,
+    vote_count: Option<usize>,
+    user_vote: isize,
+    comment_count: usize,
+}
+
+impl From<models::ItemState> for Item {
+    fn from(item: models::ItemState) -> Self {
+        Self {
+            id: item.id,
+            question_id: item.question_id,
+            text: item.text,
+            author: item.author,
+            is_author: item.is_author,
+            created_at: item.created_at,
+            score: item.score,
+            vote_count: item.stats.map(|stats| stats.vote_count),
+            user_vote: item.user_vote,
+            comment_count: item.comment_count,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Question {
+    id: usize,
+    title: String,
+    vote_mode: VoteMode,
+    top_items: Vec<Item>,
+    // newest first
+    latest_items: Vec<Item>,
+}
+
+// poll as the requesting user sees it
+#[derive(SimpleObject)]
+pub struct Poll {
+    id: String,
+    // increases on every change of the poll
+    version: u64,
+    // send this back as a bearer token to act as the same user
+    token: Uuid,
+    title: String,
+    description: Option<String>,
+    closed: bool,
+    is_owner: bool,
+    questions: Vec<Question>,
+    pinned_items: Vec<Item>,
+    user_items: Vec<Item>,
+    // only available to poll owner
+    pending_items: Vec<Item>,
+}
+
+impl Poll {
+    fn new(poll: &models::Poll, user_id: Uuid) -> Self {
+        let state = poll.get_state(&user_id);
+        let items = |items: Vec<models::ItemState>| items.into_iter().map(Item::from).collect();
+        Self {
+            id: poll.get_id().to_owned(),
+            version: poll.version(),
+            token: user_id,
+            title: state.meta.poll_title,
+            description: state.meta.description,
+            closed: state.meta.closed,
+            is_owner: state.meta.is_owner,
+            questions: state
+                .questions
+                .into_iter()
+                .map(|question| Question {
+                    id: question.id,
+                    title: question.title,
+                    vote_mode: question.vote_mode.into(),
+                    top_items: items(question.top_items),
+                    latest_items: items(question.latest_items),
+                })
+                .collect(),
+            pinned_items: items(state.pinned_items),
+            user_items: items(state.user_items),
+            pending_items: items(state.pending_items),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CreatedPoll {
+    poll_id: String,
+    // token of the owner
+    token: Uuid,
+}
+
+// poll and the requesting user in it; users are created on their first request
+fn enter_poll(
+    ctx: &Context<'_>,
+    poll_id: &str,
+) -> async_graphql::Result<(Arc<Mutex<models::Poll>>, Uuid)> {
+    let state = ctx.data::<AppState>()?;
+    let user = ctx.data::<models::UserDetails>()?.clone();
+    let poll = state
+        .polls
+        .lock()
+        .unwrap()
+        .get_poll(poll_id)
+        .ok_or("No such poll exists.")?;
+    let user_id = poll.lock().unwrap().enter(user)?;
+    Ok((poll, user_id))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn poll(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Poll> {
+        let (poll, user_id) = enter_poll(ctx, &id)?;
+        let poll = Poll::new(&poll.lock().unwrap(), user_id);
+        Ok(poll)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    // settings are same as the ones of the JSON API
+    async fn create_poll(
+        &self,
+        ctx: &Context<'_>,
+        settings: async_graphql::Json<models::PollSettings>,
+    ) -> async_graphql::Result<CreatedPoll> {
+        let state = ctx.data::<AppState>()?;
+        let user = ctx.data::<models::UserDetails>()?.clone();
+        let mut settings = settings.0;
+        if settings.max_item_length == 0 {
+            settings.max_item_length = state.config.max_item_length;
+        }
+        if let Some(err) = views::validate_poll_settings(&settings, &state.config).first() {
+            return Err(serde_json::to_string(err)?.into());
+        }
+        let (token, poll) = state.polls.lock().unwrap().add_poll(settings, user);
+        let poll_id = poll.lock().unwrap().get_id().to_owned();
+        Ok(CreatedPoll { poll_id, token })
+    }
+
+    // returns id of the new item
+    async fn add_item(
+        &self,
+        ctx: &Context<'_>,
+        poll_id: String,
+        text: String,
+        #[graphql(default)] question_id: usize,
+    ) -> async_graphql::Result<usize> {
+        // referenced polls are locked here; so this must be done before locking this poll
+        let references = models::resolve_references(&ctx.data::<AppState>()?.polls, &text);
+        let (poll, user_id) = enter_poll(ctx, &poll_id)?;
+        let item_id = poll
+            .lock()
+            .unwrap()
+            .add_item(user_id, question_id, text, references)?;
+        Ok(item_id)
+    }
+
+    // zero `value` takes the vote back
+    async fn vote(
+        &self,
+        ctx: &Context<'_>,
+        poll_id: String,
+        item_id: usize,
+        value: isize,
+    ) -> async_graphql::Result<bool> {
+        let (poll, user_id) = enter_poll(ctx, &poll_id)?;
+        poll.lock().unwrap().vote_item(user_id, item_id, value)?;
+        Ok(true)
+    }
+
+    async fn close_poll(&self, ctx: &Context<'_>, poll_id: String) -> async_graphql::Result<bool> {
+        let (poll, user_id) = enter_poll(ctx, &poll_id)?;
+        poll.lock().unwrap().close_by(user_id)?;
+        Ok(true)
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    // current poll first, then the poll after every change; ends when the poll closes
+    async fn poll(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> async_graphql::Result<impl Stream<Item = Poll>> {
+        let (poll, user_id) = enter_poll(ctx, &id)?;
+        let user = ctx.data::<models::UserDetails>()?.clone();
+        let (_, feed) = poll.lock().unwrap().join(user)?;
+        // a poll is sent for every update; slow subscribers skip to the latest one
+        Ok(futures_util::stream::unfold(
+            (poll, feed),
+            move |(poll, mut feed)| async move {
+                feed.next().await?;
+                let current = Poll::new(&poll.lock().unwrap(), user_id);
+                Some((current, (poll, feed)))
+            },
+        ))
+    }
+}
+
+pub async fn graphql(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Json(request): Json<async_graphql::Request>,
+) -> Response {
+    let request = request.data(state.clone()).data(user);
+    Json(state.graphql.execute(request).await).into_response()
+}
+
+// subscriptions, over `graphql-transport-ws` or the older `graphql-ws` protocol
+pub async fn graphql_ws(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    headers: HeaderMap,
+    ws: ws::WebSocketUpgrade,
+) -> Response {
+    let protocol = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| {
+            protocols
+                .split(',')
+                .find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok())
+        });
+    let Some(protocol) = protocol else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Use graphql-transport-ws or graphql-ws subprotocol.",
+        )
+            .into_response();
+    };
+
+    ws.protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| async move {
+            let (mut ws_sender, ws_receiver) = socket.split();
+            let input = ws_receiver
+                .take_while(|message| future::ready(message.is_ok()))
+                .filter_map(|message| {
+                    future::ready(match message {
+                        Ok(ws::Message::Text(text)) => Some(text),
+                        _ => None,
+                    })
+                });
+            let mut data = Data::default();
+            data.insert(state.clone());
+            data.insert(user);
+            let mut messages =
+                WebSocket::new(state.graphql.clone(), input, protocol).connection_data(data);
+            while let Some(message) = messages.next().await {
+                let message = match message {
+                    WsMessage::Text(text) => ws::Message::Text(text),
+                    WsMessage::Close(code, reason) => ws::Message::Close(Some(ws::CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    })),
+                };
+                if ws_sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        })
+}
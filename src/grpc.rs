@@ -0,0 +1,257 @@
+// `Status` is what every RPC fails with; helpers return it as is instead of boxing it
+#![allow(clippy::result_large_err)]
+
+use crate::{error::AnketError, models, views, AppState};
+
+use futures_util::stream::Stream;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+pub mod proto {
+    tonic::include_proto!("anket.v1");
+}
+
+use proto::anket_server::{Anket, AnketServer};
+
+/// Serves the gRPC API of `proto/anket.proto` on its own address, until anket shuts down.
+///
+/// Polls are the same ones that are served over HTTP; so changes made here are seen by
+/// browsers and the other way around.
+pub async fn serve(addr: SocketAddr, state: AppState) -> Result<(), AnketError> {
+    tonic::transport::Server::builder()
+        .add_service(AnketServer::new(AnketService { state }))
+        .serve_with_shutdown(addr, crate::shutdown_signal())
+        .await
+        .map_err(AnketError::Grpc)
+}
+
+struct AnketService {
+    state: AppState,
+}
+
+// requests come from other backends directly; so forwarded headers aren't trusted here
+fn user_details<T>(request: &Request<T>) -> models::UserDetails {
+    let ip = request
+        .remote_addr()
+        .map(|addr| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let id = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| Uuid::from_str(token.trim()).ok());
+    models::UserDetails { ip, id }
+}
+
+// poll of the request and the user that sent it; users are created on their first request
+fn enter_poll(
+    state: &AppState,
+    poll_id: &str,
+    user: models::UserDetails,
+) -> Result<(Arc<Mutex<models::Poll>>, Uuid), Status> {
+    let poll = state
+        .polls
+        .lock()
+        .unwrap()
+        .get_poll(poll_id)
+        .ok_or_else(|| Status::not_found("No such poll exists."))?;
+    let user_id = poll.lock().unwrap().enter(user)?;
+    Ok((poll, user_id))
+}
+
+fn poll_settings(req: proto::CreatePollRequest) -> Result<models::PollSettings, Status> {
+    let user_lookup_method = match req.user_lookup_method() {
+        proto::UserLookupMethod::SessionBased => "SessionBased",
+        proto::UserLookupMethod::IpBased => "IPBased",
+    };
+    let add_item_permit = match req.add_item_permit() {
+        proto::AddItemPermit::Anyone => "Anyone",
+        proto::AddItemPermit::OwnerOnly => "OwnerOnly",
+    };
+    let vote_mode = match req.vote_mode() {
+        proto::VoteMode::UpDown => "UpDown",
+        proto::VoteMode::Approval => "Approval",
+        proto::VoteMode::Quadratic => "Quadratic",
+        proto::VoteMode::Rating => "Rating",
+    };
+    let retention_policy = match req.retention_policy() {
+        proto::RetentionPolicy::Keep => "Keep",
+        proto::RetentionPolicy::Anonymize => "Anonymize",
+        proto::RetentionPolicy::Purge => "Purge",
+    };
+    // settings are built from JSON, so the missing ones get the same defaults as on HTTP
+    serde_json::from_value(serde_json::json!({
+        "title": req.title,
+        "description": req.description,
+        "user_lookup_method": user_lookup_method,
+        "add_item_permit": add_item_permit,
+        "vote_mode": vote_mode,
+        "retention_policy": retention_policy,
+        "retention_days": req.retention_days,
+        "vote_credits": req.vote_credits,
+    }))
+    .map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+fn vote_mode(vote_mode: models::VoteMode) -> proto::VoteMode {
+    match vote_mode {
+        models::VoteMode::UpDown => proto::VoteMode::UpDown,
+        models::VoteMode::Approval => proto::VoteMode::Approval,
+        models::VoteMode::Quadratic => proto::VoteMode::Quadratic,
+        models::VoteMode::Rating => proto::VoteMode::Rating,
+    }
+}
+
+fn snapshot(poll: &models::Poll, user_id: Uuid) -> proto::PollSnapshot {
+    let state = poll.get_state(&user_id);
+    proto::PollSnapshot {
+        poll_id: poll.get_id().to_owned(),
+        version: poll.version(),
+        token: user_id.to_string(),
+        title: state.meta.poll_title,
+        closed: state.meta.closed,
+        questions: state
+            .questions
+            .into_iter()
+            .map(|question| proto::Question {
+                id: question.id as u64,
+                title: question.title,
+                vote_mode: vote_mode(question.vote_mode).into(),
+                top_items: question
+                    .top_items
+                    .into_iter()
+                    .map(|item| proto::Item {
+                        id: item.id as u64,
+                        text: item.text,
+                        created_at: item.created_at,
+                        score: item.score.map(|score| score as i64),
+                        user_vote: item.user_vote as i64,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl Anket for AnketService {
+    async fn create_poll(
+        &self,
+        request: Request<proto::CreatePollRequest>,
+    ) -> Result<Response<proto::CreatePollResponse>, Status> {
+        let user = user_details(&request);
+        let mut settings = poll_settings(request.into_inner())?;
+        if settings.max_item_length == 0 {
+            settings.max_item_length = self.state.config.max_item_length;
+        }
+        if let Some(err) = views::validate_poll_settings(&settings, &self.state.config).first() {
+            return Err(Status::invalid_argument(format!(
+                "{}: {}",
+                err.field, err.message
+            )));
+        }
+        let (token, poll) = self.state.polls.lock().unwrap().add_poll(settings, user);
+        let poll_id = poll.lock().unwrap().get_id().to_owned();
+        Ok(Response::new(proto::CreatePollResponse {
+            poll_id,
+            token: token.to_string(),
+        }))
+    }
+
+    async fn add_item(
+        &self,
+        request: Request<proto::AddItemRequest>,
+    ) -> Result<Response<proto::AddItemResponse>, Status> {
+        let user = user_details(&request);
+        let req = request.into_inner();
+        // referenced polls are locked here; so this must be done before locking this poll
+        let references = models::resolve_references(&self.state.polls, &req.text);
+        let (poll, user_id) = enter_poll(&self.state, &req.poll_id, user)?;
+        let mut poll = poll.lock().unwrap();
+        let item_id = poll.add_item(user_id, req.question_id as usize, req.text, references)?;
+        Ok(Response::new(proto::AddItemResponse {
+            item_id: item_id as u64,
+            pending: poll.is_pending(item_id),
+            token: user_id.to_string(),
+        }))
+    }
+
+    async fn vote(
+        &self,
+        request: Request<proto::VoteRequest>,
+    ) -> Result<Response<proto::VoteResponse>, Status> {
+        let user = user_details(&request);
+        let req = request.into_inner();
+        let (poll, user_id) = enter_poll(&self.state, &req.poll_id, user)?;
+        poll.lock()
+            .unwrap()
+            .vote_item(user_id, req.item_id as usize, req.value as isize)?;
+        Ok(Response::new(proto::VoteResponse {
+            token: user_id.to_string(),
+        }))
+    }
+
+    type WatchPollStream = Pin<Box<dyn Stream<Item = Result<proto::PollSnapshot, Status>> + Send>>;
+
+    async fn watch_poll(
+        &self,
+        request: Request<proto::WatchPollRequest>,
+    ) -> Result<Response<Self::WatchPollStream>, Status> {
+        let user = user_details(&request);
+        let (poll, user_id) = enter_poll(&self.state, &request.get_ref().poll_id, user.clone())?;
+        let (_, feed) = poll.lock().unwrap().join(user)?;
+        // a snapshot is sent for every update; slow streams skip to the latest one
+        let snapshots =
+            futures_util::stream::unfold((poll, feed), move |(poll, mut feed)| async move {
+                feed.next().await?;
+                let current = snapshot(&poll.lock().unwrap(), user_id);
+                Some((Ok(current), (poll, feed)))
+            });
+        Ok(Response::new(Box::pin(snapshots)))
+    }
+}
+
+impl From<models::UserCreateError> for Status {
+    fn from(err: models::UserCreateError) -> Self {
+        match err {
+            models::UserCreateError::Banned => Status::permission_denied(err.to_string()),
+            models::UserCreateError::UserAlreadyExists => Status::already_exists(err.to_string()),
+        }
+    }
+}
+
+impl From<models::AddPollItemError> for Status {
+    fn from(err: models::AddPollItemError) -> Self {
+        use models::AddPollItemError::*;
+        let message = err.to_string();
+        match err {
+            NotOwner | Banned => Status::permission_denied(message),
+            QuestionNotFound | AttachmentNotFound | SectionNotFound => Status::not_found(message),
+            PollClosed | AttachmentInUse | ItemLimitReached | PollFull => {
+                Status::failed_precondition(message)
+            }
+            TooShort(_) | TooLong(_) | Rejected => Status::invalid_argument(message),
+        }
+    }
+}
+
+impl From<models::VotePollItemError> for Status {
+    fn from(err: models::VotePollItemError) -> Self {
+        use models::VotePollItemError::*;
+        let message = err.to_string();
+        match err {
+            Banned => Status::permission_denied(message),
+            ItemNotFound => Status::not_found(message),
+            PollClosed | NotEnoughCredits | VoteLimitReached | DuplicateVote => {
+                Status::failed_precondition(message)
+            }
+            VoteCooldown => Status::resource_exhausted(message),
+            InvalidValue => Status::invalid_argument(message),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::utils::{constant_time_eq, hmac_sha1, to_hex};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+// requests are signed as `sha1=<hex encoded HMAC-SHA1 of body>` with the secret of poll
+pub const SIGNATURE_HEADER: &str = "x-anket-signature";
+const SIGNATURE_PREFIX: &str = "sha1=";
+const SECRET_LENGTH: usize = 20;
+
+// an action of a user on an external system like an SMS gateway or a chat bot
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IngestRequest {
+    // name of the external system; same user ids of different sources are different users
+    pub source: String,
+    // id of the user on the external system
+    pub user: String,
+    pub action: IngestAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "content")]
+pub enum IngestAction {
+    // items are added to the first question if `question_id` is missing
+    AddItem {
+        text: String,
+        #[serde(default)]
+        question_id: usize,
+    },
+    VoteItem {
+        item_id: usize,
+        vote: isize,
+    },
+}
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LENGTH];
+    rand::thread_rng().fill_bytes(&mut secret);
+    to_hex(&secret)
+}
+
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    format!("{}{}", SIGNATURE_PREFIX, to_hex(&hmac_sha1(secret, body)))
+}
+
+pub fn verify(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    constant_time_eq(&sign(secret, body), signature)
+}
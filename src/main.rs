@@ -1,38 +1,98 @@
+mod api;
+mod changelog;
+mod error;
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod ingest;
 mod models;
+mod preview;
+#[cfg(test)]
+mod test_support;
+mod translate;
 mod utils;
 mod views;
 
-use axum::{middleware, routing};
-use std::borrow::Borrow;
+use axum::{extract::DefaultBodyLimit, middleware, routing};
+use error::{AnketError, ConfigError};
 use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::{self, signal};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 pub const SESSION_KEY: &str = "anket_session";
+pub const RECENT_POLLS_KEY: &str = "anket_recent_polls";
+pub const NODE_KEY: &str = "anket_node";
+pub const NODE_HEADER: &str = "x-anket-node";
+pub const RECENT_POLLS_COUNT: usize = 10;
 pub const SESSION_DURATION: cookie::time::Duration = cookie::time::Duration::weeks(52);
+pub const MIN_TITLE_LENGTH: usize = 3;
 
 #[derive(Clone)]
 pub struct AppState {
     config: Arc<AppConfig>,
+    urls: utils::Urls,
     polls: Arc<Mutex<models::Polls>>,
+    translator: Option<Arc<translate::Translator>>,
     templates: minijinja::Environment<'static>,
+    graphql: graphql::AnketSchema,
+    // signs websocket tokens; polls only live in memory, so tokens don't need to outlive anket
+    token_secret: Arc<str>,
 }
 
 impl AppState {
     fn init(config: AppConfig) -> Self {
-        let polls = models::Polls::new();
+        let content_filter = config
+            .word_list
+            .clone()
+            .map(|list| Arc::new(list) as Arc<dyn models::ContentFilter>);
+        let polls = models::Polls::new(config.vote_cooldown, content_filter);
+        polls
+            .lock()
+            .unwrap()
+            .set_broadcast_delay(config.broadcast_delay);
+        if !config.preview_hosts.is_empty() {
+            let previewer = preview::LinkPreviewer::new(config.preview_hosts.clone());
+            polls
+                .lock()
+                .unwrap()
+                .set_link_previewer(Arc::new(previewer));
+        }
+        let urls = utils::Urls::new(config.base_path.clone());
+        let translator = config
+            .translate_url
+            .clone()
+            .map(|url| Arc::new(translate::Translator::new(url)));
         let templates = {
             let mut env = minijinja::Environment::new();
+            // `.jinja` templates are HTML pages; they render user provided text like poll titles
+            env.set_auto_escape_callback(|name| {
+                if name.ends_with(".jinja") {
+                    minijinja::AutoEscape::Html
+                } else {
+                    minijinja::default_auto_escape_callback(name)
+                }
+            });
             minijinja_embed::load_templates!(&mut env);
+            // templates build every URL with these; so they work under a sub-path as well
+            env.add_global("base_path", urls.base_path());
+            let template_urls = urls.clone();
+            env.add_function("url", move |path: String| template_urls.path(&path));
             env
         };
 
         Self {
             config: Arc::new(config),
+            urls,
             polls,
+            translator,
             templates,
+            graphql: graphql::schema(),
+            token_secret: ingest::generate_secret().into(),
         }
     }
 }
@@ -41,24 +101,255 @@ impl AppState {
 struct AppConfig {
     bind_addr: SocketAddr,
     secure: bool,
+    max_title_length: usize,
+    // in characters
+    max_description_length: usize,
+    // polls can't accept items longer than this; also the default limit of new polls
+    max_item_length: usize,
+    // images bigger than this many bytes can't be attached to items
+    max_attachment_size: usize,
+    announcement: Option<String>,
+    vote_cooldown: Duration,
+    // changes of polls are broadcast together in this long; polls can set their own
+    broadcast_delay: Duration,
+    // items and comments are checked against these words; `None` when nothing is filtered
+    word_list: Option<models::WordList>,
+    // identifier of this instance; load balancers route requests of a poll by this
+    node_id: Option<String>,
+    // LibreTranslate compatible endpoint that items are translated by
+    translate_url: Option<hyper::Uri>,
+    // links to these domains and their subdomains are previewed; empty when nothing is
+    preview_hosts: Vec<String>,
+    // path prefix that anket is served under; empty when it's served from root
+    base_path: String,
+    // `/api/docs` shows Swagger UI; otherwise it redirects to the OpenAPI document
+    swagger_ui: bool,
+    // large websocket messages are compressed for clients that ask for it
+    ws_compression: bool,
+    // websocket clients are pinged this often; `None` when they aren't
+    ws_ping_interval: Option<Duration>,
+    // connections are dropped when this many pings in a row are not answered
+    ws_max_missed_pongs: u32,
+    // gRPC API is served on this address too; `None` when it isn't served
+    grpc_addr: Option<SocketAddr>,
+}
+
+// value of the environment variable; unset and blank values are `None`
+fn env_var(name: &'static str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn parse_env<T: FromStr>(
+    name: &'static str,
+    default: &str,
+    expected: &str,
+) -> Result<T, ConfigError> {
+    let value = env_var(name).unwrap_or_else(|| default.to_owned());
+    value
+        .parse::<T>()
+        .map_err(|_| ConfigError::new(name, format!("must be {}, got {:?}", expected, value)))
 }
 
-fn get_config() -> AppConfig {
-    let bind_addr = std::env::var("ANKET_LISTEN")
-        .unwrap_or_else(|_| "0.0.0.0:3000".into())
-        .parse::<SocketAddr>()
-        .expect("ANKET_LISTEN is not a valid socket address");
-
-    let secure = match std::env::var("ANKET_SECURE")
-        .unwrap_or_else(|_| "0".into())
-        .borrow()
-    {
-        "0" => false,
-        "1" => true,
-        _ => panic!("ANKET_SECURE can be 0 or 1"),
+fn get_config() -> Result<AppConfig, ConfigError> {
+    let bind_addr = parse_env::<SocketAddr>(
+        "ANKET_LISTEN",
+        "0.0.0.0:3000",
+        "a socket address like 0.0.0.0:3000",
+    )?;
+
+    let secure = match env_var("ANKET_SECURE").as_deref() {
+        None | Some("0") => false,
+        Some("1") => true,
+        Some(value) => {
+            return Err(ConfigError::new(
+                "ANKET_SECURE",
+                format!("must be 0 or 1, got {:?}", value),
+            ))
+        }
+    };
+
+    let max_title_length = parse_env::<usize>("ANKET_MAX_TITLE_LENGTH", "200", "a number")?;
+    if max_title_length < MIN_TITLE_LENGTH {
+        return Err(ConfigError::new(
+            "ANKET_MAX_TITLE_LENGTH",
+            format!("can't be less than {}", MIN_TITLE_LENGTH),
+        ));
+    }
+
+    let max_description_length =
+        parse_env::<usize>("ANKET_MAX_DESCRIPTION_LENGTH", "2000", "a number")?;
+
+    let max_item_length = parse_env::<usize>("ANKET_MAX_ITEM_LENGTH", "300", "a number")?;
+    if max_item_length == 0 {
+        return Err(ConfigError::new(
+            "ANKET_MAX_ITEM_LENGTH",
+            "must be greater than 0",
+        ));
+    }
+
+    let max_attachment_size =
+        parse_env::<usize>("ANKET_MAX_ATTACHMENT_SIZE", "2097152", "a number of bytes")?;
+    if max_attachment_size == 0 {
+        return Err(ConfigError::new(
+            "ANKET_MAX_ATTACHMENT_SIZE",
+            "must be greater than 0",
+        ));
+    }
+
+    let announcement = env_var("ANKET_ANNOUNCEMENT");
+
+    let vote_cooldown = parse_env::<u64>("ANKET_VOTE_COOLDOWN_MS", "1000", "a number")
+        .map(Duration::from_millis)?;
+
+    let broadcast_delay = parse_env::<u64>("ANKET_BROADCAST_DELAY_MS", "100", "a number")
+        .map(Duration::from_millis)?;
+
+    let word_list = match env_var("ANKET_WORD_LIST") {
+        Some(path) => {
+            let action = parse_env::<models::FilterAction>(
+                "ANKET_WORD_LIST_ACTION",
+                "mask",
+                "mask or reject",
+            )?;
+            let list = std::fs::read_to_string(&path).map_err(|err| {
+                ConfigError::new("ANKET_WORD_LIST", format!("can't be read: {}", err))
+            })?;
+            let list = models::WordList::parse(&list, action);
+            if list.is_empty() {
+                return Err(ConfigError::new(
+                    "ANKET_WORD_LIST",
+                    "doesn't contain any words",
+                ));
+            }
+            Some(list)
+        }
+        None => None,
     };
 
-    AppConfig { bind_addr, secure }
+    let node_id = env_var("ANKET_NODE_ID");
+    if let Some(node_id) = &node_id {
+        if !node_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ConfigError::new(
+                "ANKET_NODE_ID",
+                "can only contain ASCII letters, digits, '-' and '_'",
+            ));
+        }
+    }
+
+    let translate_url = match env_var("ANKET_TRANSLATE_URL") {
+        Some(_) => Some(parse_env::<hyper::Uri>("ANKET_TRANSLATE_URL", "", "a URL")?),
+        None => None,
+    };
+
+    let preview_hosts: Vec<String> = env_var("ANKET_PREVIEW_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect();
+    if let Some(host) = preview_hosts.iter().find(|host| {
+        !host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    }) {
+        return Err(ConfigError::new(
+            "ANKET_PREVIEW_HOSTS",
+            format!("must be a comma separated list of domains, got {:?}", host),
+        ));
+    }
+
+    let base_path = env_var("ANKET_BASE_PATH")
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_owned();
+    let valid_base_path = base_path.is_empty()
+        || base_path.starts_with('/')
+            && base_path[1..].split('/').all(|segment| {
+                !segment.is_empty()
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            });
+    if !valid_base_path {
+        return Err(ConfigError::new(
+            "ANKET_BASE_PATH",
+            "must be a path like /anket, made of ASCII letters, digits, '-', '_' and '.'",
+        ));
+    }
+
+    let swagger_ui = match env_var("ANKET_SWAGGER_UI").as_deref() {
+        None | Some("1") => true,
+        Some("0") => false,
+        Some(value) => {
+            return Err(ConfigError::new(
+                "ANKET_SWAGGER_UI",
+                format!("must be 0 or 1, got {:?}", value),
+            ))
+        }
+    };
+
+    let ws_compression = match env_var("ANKET_WS_COMPRESSION").as_deref() {
+        None | Some("0") => false,
+        Some("1") => true,
+        Some(value) => {
+            return Err(ConfigError::new(
+                "ANKET_WS_COMPRESSION",
+                format!("must be 0 or 1, got {:?}", value),
+            ))
+        }
+    };
+
+    let ws_ping_interval = parse_env::<u64>("ANKET_WS_PING_INTERVAL", "30", "a number")
+        .map(|secs| (secs > 0).then(|| Duration::from_secs(secs)))?;
+    let ws_max_missed_pongs = parse_env::<u32>("ANKET_WS_MAX_MISSED_PONGS", "2", "a number")?;
+    if ws_max_missed_pongs == 0 {
+        return Err(ConfigError::new(
+            "ANKET_WS_MAX_MISSED_PONGS",
+            "must be greater than 0",
+        ));
+    }
+
+    let grpc_addr = match env_var("ANKET_GRPC_LISTEN") {
+        Some(_) if !cfg!(feature = "grpc") => {
+            return Err(ConfigError::new(
+                "ANKET_GRPC_LISTEN",
+                "needs anket to be built with the grpc feature",
+            ))
+        }
+        Some(_) => Some(parse_env::<SocketAddr>(
+            "ANKET_GRPC_LISTEN",
+            "",
+            "a socket address like 0.0.0.0:50051",
+        )?),
+        None => None,
+    };
+
+    Ok(AppConfig {
+        bind_addr,
+        secure,
+        max_title_length,
+        max_description_length,
+        max_item_length,
+        max_attachment_size,
+        announcement,
+        vote_cooldown,
+        broadcast_delay,
+        word_list,
+        node_id,
+        translate_url,
+        preview_hosts,
+        base_path,
+        swagger_ui,
+        ws_compression,
+        ws_ping_interval,
+        ws_max_missed_pongs,
+        grpc_addr,
+    })
 }
 
 async fn shutdown_signal() {
@@ -86,7 +377,7 @@ async fn shutdown_signal() {
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_env("ANKET_LOG")
@@ -95,8 +386,19 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let app_config = get_config();
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("anket: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn run() -> Result<(), AnketError> {
+    let app_config = get_config()?;
     let app_state = AppState::init(app_config.clone());
+    let poll_index = app_state.urls.path("/p");
 
     let routes = routing::Router::new()
         .route(
@@ -105,24 +407,86 @@ async fn main() {
         )
         .route("/p/:id", routing::get(views::get_poll))
         .route("/p/:id/ws", routing::get(views::join_poll))
+        .route("/p/:id/events", routing::get(views::poll_events))
+        .route("/p/:id/state", routing::get(views::poll_state))
+        .route("/api/graphql", routing::post(graphql::graphql))
+        .route("/api/graphql/ws", routing::get(graphql::graphql_ws))
+        .route("/p/:id/insights", routing::get(views::poll_insights))
+        .route("/p/:id/export", routing::get(views::poll_export))
+        .route("/p/:id/audit", routing::get(views::poll_audit_log))
+        .route("/p/:id/items", routing::post(views::add_poll_item))
+        .route(
+            "/p/:id/items/:item_id/vote",
+            routing::post(views::vote_poll_item),
+        )
+        .route(
+            "/p/:id/attachments",
+            routing::post(views::upload_attachment)
+                .layer(DefaultBodyLimit::max(app_config.max_attachment_size)),
+        )
         .route_layer(middleware::from_fn_with_state(
             app_state.clone(),
             views::identify_user,
         ))
+        .route("/p/:id/feed.atom", routing::get(views::poll_feed))
+        .route("/p/:id/result", routing::get(views::poll_result))
+        .route("/p/:id/state.json", routing::get(views::poll_public_state))
+        .route(
+            "/p/:id/attachments/:attachment_id",
+            routing::get(views::get_attachment),
+        )
+        .route("/p/:id/ingest", routing::post(views::poll_ingest))
+        .route("/p/:id/badge.svg", routing::get(views::poll_badge_svg))
+        .route("/p/:id/badge.json", routing::get(views::poll_badge_json))
         .route("/", routing::get(views::anket_index))
+        .route("/join", routing::get(views::join_by_code))
+        .route("/api/version", routing::get(views::api_version))
+        .route("/api/config", routing::get(views::client_config))
+        .nest("/api/v1", api::router(app_state.clone()))
+        .route("/api/docs", routing::get(api::api_docs))
+        .route(
+            "/api/docs/openapi.json",
+            routing::get(api::openapi_document),
+        )
+        .route("/time", routing::get(views::server_time))
         // TODO remove this and use tower-http layer
         .route(
             "/p/",
-            routing::get(|| async { axum::response::Redirect::temporary("/p") }),
+            routing::get(|| async move { axum::response::Redirect::temporary(&poll_index) }),
         )
-        .nest("/assets", views::assets_router(app_state.clone()))
+        .nest("/assets", views::assets_router(app_state.clone()));
+    let routes = if app_config.base_path.is_empty() {
+        routes
+    } else {
+        routing::Router::new().nest(&app_config.base_path, routes)
+    };
+    let routes = routes
         .fallback(views::handler_404)
-        .with_state(app_state);
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            views::node_header,
+        ))
+        .with_state(app_state.clone());
 
+    let server =
+        axum::Server::try_bind(&app_config.bind_addr).map_err(|source| AnketError::Bind {
+            addr: app_config.bind_addr,
+            source,
+        })?;
     info!("started on {}", &app_config.bind_addr);
-    axum::Server::bind(&app_config.bind_addr)
-        .serve(routes.into_make_service_with_connect_info::<SocketAddr>())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    let http = async {
+        server
+            .serve(routes.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            .map_err(AnketError::Serve)
+    };
+    match app_config.grpc_addr {
+        #[cfg(feature = "grpc")]
+        Some(grpc_addr) => {
+            info!("gRPC started on {}", grpc_addr);
+            tokio::try_join!(http, grpc::serve(grpc_addr, app_state)).map(|_| ())
+        }
+        _ => http.await,
+    }
 }
@@ -0,0 +1,67 @@
+use hyper::body::Bytes;
+use uuid::Uuid;
+
+// uploads are kept in memory with their polls; this keeps a single poll from taking too much
+pub const MAX_ATTACHMENTS_PER_POLL: usize = 50;
+
+// formats that every browser shows; type of an upload is decided by its content, not its header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageType {
+    // `None` if `data` doesn't start like any of the supported formats
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageType::Png)
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            Some(ImageType::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageType::Gif)
+        } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+            Some(ImageType::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageType::Png => "image/png",
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Gif => "image/gif",
+            ImageType::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub image_type: ImageType,
+    pub data: Bytes,
+    // uploader; only they can add an item with this attachment
+    pub user_id: Uuid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_detected_by_content() {
+        assert_eq!(
+            ImageType::detect(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some(ImageType::Png)
+        );
+        assert_eq!(
+            ImageType::detect(b"RIFF\x24\0\0\0WEBPVP8 "),
+            Some(ImageType::Webp)
+        );
+        assert_eq!(ImageType::detect(b"<svg onload=alert(1)>"), None);
+        assert_eq!(ImageType::detect(b"RIFF"), None);
+    }
+}
@@ -0,0 +1,55 @@
+use super::IdentityMapper;
+use cookie::time::OffsetDateTime;
+use uuid::Uuid;
+
+// what is changed; users that are involved are kept in the record, not in the event
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum AuditEvent {
+    ItemAdded { item_id: usize },
+    ItemEdited { item_id: usize },
+    ItemDeleted { item_id: usize },
+    ItemApproved { item_id: usize },
+    ItemRejected { item_id: usize },
+    ItemsMerged { item_id: usize, into: usize },
+    // zero `value` takes the vote back
+    VoteCast { item_id: usize, value: isize },
+    VotesReset,
+    UserWeightSet { weight: u32 },
+    UserShadowBanned { banned: bool },
+    UserKicked,
+    UserBanned { remove_content: bool },
+    PollClosed,
+}
+
+// a change in a poll; `actor` and `target` are session ids of users, so records are never
+// sent as they are
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub at: OffsetDateTime,
+    pub actor: Uuid,
+    // user that is affected, if the event is about a single user
+    pub target: Option<Uuid>,
+    pub event: AuditEvent,
+}
+
+impl AuditRecord {
+    pub fn export(&self, mapper: &mut dyn IdentityMapper) -> AuditEntry {
+        AuditEntry {
+            at: self.at.unix_timestamp(),
+            actor: mapper.identify(&self.actor),
+            target: self.target.map(|target| mapper.identify(&target)),
+            event: self.event.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    // unix timestamp
+    pub at: i64,
+    pub actor: String,
+    pub target: Option<String>,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Decides how users are identified in exported poll data.
+///
+/// Same user must get the same identifier within an export. User ids are also session tokens of
+/// users; so they must never be exported as is. Real identifiers can be exported once users have
+/// names or accounts.
+pub trait IdentityMapper {
+    fn identify(&mut self, user_id: &Uuid) -> String;
+}
+
+// users are numbered by their first appearance in the export; `Voter-1`, `Voter-2`...
+#[derive(Default)]
+pub struct Pseudonyms {
+    names: HashMap<Uuid, String>,
+}
+
+impl IdentityMapper for Pseudonyms {
+    fn identify(&mut self, user_id: &Uuid) -> String {
+        let next = self.names.len() + 1;
+        self.names
+            .entry(*user_id)
+            .or_insert_with(|| format!("Voter-{}", next))
+            .clone()
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PollExport {
+    pub poll_id: String,
+    pub title: String,
+    pub questions: Vec<QuestionExport>,
+    pub closed: bool,
+    pub items: Vec<ItemExport>,
+    // oldest change first
+    pub audit_log: Vec<super::AuditEntry>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct QuestionExport {
+    pub id: usize,
+    pub title: String,
+    pub vote_mode: super::VoteMode,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ItemExport {
+    pub id: usize,
+    pub question_id: usize,
+    pub text: String,
+    // unix timestamp
+    pub created_at: i64,
+    pub author: String,
+    pub score: isize,
+    pub votes: Vec<VoteExport>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct VoteExport {
+    pub voter: String,
+    pub value: isize,
+    pub weight: u32,
+}
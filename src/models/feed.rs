@@ -0,0 +1,269 @@
+use super::{PollEvent, Subscription};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+// where an event is kept in an update; sections are sent in this order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+    Meta,
+    // by question id
+    Question(usize),
+    PinnedItems,
+    UserItems,
+    PendingItems,
+    UserVotes,
+    ItemOverlay,
+    Nudge,
+}
+
+impl Slot {
+    fn of(event: &PollEvent) -> Option<Self> {
+        Some(match event {
+            PollEvent::Clock(_) => return None,
+            PollEvent::Meta(_) => Slot::Meta,
+            PollEvent::Question(question) => Slot::Question(question.id),
+            PollEvent::PinnedItems(_) => Slot::PinnedItems,
+            PollEvent::UserItems(_) => Slot::UserItems,
+            PollEvent::PendingItems(_) => Slot::PendingItems,
+            PollEvent::UserVotes(_) => Slot::UserVotes,
+            PollEvent::ItemOverlay(_) => Slot::ItemOverlay,
+            PollEvent::Nudge(_) => Slot::Nudge,
+        })
+    }
+}
+
+// latest state of a user as events, which the poll keeps in a watch channel for each view of the
+// user; sections that didn't change keep their events, so connections find what's changed since
+// the update they sent last by comparing events, and slow ones skip the updates in between
+#[derive(Debug, Default)]
+pub struct UserUpdate {
+    // clock of the broadcast that made this; `None` until the first one
+    clock: Option<Arc<PollEvent>>,
+    sections: BTreeMap<Slot, Arc<PollEvent>>,
+}
+
+impl UserUpdate {
+    // `None` when none of the events are different than the ones in this update
+    fn next(&self, clock: &Arc<PollEvent>, events: Vec<Arc<PollEvent>>) -> Option<Self> {
+        let mut sections = self.sections.clone();
+        let mut changed = false;
+        for event in events {
+            let Some(slot) = Slot::of(&event) else {
+                continue;
+            };
+            // same nudge can be sent again; so nudges always count as new
+            let same = slot != Slot::Nudge
+                && self
+                    .sections
+                    .get(&slot)
+                    .is_some_and(|current| Arc::ptr_eq(current, &event) || **current == *event);
+            if !same {
+                sections.insert(slot, event);
+                changed = true;
+            }
+        }
+        changed.then(|| Self {
+            clock: Some(clock.clone()),
+            sections,
+        })
+    }
+
+    // sections that differ from the `previous` update, after the clock; every section if there
+    // is none, but nudges are only sent to connections that were there when they're made
+    fn changes_since(&self, previous: Option<&UserUpdate>) -> Vec<Arc<PollEvent>> {
+        let mut events: Vec<Arc<PollEvent>> = self
+            .sections
+            .iter()
+            .filter(|(slot, event)| match previous {
+                Some(previous) => previous
+                    .sections
+                    .get(slot)
+                    .is_none_or(|sent| !Arc::ptr_eq(sent, event)),
+                None => **slot != Slot::Nudge,
+            })
+            .map(|(_, event)| event.clone())
+            .collect();
+        if !events.is_empty() {
+            events.splice(0..0, self.clock.clone());
+        }
+        events
+    }
+}
+
+// makes the next update of the channel from the current events of its sections; receivers are
+// only woken up when any of them changed
+pub(super) fn publish(
+    updates: &watch::Sender<Arc<UserUpdate>>,
+    clock: &Arc<PollEvent>,
+    events: Vec<Arc<PollEvent>>,
+) -> bool {
+    updates.send_if_modified(|update| match update.next(clock, events) {
+        Some(next) => {
+            *update = Arc::new(next);
+            true
+        }
+        None => false,
+    })
+}
+
+// new connections calibrate their countdowns by the clock of their first update; so it's made
+// again for them, and connections that got the sections already don't send anything for it
+pub(super) fn restamp(updates: &watch::Sender<Arc<UserUpdate>>, clock: &Arc<PollEvent>) {
+    updates.send_modify(|update| {
+        *update = Arc::new(UserUpdate {
+            clock: Some(clock.clone()),
+            sections: update.sections.clone(),
+        })
+    });
+}
+
+// updates of a user for one connection; only the part of them that it's subscribed to is sent
+pub struct UserFeed {
+    updates: watch::Receiver<Arc<UserUpdate>>,
+    subscription: watch::Receiver<Subscription>,
+    // `None` until the connection gets its first update, which has every section
+    sent: Option<Arc<UserUpdate>>,
+}
+
+impl UserFeed {
+    pub(super) fn new(
+        updates: watch::Receiver<Arc<UserUpdate>>,
+        subscription: watch::Receiver<Subscription>,
+    ) -> Self {
+        Self {
+            updates,
+            subscription,
+            sent: None,
+        }
+    }
+
+    // events of the latest update since the one this connection sent last; `None` when there's
+    // nothing new for it
+    pub fn try_next(&mut self) -> Option<Vec<Arc<PollEvent>>> {
+        let update = self.updates.borrow_and_update().clone();
+        if self
+            .sent
+            .as_ref()
+            .is_some_and(|sent| Arc::ptr_eq(sent, &update))
+        {
+            return None;
+        }
+        let events = update.changes_since(self.sent.as_deref());
+        self.sent = Some(update);
+        if events.is_empty() {
+            return None;
+        }
+        let subscription = self.subscription.borrow();
+        Some(
+            events
+                .into_iter()
+                .filter_map(|event| subscription.filter(event))
+                .collect(),
+        )
+    }
+
+    // waits for the next events; `None` once the poll closes the connections of the user, after
+    // the last update is taken
+    pub async fn next(&mut self) -> Option<Vec<Arc<PollEvent>>> {
+        loop {
+            if let Some(events) = self.try_next() {
+                return Some(events);
+            }
+            self.updates.changed().await.ok()?;
+        }
+    }
+
+    // whether the poll closed the connections of the user; connections find it out from `next`
+    #[cfg(test)]
+    pub fn is_closed(&self) -> bool {
+        self.updates.has_changed().is_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Polls, StateSection, StateView, UserLookupMethod, VoteMode};
+    use crate::test_support::{pass, user, Inspector, PollBuilder};
+
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_connections_skip_to_latest_update() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (_, mut inspector) = test.join([10, 0, 0, 1]);
+        inspector.drain();
+
+        for text in ["first", "second", "third"] {
+            test.poll
+                .lock()
+                .unwrap()
+                .add_item(test.owner_id, 0, text.to_string(), vec![])
+                .unwrap();
+            pass(Duration::from_secs(1)).await;
+        }
+        assert_eq!(test.poll.lock().unwrap().version(), 3);
+        // only the latest of the changed sections, once
+        let events = inspector.drain();
+        assert!(matches!(&events[0], PollEvent::Clock(clock) if clock.tick == 3));
+        assert_eq!(events.len(), 2);
+        match &events[1] {
+            PollEvent::Question(question) => assert_eq!(question.latest_items.len(), 3),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(inspector.drain().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn broadcasts_follow_subscription() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (subscribe, subscription) = watch::channel(Subscription::default());
+        let (mut inspector, first, second) = {
+            let mut poll = test.poll.lock().unwrap();
+            let (_, feed) = poll
+                .join_subscribed(user([10, 0, 0, 1]), subscription)
+                .unwrap();
+            poll.add_question(test.owner_id, "second".to_string(), VoteMode::UpDown)
+                .unwrap();
+            let first = poll
+                .add_item(test.owner_id, 0, "first item".to_string(), vec![])
+                .unwrap();
+            let second = poll
+                .add_item(test.owner_id, 0, "second item".to_string(), vec![])
+                .unwrap();
+            (Inspector::new(feed), first, second)
+        };
+        subscribe.send_replace(Subscription {
+            sections: Some(vec![StateSection::Questions]),
+            questions: Some(vec![0]),
+            items: Some(vec![second]),
+            top_items_only: true,
+            view: StateView::Personal,
+        });
+        inspector.drain();
+
+        pass(Duration::from_secs(1)).await;
+        let events = inspector.drain();
+        assert!(matches!(events[0], PollEvent::Clock(_)));
+        let questions: Vec<_> = events[1..]
+            .iter()
+            .map(|event| match event {
+                PollEvent::Question(question) => question,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].id, 0);
+        assert!(questions[0].latest_items.is_empty());
+        let top_items: Vec<usize> = questions[0].top_items.iter().map(|item| item.id).collect();
+        assert!(top_items.contains(&second) && !top_items.contains(&first));
+    }
+}
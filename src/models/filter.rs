@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Checks texts that users submit, like items and comments, before they're stored.
+///
+/// Filters see the normalized text. They can let it pass as is, replace it with a cleaned version,
+/// or reject it altogether.
+pub trait ContentFilter: Send + Sync {
+    fn check(&self, text: &str) -> Verdict;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    // text is stored with this instead
+    Masked(String),
+    Rejected,
+}
+
+// what happens to texts that contain a listed word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterAction {
+    // listed words are replaced with asterisks
+    Mask,
+    Reject,
+}
+
+impl FromStr for FilterAction {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mask" => Ok(FilterAction::Mask),
+            "reject" => Ok(FilterAction::Reject),
+            _ => Err(()),
+        }
+    }
+}
+
+// words are matched whole and case insensitively; `ass` doesn't match `class`
+#[derive(Clone, Debug)]
+pub struct WordList {
+    words: HashSet<String>,
+    action: FilterAction,
+}
+
+impl WordList {
+    // one word per line; empty lines and lines starting with `#` are skipped
+    pub fn parse(list: &str, action: FilterAction) -> Self {
+        Self {
+            words: list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+            action,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl ContentFilter for WordList {
+    fn check(&self, text: &str) -> Verdict {
+        let mut masked = String::with_capacity(text.len());
+        let mut found = false;
+        // every piece is a word followed by the separators after it
+        for piece in text.split_inclusive(|c: char| !c.is_alphanumeric()) {
+            let word = piece.trim_end_matches(|c: char| !c.is_alphanumeric());
+            if !word.is_empty() && self.words.contains(&word.to_lowercase()) {
+                if self.action == FilterAction::Reject {
+                    return Verdict::Rejected;
+                }
+                found = true;
+                masked.extend(word.chars().map(|_| '*'));
+                masked.push_str(&piece[word.len()..]);
+            } else {
+                masked.push_str(piece);
+            }
+        }
+        if found {
+            Verdict::Masked(masked)
+        } else {
+            Verdict::Allowed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "# listed words\nheck\n\n  Darn \n";
+
+    #[test]
+    fn listed_words_are_masked() {
+        let filter = WordList::parse(LIST, FilterAction::Mask);
+        assert!(!filter.is_empty());
+        assert_eq!(
+            filter.check("Oh HECK, darn it!"),
+            Verdict::Masked("Oh ****, **** it!".to_string())
+        );
+        // only whole words match
+        assert_eq!(filter.check("checks and darned socks"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn listed_words_are_rejected() {
+        let filter = WordList::parse(LIST, FilterAction::Reject);
+        assert_eq!(filter.check("what the heck"), Verdict::Rejected);
+        assert_eq!(filter.check("what the hedge"), Verdict::Allowed);
+    }
+}
@@ -0,0 +1,103 @@
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{Event, Parser, Tag};
+
+// links with other schemes, like `javascript:`, are shown as plain text
+const ALLOWED_SCHEMES: [&str; 3] = ["http://", "https://", "mailto:"];
+
+/// Renders markdown text of an item into HTML that clients can insert as is.
+///
+/// Only a small set of elements is produced: paragraphs, emphasis, code, quotes, lists and links.
+/// Raw HTML in the text is escaped, headings become paragraphs and images are replaced with their
+/// alt text; so the output can't contain anything that runs scripts or loads other resources.
+pub fn render(text: &str) -> String {
+    let mut html = String::with_capacity(text.len() * 3 / 2);
+    // closing tags of the open elements; empty for the ones that are not rendered
+    let mut closing: Vec<&'static str> = Vec::new();
+    for event in Parser::new(text) {
+        match event {
+            Event::Start(tag) => {
+                let (open, close) = element(&tag);
+                html.push_str(&open);
+                closing.push(close);
+            }
+            Event::End(_) => html.push_str(closing.pop().unwrap_or_default()),
+            Event::Text(text) | Event::Html(text) => push_escaped(&mut html, &text),
+            Event::Code(code) => {
+                html.push_str("<code>");
+                push_escaped(&mut html, &code);
+                html.push_str("</code>");
+            }
+            Event::SoftBreak => html.push('\n'),
+            Event::HardBreak => html.push_str("<br>"),
+            Event::Rule => html.push_str("<hr>"),
+            Event::FootnoteReference(_) | Event::TaskListMarker(_) => {}
+        }
+    }
+    html
+}
+
+// opening and closing tags of an element
+fn element(tag: &Tag) -> (String, &'static str) {
+    let (open, close) = match tag {
+        Tag::Paragraph | Tag::Heading(..) => ("<p>", "</p>"),
+        Tag::BlockQuote => ("<blockquote>", "</blockquote>"),
+        Tag::CodeBlock(_) => ("<pre><code>", "</code></pre>"),
+        Tag::List(Some(start)) if *start != 1 => {
+            return (format!("<ol start=\"{}\">", start), "</ol>");
+        }
+        Tag::List(Some(_)) => ("<ol>", "</ol>"),
+        Tag::List(None) => ("<ul>", "</ul>"),
+        Tag::Item => ("<li>", "</li>"),
+        Tag::Emphasis => ("<em>", "</em>"),
+        Tag::Strong => ("<strong>", "</strong>"),
+        Tag::Strikethrough => ("<del>", "</del>"),
+        Tag::Link(_, url, _) if is_allowed_url(url) => {
+            let mut open = String::from("<a href=\"");
+            escape_href(&mut open, url).unwrap();
+            open.push_str("\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">");
+            return (open, "</a>");
+        }
+        // text of these is still rendered; only the element itself is left out
+        Tag::Link(..) | Tag::Image(..) | Tag::FootnoteDefinition(_) => ("", ""),
+        Tag::Table(_) | Tag::TableHead | Tag::TableRow | Tag::TableCell => ("", ""),
+    };
+    (open.to_owned(), close)
+}
+
+fn is_allowed_url(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    ALLOWED_SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+}
+
+fn push_escaped(html: &mut String, text: &str) {
+    // writing into a `String` can't fail
+    escape_html(html, text).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_formatting_is_rendered() {
+        assert_eq!(
+            render("**more** _coffee_, see `menu` on [site](https://example.com/a?b=1&c=2)"),
+            "<p><strong>more</strong> <em>coffee</em>, see <code>menu</code> on \
+             <a href=\"https://example.com/a?b=1&amp;c=2\" rel=\"nofollow noopener noreferrer\" \
+             target=\"_blank\">site</a></p>"
+        );
+    }
+
+    #[test]
+    fn unsafe_markup_is_escaped_or_dropped() {
+        assert_eq!(
+            render("hi <img src=x onerror=alert(1)>"),
+            "<p>hi &lt;img src=x onerror=alert(1)&gt;</p>"
+        );
+        assert_eq!(
+            render("[click](javascript:alert(1)) ![pic](https://example.com/x.png)"),
+            "<p>click pic</p>"
+        );
+        assert_eq!(render("# title"), "<p>title</p>");
+    }
+}
@@ -1,3 +1,19 @@
+pub mod attachment;
+pub mod audit;
+pub mod export;
+pub mod feed;
+pub mod filter;
+pub mod markdown;
 pub mod poll;
+pub mod reference;
+pub mod scoring;
+pub mod similarity;
+pub mod text;
 
+pub use anket_shared::{settings::*, state::*, subscription::*};
+pub use audit::*;
+pub use export::*;
+pub use feed::*;
+pub use filter::*;
 pub use poll::*;
+pub use reference::*;
@@ -1,28 +1,69 @@
-use crate::utils::{HashMapVecInsert, RingBuffer, StringKeyGenerate, TouchTimed, UuidKeyGenerate};
+use super::attachment::{Attachment, ImageType, MAX_ATTACHMENTS_PER_POLL};
+use super::audit::{AuditEntry, AuditEvent, AuditRecord};
+use super::export::{IdentityMapper, ItemExport, PollExport, QuestionExport, VoteExport};
+use super::feed::{self, UserFeed, UserUpdate};
+use super::filter::{ContentFilter, Verdict};
+use super::markdown;
+use super::scoring::{self, ScoreStrategy, Vote};
+use super::similarity;
+use super::text;
+use crate::preview::LinkPreviewer;
+use crate::utils::{
+    HashMapVecInsert, OrderedF64, RingBuffer, StringKeyGenerate, TouchTimed, UuidKeyGenerate,
+};
+use anket_shared::{
+    AddItemPermit, ClockHint, CommentState, DuplicateVoteCheck, ItemOrder, ItemOverlay, ItemPage,
+    ItemPreview, ItemState, LinkPreview, ModerationAction, ModerationEntry, ParticipantState,
+    PollEvent, PollMeta, PollSettings, PollState, QuestionState, RankingMethod, RatingState,
+    ReactionState, ReportState, RetentionPolicy, SectionState, SimilarItem, StateView,
+    Subscription, UserLookupMethod, UserVoteState, VoteMode, VoteStats,
+};
+use cookie::time::OffsetDateTime;
+use hyper::{body::Bytes, Uri};
 
-use std::collections::{BTreeSet, HashMap};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::net::IpAddr;
-use std::ops::RangeInclusive;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
+use strum::IntoStaticStr;
 use thiserror::Error;
-use tokio::sync::mpsc;
-use tracing::debug;
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::time::Instant;
+use tracing::{debug, info};
 use uuid::Uuid;
 
 pub struct Polls {
     // HashMap<poll id, poll>
     polls: HashMap<String, Arc<Mutex<Poll>>>,
+    // HashMap<poll id, expiration time>, closed polls that are kept by their retention policy
+    archive: HashMap<String, Instant>,
+    // minimum time between two votes of a user on the same item
+    vote_cooldown: Duration,
+    // checks texts of every poll; `None` when this instance doesn't filter anything
+    content_filter: Option<Arc<dyn ContentFilter>>,
+    // `None` when this instance doesn't preview links
+    link_previewer: Option<Arc<LinkPreviewer>>,
+    // broadcast delay of polls that don't set their own
+    broadcast_delay: Duration,
 
     close_ch: mpsc::UnboundedSender<String>,
     task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl Polls {
-    pub fn new() -> Arc<Mutex<Self>> {
+    pub fn new(
+        vote_cooldown: Duration,
+        content_filter: Option<Arc<dyn ContentFilter>>,
+    ) -> Arc<Mutex<Self>> {
         let (sender, receiver) = mpsc::unbounded_channel();
         let polls_raw = Self {
             polls: HashMap::new(),
+            archive: HashMap::new(),
+            vote_cooldown,
+            content_filter,
+            link_previewer: None,
+            broadcast_delay: BROADCAST_DELAY,
             close_ch: sender,
             task: None,
         };
@@ -33,19 +74,82 @@ impl Polls {
 
         polls
     }
+    // polls that are created after this fetch previews of links in their items
+    pub fn set_link_previewer(&mut self, link_previewer: Arc<LinkPreviewer>) {
+        self.link_previewer = Some(link_previewer);
+    }
+    // polls that are created after this use it unless they set their own
+    pub fn set_broadcast_delay(&mut self, broadcast_delay: Duration) {
+        self.broadcast_delay = broadcast_delay;
+    }
     pub fn add_poll(
         &mut self,
         settings: PollSettings,
         user_details: UserDetails,
     ) -> (Uuid, Arc<Mutex<Poll>>) {
         let id = self.polls.generate_key(8);
-        let (poll, user_id) = Poll::new(id.clone(), settings, user_details, self.close_ch.clone());
+        let broadcast_delay = settings
+            .broadcast_delay_ms
+            .map_or(self.broadcast_delay, |delay| {
+                Duration::from_millis(delay as u64)
+            });
+        let (poll, user_id) = Poll::new(
+            id.clone(),
+            settings,
+            user_details,
+            self.vote_cooldown,
+            self.content_filter.clone(),
+            self.link_previewer.clone(),
+            self.close_ch.clone(),
+        );
+        // worker reads this when it's woken up; nothing is changed before this returns
+        poll.lock().unwrap().broadcast_delay = broadcast_delay;
         self.polls.insert(id, poll.clone());
         (user_id, poll)
     }
     pub fn get_poll(&self, poll_id: &str) -> Option<Arc<Mutex<Poll>>> {
         self.polls.get(poll_id).cloned()
     }
+    // summaries of given polls in same order; unknown polls are skipped
+    pub fn get_summaries(&self, poll_ids: &[String]) -> Vec<PollSummary> {
+        poll_ids
+            .iter()
+            .filter_map(|poll_id| self.polls.get(poll_id))
+            .map(|poll| poll.lock().unwrap().summary())
+            .collect()
+    }
+    fn archive_poll(&mut self, poll_id: &str) {
+        let retention = match self.polls.get(poll_id) {
+            Some(poll) => poll.lock().unwrap().retention_period(),
+            None => {
+                debug!("{} is closed but it's unknown", poll_id);
+                return;
+            }
+        };
+        match retention {
+            Some(period) => {
+                self.archive
+                    .insert(poll_id.to_owned(), Instant::now() + period);
+            }
+            None => {
+                self.polls.remove(poll_id);
+            }
+        }
+    }
+    fn purge_expired(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .archive
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(poll_id, _)| poll_id.clone())
+            .collect();
+        for poll_id in expired.iter() {
+            debug!("{} retention period is over, purging", poll_id);
+            self.archive.remove(poll_id);
+            self.polls.remove(poll_id);
+        }
+    }
 }
 
 impl Drop for Polls {
@@ -57,9 +161,17 @@ impl Drop for Polls {
 }
 
 async fn polls_worker(polls: Arc<Mutex<Polls>>, mut close_recv: mpsc::UnboundedReceiver<String>) {
-    while let Some(poll_id) = close_recv.recv().await {
-        polls.lock().unwrap().polls.remove(&poll_id);
-        // TODO debug! if poll_id is unknown
+    let mut retention_timer = tokio::time::interval(Duration::from_secs(60 * 60));
+    retention_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            poll_id = close_recv.recv() => match poll_id {
+                Some(poll_id) => polls.lock().unwrap().archive_poll(&poll_id),
+                None => break,
+            },
+            _ = retention_timer.tick() => polls.lock().unwrap().purge_expired(),
+        }
     }
 }
 
@@ -76,14 +188,13 @@ trait UserCollection: Send + Sync {
     fn get_map(&self) -> &HashMap<Uuid, PollUser>;
     fn get_map_mut(&mut self) -> &mut HashMap<Uuid, PollUser>;
     fn create_user(&mut self, details: UserDetails) -> Result<Uuid, UserCreateError>;
+    fn remove_user(&mut self, user_id: &Uuid) -> Option<PollUser>;
+    // users like this one can't be created again; by session for session based collections, by
+    // IP address for IP based ones
+    fn deny(&mut self, user: &PollUser);
     fn clear(&mut self);
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub enum UserLookupMethod {
-    IPBased,
-    SessionBased,
-}
 impl From<UserLookupMethod> for Box<dyn UserCollection> {
     fn from(val: UserLookupMethod) -> Self {
         match val {
@@ -97,11 +208,13 @@ impl From<UserLookupMethod> for Box<dyn UserCollection> {
 
 struct PlainUsers {
     users: HashMap<Uuid, PollUser>,
+    denied_ids: HashSet<Uuid>,
 }
 impl PlainUsers {
     fn new() -> Self {
         Self {
             users: HashMap::new(),
+            denied_ids: HashSet::new(),
         }
     }
 }
@@ -120,13 +233,24 @@ impl UserCollection for PlainUsers {
         &mut self.users
     }
 
-    fn create_user(&mut self, _details: UserDetails) -> Result<Uuid, UserCreateError> {
+    fn create_user(&mut self, details: UserDetails) -> Result<Uuid, UserCreateError> {
+        if details.id.is_some_and(|id| self.denied_ids.contains(&id)) {
+            return Err(UserCreateError::Banned);
+        }
         let id = self.users.generate_key();
-        self.users.insert(id, PollUser::new(id));
+        self.users.insert(id, PollUser::new(id, details.ip));
         Ok(id)
     }
 
+    fn remove_user(&mut self, user_id: &Uuid) -> Option<PollUser> {
+        self.users.remove(user_id)
+    }
+    fn deny(&mut self, user: &PollUser) {
+        self.denied_ids.insert(user.id);
+    }
+
     fn clear(&mut self) {
+        self.denied_ids.clear();
         self.users.clear();
     }
 }
@@ -134,12 +258,14 @@ impl UserCollection for PlainUsers {
 struct IPBasedUsers {
     users: HashMap<Uuid, PollUser>,
     users_by_ip: HashMap<IpAddr, Uuid>,
+    denied_ips: HashSet<IpAddr>,
 }
 impl IPBasedUsers {
     fn new() -> Self {
         Self {
             users: HashMap::new(),
             users_by_ip: HashMap::new(),
+            denied_ips: HashSet::new(),
         }
     }
 }
@@ -156,46 +282,198 @@ impl UserCollection for IPBasedUsers {
     }
 
     fn create_user(&mut self, details: UserDetails) -> Result<Uuid, UserCreateError> {
-        if self.users_by_ip.get(&details.ip).is_some() {
+        if self.denied_ips.contains(&details.ip) {
+            return Err(UserCreateError::Banned);
+        }
+        if self.users_by_ip.contains_key(&details.ip) {
             return Err(UserCreateError::UserAlreadyExists);
         }
         let id = self.users.generate_key();
-        self.users.insert(id, PollUser::new(id));
+        self.users.insert(id, PollUser::new(id, details.ip));
         self.users_by_ip.insert(details.ip, id);
         Ok(id)
     }
 
+    fn remove_user(&mut self, user_id: &Uuid) -> Option<PollUser> {
+        let user = self.users.remove(user_id)?;
+        self.users_by_ip.retain(|_, id| id != user_id);
+        Some(user)
+    }
+    fn deny(&mut self, user: &PollUser) {
+        self.denied_ips.insert(user.ip);
+    }
+
     fn clear(&mut self) {
+        self.denied_ips.clear();
         self.users_by_ip.clear();
         self.users.clear();
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-pub enum AddItemPermit {
-    Anyone,
-    OwnerOnly,
-}
+pub const MAX_VOTE_CREDITS: u32 = 10_000;
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct PollSettings {
-    pub title: String,
-    pub user_lookup_method: UserLookupMethod,
-    pub add_item_permit: AddItemPermit,
-}
+// vote weights that poll owner can assign to users
+pub const MIN_USER_WEIGHT: u32 = 1;
+pub const MAX_USER_WEIGHT: u32 = 10;
+
+// count of latest activities that are kept for the feed
+const ACTIVITY_COUNT: usize = 50;
+// width of score ranges that items are grouped by on score histogram
+const SCORE_BUCKET_WIDTH: isize = 5;
+
+// questions that a poll can have, including the first one
+pub const MAX_QUESTIONS: usize = 20;
+// sections are shared by every question of a poll
+pub const MAX_SECTIONS: usize = 8;
+pub const MAX_SECTION_NAME_LENGTH: usize = 40;
+
+// reactions users can leave on items; they don't affect scores
+pub const REACTIONS: [&str; 3] = ["❤️", "😂", "🤔"];
+
+// count of latest votes of a user that can be undone
+const VOTE_HISTORY_COUNT: usize = 20;
+
+// comments longer than this are rejected
+pub const MAX_COMMENT_LENGTH: usize = 500;
+// an item can't have more comments than this
+const MAX_COMMENTS_PER_ITEM: usize = 200;
+
+// reasons given while reporting an item can't be longer than this
+const MAX_REPORT_REASON_LENGTH: usize = 200;
+
+// display names of users can't be longer than this
+pub const MAX_NAME_LENGTH: usize = 32;
+// users without a name are shown by their number, like `Participant 3`
+const NUMBERED_NAME_PREFIX: &str = "Participant ";
+
+// items that poll owner can pin at the same time
+pub const MAX_PINNED_ITEMS: usize = 5;
+
+// at most this many items are listed at once when every item is browsed
+pub const MAX_ITEM_PAGE_SIZE: usize = 100;
+
+// count of similar items that are suggested while a user types an item
+const SIMILAR_ITEM_COUNT: usize = 5;
+
+// count of latest moderation decisions that are shown to poll owner
+const MODERATION_LOG_COUNT: usize = 50;
+
+// count of latest changes kept in audit log of a poll; votes fill it much faster than others
+const AUDIT_LOG_COUNT: usize = 2000;
+
+// time dependent ranks are recalculated periodically
+const RERANK_INTERVAL: Duration = Duration::from_secs(30);
+
+const NUDGE_INTERVAL: Duration = Duration::from_secs(60);
+// changes made in this long after the first one are broadcast together; polls can change it
+const BROADCAST_DELAY: Duration = Duration::from_millis(100);
+// polls without any changes for this long are closed
+const INACTIVE_TIME: Duration = Duration::from_secs(15 * 60);
+// users get nudged after being idle for this long
+const NUDGE_IDLE_TIME: Duration = Duration::from_secs(5 * 60);
+// and only if at least this many items were added since their last action
+const NUDGE_MIN_UNSEEN_ITEMS: usize = 5;
+
+pub const MAX_RETENTION_DAYS: u32 = 90;
+// authors can edit their items at most this long after adding them
+pub const MAX_EDIT_WINDOW_MINUTES: u32 = 24 * 60;
+// changes of a poll are broadcast at most this long after they're made
+pub const MAX_BROADCAST_DELAY_MS: u32 = 10_000;
 
 struct PollUser {
     id: Uuid,
-    // user may have opened multiple browser tabs to same poll
-    // this is because we have a vec here, insted of single sender
-    senders: Vec<mpsc::UnboundedSender<PollState>>,
+    // `id` is also the session token of user; this one is shown to others instead
+    public_id: Uuid,
+    // last IP address that user joined from
+    ip: IpAddr,
+    // external system this user comes from; `None` for users joined from browser
+    source: Option<String>,
+    // latest updates of the user for connections with personal and shared views; user may have
+    // opened multiple browser tabs to same poll, and each of them has a receiver of one
+    personal_updates: watch::Sender<Arc<UserUpdate>>,
+    shared_updates: watch::Sender<Arc<UserUpdate>>,
+    // credits spent on quadratic voting
+    spent_credits: u32,
+    // count of items this user has a vote on
+    voted_items: u32,
+    // HashMap<item id, last vote time>, only holds votes that are still in cooldown
+    last_votes: HashMap<usize, Instant>,
+    // latest vote changes of this user, for undo
+    vote_history: RingBuffer<VoteChange>,
+    // votes of this user count this many times; assigned by poll owner
+    weight: u32,
+    // set by poll owner; items and votes of this user are only shown to themselves
+    shadow_banned: bool,
+    // by the order users joined, starting from 1; 0 until poll numbers this user
+    number: usize,
+    // chosen by the user, unique in the poll; `None` until user sets one
+    name: Option<String>,
+    joined_at: Instant,
+    // last time user added or voted an item
+    last_action: Instant,
+    // count of items added by others since `last_action`
+    unseen_items: usize,
+    // we nudge users once after each action
+    nudged: bool,
+    // version of poll when the updates of the user last changed
+    synced_version: u64,
     // we may add UserDetails here to make easy to delete users from `UserLookup` implementations
 }
 impl PollUser {
-    fn new(id: Uuid) -> Self {
+    fn updates(&self, view: StateView) -> &watch::Sender<Arc<UserUpdate>> {
+        match view {
+            StateView::Personal => &self.personal_updates,
+            StateView::Shared => &self.shared_updates,
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.personal_updates.receiver_count() > 0 || self.shared_updates.receiver_count() > 0
+    }
+
+    // connections of the user end once they take the last update of the dropped channels
+    fn close_connections(&mut self) {
+        self.personal_updates = watch::channel(Arc::default()).0;
+        self.shared_updates = watch::channel(Arc::default()).0;
+    }
+
+    fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{}{}", NUMBERED_NAME_PREFIX, self.number),
+        }
+    }
+
+    // votes of shadow banned users are kept without any weight; so they don't change scores
+    fn vote_weight(&self) -> u32 {
+        if self.shadow_banned {
+            0
+        } else {
+            self.weight
+        }
+    }
+
+    fn new(id: Uuid, ip: IpAddr) -> Self {
         Self {
             id,
-            senders: Vec::with_capacity(1),
+            public_id: Uuid::new_v4(),
+            ip,
+            source: None,
+            personal_updates: watch::channel(Arc::default()).0,
+            shared_updates: watch::channel(Arc::default()).0,
+            spent_credits: 0,
+            voted_items: 0,
+            last_votes: HashMap::new(),
+            vote_history: RingBuffer::new(VOTE_HISTORY_COUNT),
+            weight: MIN_USER_WEIGHT,
+            shadow_banned: false,
+            number: 0,
+            name: None,
+            joined_at: Instant::now(),
+            last_action: Instant::now(),
+            unseen_items: 0,
+            nudged: false,
+            synced_version: 0,
         }
     }
 }
@@ -203,80 +481,279 @@ impl PollUser {
 pub struct Poll {
     id: String,
     title: String,
-    owner: Uuid, // user id
+    description: Option<String>,
+    // user id; `None` after the poll is anonymized, so nobody owns it anymore
+    owner: Option<Uuid>,
 
-    // indicates that; some changes made and should be calculated & published on the next timer.tick
+    // indicates that; some changes made and should be calculated & published by the worker
     changed: TouchTimed<bool>,
-    // valid value range for a user item vote
-    value_range: RangeInclusive<isize>,
+    // wakes the worker up when the poll is changed or closed
+    changes: Arc<Notify>,
+    // questions that shared connections got on the last broadcast; an event is only made again
+    // when its question changes
+    shared_questions: Vec<Arc<PollEvent>>,
+    // changes made in this long after the first one are broadcast together
+    broadcast_delay: Duration,
+    // first question is created from poll settings, owner adds the others
+    questions: Vec<Question>,
+    // questions added later are scored by these settings with their own vote modes
+    settings: PollSettings,
+    nudges: bool,
+    // `None` means users can vote any number of items
+    max_votes_per_user: Option<u32>,
+    // `None` means there is no limit; deleted items don't count
+    max_items_per_user: Option<u32>,
+    max_items_total: Option<u32>,
+    // users can't change their vote on an item again before this much time passes
+    vote_cooldown: Duration,
+    // items and comments are checked by this before they're stored
+    content_filter: Option<Arc<dyn ContentFilter>>,
+    // links are found by this previewer and sent to the preview worker of this poll
+    link_previews: Option<PreviewQueue>,
+    // authors can edit their items this long after adding them; owner can edit at any time
+    edit_window: Duration,
+    // limits of item texts, in characters
+    min_item_length: usize,
+    max_item_length: usize,
+    duplicate_vote_check: DuplicateVoteCheck,
     add_item_permit: AddItemPermit,
+    retention_policy: RetentionPolicy,
+    retention_days: u32,
+    // closed polls are read-only
+    closed: bool,
+    hide_results_until_close: bool,
+    shuffle_items: bool,
+    show_authors: bool,
+    moderate_items: bool,
+    markdown: bool,
 
-    // item id, item
+    // names of sections that items can be added to, like columns of a retrospective board
+    sections: Vec<String>,
+    // items that wait for approval of poll owner; they're not in `items` until then
+    pending_items: BTreeMap<usize, Item>,
+    // item id, item; ids are unique across questions
     items: HashMap<usize, Item>,
-    // BTreeSet<(score of item, id of item)>, sorted by scores
-    items_by_score: BTreeSet<(isize, usize)>,
+    // ids of deleted items are not used again
+    next_item_id: usize,
+    // HashMap<merged item id, id of item it's merged into>
+    merged_items: HashMap<usize, usize>,
+    // ids of items that poll owner pinned, in the order they're pinned
+    pinned_items: Vec<usize>,
+    // images uploaded to this poll, attached to an item or waiting to be
+    attachments: HashMap<Uuid, Attachment>,
+    // BTreeMap<start of score bucket, count of items>, updated on every score change
+    score_histogram: BTreeMap<isize, usize>,
     // HashMap<user id, item id>
     items_by_user: HashMap<Uuid, Vec<usize>>,
-    // id of item
-    last_items: RingBuffer<usize>,
+    // latest events of this poll, served as a feed
+    activities: RingBuffer<Activity>,
+    // total count of activities; used as an identifier for activities
+    activity_count: usize,
+    // count of broadcasts; lets clients order state updates without trusting their clocks
+    // it's also the version of poll state, sent to the waiters of the next version by `versions`
+    broadcast_tick: u64,
+    versions: watch::Sender<u64>,
+    // HashMap<(item id, language), translated text>
+    translations: HashMap<(usize, String), String>,
+    // latest moderation decisions of poll owner
+    moderation_log: RingBuffer<ModerationEntry>,
+    // every change of items, votes and participants; available to poll owner and in exports
+    audit_log: RingBuffer<AuditRecord>,
+    // `None` when external systems are not allowed to push actions into this poll
+    ingest_secret: Option<String>,
+    // HashMap<(source, external user id), user id>
+    external_users: HashMap<(String, String), Uuid>,
 
     users: Box<dyn UserCollection>,
+    // count of users that ever joined; numbers of removed users are not given again
+    user_count: usize,
 
     // this is an Option, because task created after this
     task: Option<tokio::task::JoinHandle<()>>,
 }
 
-async fn poll_worker(poll_mutex: Arc<Mutex<Poll>>, close_ch: mpsc::UnboundedSender<String>) {
-    let mut timer = tokio::time::interval(Duration::from_millis(500));
-    timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+// sleeps until the poll is changed; quiet polls only wake up for periodic work, if they have any
+async fn poll_worker(
+    poll_mutex: Arc<Mutex<Poll>>,
+    changes: Arc<Notify>,
+    close_ch: mpsc::UnboundedSender<String>,
+) {
+    let mut rerank_timer =
+        tokio::time::interval_at(Instant::now() + RERANK_INTERVAL, RERANK_INTERVAL);
+    rerank_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut nudge_timer = tokio::time::interval_at(Instant::now() + NUDGE_INTERVAL, NUDGE_INTERVAL);
+    nudge_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     debug!("poll worker started");
     loop {
-        timer.tick().await;
+        let (time_dependent, nudges, inactive_at) = {
+            let poll = poll_mutex.lock().unwrap();
+            let time_dependent = poll
+                .questions
+                .iter()
+                .any(|question| question.scoring.is_time_dependent());
+            let inactive_at = Instant::now() + INACTIVE_TIME.saturating_sub(poll.changed.elapsed());
+            (time_dependent, poll.nudges, inactive_at)
+        };
+        tokio::select! {
+            _ = changes.notified() => {
+                // a change rarely comes alone; following ones are sent with it
+                let broadcast_delay = poll_mutex.lock().unwrap().broadcast_delay;
+                if !broadcast_delay.is_zero() {
+                    tokio::time::sleep(broadcast_delay).await;
+                }
+            }
+            _ = rerank_timer.tick(), if time_dependent => {
+                poll_mutex.lock().unwrap().rerank();
+            }
+            _ = nudge_timer.tick(), if nudges => {
+                poll_mutex.lock().unwrap().send_nudges();
+            }
+            _ = tokio::time::sleep_until(inactive_at) => {}
+        }
         let mut poll = poll_mutex.lock().unwrap();
 
-        if *poll.changed.value() {
+        if poll.closed {
+            debug!("{} is closed, worker stops", poll.id);
+            let _ = close_ch.send(poll.id.clone());
+            break;
+        } else if *poll.changed.value() {
             debug!("{} poll.changed, broadcasting...", poll.id);
             poll.broadcast();
-        } else if poll.changed.elapsed() > Duration::from_secs(15 * 60) {
+        } else if poll.changed.elapsed() >= INACTIVE_TIME {
             debug!("{} is inactive, worker stops", poll.id);
-            poll.users.clear();
+            poll.close();
             let _ = close_ch.send(poll.id.clone());
             break;
         }
     }
 }
 
+// previewer of a poll and the channel of its preview worker; links are sent with their item ids
+type PreviewQueue = (Arc<LinkPreviewer>, mpsc::UnboundedSender<(usize, Uri)>);
+
+// fetches previews of links in items without holding the poll locked; stops when poll is dropped
+async fn preview_worker(
+    poll: Weak<Mutex<Poll>>,
+    previewer: Arc<LinkPreviewer>,
+    mut links: mpsc::UnboundedReceiver<(usize, Uri)>,
+) {
+    while let Some((item_id, url)) = links.recv().await {
+        let preview = match previewer.fetch(url.clone()).await {
+            Ok(preview) => preview,
+            Err(err) => {
+                debug!("couldn't preview {}: {}", url, err);
+                continue;
+            }
+        };
+        let Some(poll) = poll.upgrade() else {
+            break;
+        };
+        let mut poll = poll.lock().unwrap();
+        poll.set_link_preview(item_id, &url, preview);
+    }
+}
+
 impl Poll {
     fn new(
         id: String,
         settings: PollSettings,
         user_details: UserDetails,
+        vote_cooldown: Duration,
+        content_filter: Option<Arc<dyn ContentFilter>>,
+        link_previewer: Option<Arc<LinkPreviewer>>,
         close_ch: mpsc::UnboundedSender<String>,
     ) -> (Arc<Mutex<Self>>, Uuid) {
-        let mut users: Box<dyn UserCollection> = settings.user_lookup_method.into();
+        let question = Question::new(0, settings.title.clone(), &settings);
+        let duplicate_vote_check = match settings.user_lookup_method {
+            UserLookupMethod::SessionBased => settings.duplicate_vote_check.clone(),
+            UserLookupMethod::IPBased => DuplicateVoteCheck::Off,
+        };
+        let mut users: Box<dyn UserCollection> = settings.user_lookup_method.clone().into();
         let owner_id = users
             .create_user(user_details)
             .expect("this is the first user that we create on this poll");
 
         let poll_raw = Self {
             id,
-            owner: owner_id,
-            title: settings.title,
+            owner: Some(owner_id),
+            title: settings.title.clone(),
+            description: settings
+                .description
+                .as_deref()
+                .map(str::trim)
+                .filter(|description| !description.is_empty())
+                .map(str::to_owned),
             changed: TouchTimed::new(false),
-            value_range: -1..=1,
+            changes: Arc::new(Notify::new()),
+            shared_questions: Vec::new(),
+            broadcast_delay: BROADCAST_DELAY,
+            questions: vec![question],
+            settings: settings.clone(),
+            nudges: settings.nudges,
+            max_votes_per_user: Some(settings.max_votes_per_user).filter(|max| *max > 0),
+            max_items_per_user: Some(settings.max_items_per_user).filter(|max| *max > 0),
+            max_items_total: Some(settings.max_items_total).filter(|max| *max > 0),
+            vote_cooldown,
+            content_filter,
+            link_previews: None,
+            edit_window: Duration::from_secs(settings.edit_window_minutes as u64 * 60),
+            min_item_length: settings.min_item_length,
+            max_item_length: settings.max_item_length,
+            duplicate_vote_check,
             add_item_permit: settings.add_item_permit,
+            retention_policy: settings.retention_policy,
+            retention_days: settings.retention_days,
+            closed: false,
+            hide_results_until_close: settings.hide_results_until_close,
+            shuffle_items: settings.shuffle_items,
+            show_authors: settings.show_authors,
+            moderate_items: settings.moderate_items,
+            markdown: settings.markdown,
             items: HashMap::new(),
-            items_by_score: BTreeSet::new(),
+            next_item_id: 0,
+            merged_items: HashMap::new(),
+            pinned_items: Vec::new(),
+            attachments: HashMap::new(),
+            score_histogram: BTreeMap::new(),
             items_by_user: HashMap::new(),
-            last_items: RingBuffer::new(10),
+            activities: RingBuffer::new(ACTIVITY_COUNT),
+            activity_count: 0,
+            broadcast_tick: 0,
+            versions: watch::channel(0).0,
+            translations: HashMap::new(),
+            moderation_log: RingBuffer::new(MODERATION_LOG_COUNT),
+            audit_log: RingBuffer::new(AUDIT_LOG_COUNT),
+            sections: Vec::new(),
+            pending_items: BTreeMap::new(),
+            ingest_secret: settings
+                .allow_ingestion
+                .then(crate::ingest::generate_secret),
+            external_users: HashMap::new(),
             users,
+            user_count: 0,
             task: None,
         };
+        let changes = poll_raw.changes.clone();
         let poll = Arc::new(Mutex::new(poll_raw));
 
-        let task = tokio::spawn(poll_worker(poll.clone(), close_ch));
-        poll.lock().unwrap().task = Some(task);
+        let task = tokio::spawn(poll_worker(poll.clone(), changes, close_ch));
+        let link_previews = link_previewer.map(|previewer| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(preview_worker(
+                Arc::downgrade(&poll),
+                previewer.clone(),
+                receiver,
+            ));
+            (previewer, sender)
+        });
+        {
+            let mut poll = poll.lock().unwrap();
+            poll.link_previews = link_previews;
+            poll.number_user(&owner_id);
+            poll.record_activity(ActivityKind::Created);
+            poll.task = Some(task);
+        }
 
         (poll, owner_id)
     }
@@ -285,34 +762,460 @@ impl Poll {
         &self.id
     }
 
-    pub fn join(
+    pub fn summary(&self) -> PollSummary {
+        PollSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            closed: self.closed,
+        }
+    }
+
+    // current leader of the first question; served as an embeddable badge
+    pub fn badge(&self) -> PollBadge {
+        let results_hidden = self.hide_results_until_close && !self.closed;
+        let top_item = if results_hidden {
+            None
+        } else {
+            self.questions[0]
+                .items_by_score
+                .iter()
+                .rev()
+                .map(|(_, Reverse(item_id))| self.items.get(item_id).expect("ranked items exist"))
+                .find(|item| !self.is_shadow_banned(&item.user_id))
+                .map(|item| BadgeItem {
+                    text: item.text.clone(),
+                    score: item.score,
+                    vote_count: item.vote_count(),
+                })
+        };
+        PollBadge {
+            poll_id: self.id.clone(),
+            title: self.title.clone(),
+            closed: self.closed,
+            results_hidden,
+            top_item,
+        }
+    }
+
+    // final standings of a question by the tally of its vote mode
+    // available to everyone unless results are hidden
+    pub fn result(&self, question_id: usize) -> Result<PollResult, PollResultError> {
+        if self.hide_results_until_close && !self.closed {
+            return Err(PollResultError::ResultsHidden);
+        }
+        let question = self
+            .questions
+            .get(question_id)
+            .ok_or(PollResultError::QuestionNotFound)?;
+        let tally_method = match question.vote_mode {
+            VoteMode::UpDown | VoteMode::Quadratic => TallyMethod::Score,
+            VoteMode::Approval => TallyMethod::Approvals,
+            VoteMode::Rating => TallyMethod::AverageRating,
+        };
+        let mut standings: Vec<Standing> = self
+            .items
+            .values()
+            .filter(|item| item.question_id == question_id)
+            .filter(|item| !self.is_shadow_banned(&item.user_id))
+            .map(|item| Standing {
+                item_id: item.id,
+                text: item.text.clone(),
+                tally: match tally_method {
+                    TallyMethod::Score | TallyMethod::Approvals => item.score as f64,
+                    TallyMethod::AverageRating => item.average(),
+                },
+                vote_count: item.vote_count(),
+            })
+            .collect();
+        // more votes win the ties of average ratings; then older items come first
+        standings.sort_by(|a, b| {
+            b.tally
+                .total_cmp(&a.tally)
+                .then(b.vote_count.cmp(&a.vote_count))
+                .then(a.item_id.cmp(&b.item_id))
+        });
+        // items without any votes can't win
+        let winners = match standings.first() {
+            Some(first) if first.vote_count > 0 => standings
+                .iter()
+                .take_while(|standing| {
+                    standing.tally == first.tally && standing.vote_count == first.vote_count
+                })
+                .map(|standing| standing.item_id)
+                .collect(),
+            _ => vec![],
+        };
+        Ok(PollResult {
+            poll_id: self.id.clone(),
+            title: self.title.clone(),
+            question_id,
+            question: question.title.clone(),
+            closed: self.closed,
+            tally_method,
+            winners,
+            standings,
+        })
+    }
+
+    // how long poll should be kept after it's closed, `None` means it should be purged
+    fn retention_period(&self) -> Option<Duration> {
+        match self.retention_policy {
+            RetentionPolicy::Purge => None,
+            RetentionPolicy::Anonymize | RetentionPolicy::Keep => Some(Duration::from_secs(
+                self.retention_days as u64 * 24 * 60 * 60,
+            )),
+        }
+    }
+
+    pub fn close_by(&mut self, user_id: Uuid) -> Result<(), ClosePollError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(ClosePollError::NotOwner);
+        }
+        if self.closed {
+            return Err(ClosePollError::AlreadyClosed);
+        }
+        // worker is woken up by this and stops
+        self.record_moderation(user_id, ModerationAction::ClosePoll, None);
+        self.record_audit(user_id, None, AuditEvent::PollClosed);
+        self.close();
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.record_activity(ActivityKind::Closed);
+        if self.retention_policy == RetentionPolicy::Anonymize {
+            self.anonymize();
+        }
+        // let connected users know that poll is closed; they get disconnected after this
+        self.broadcast();
+        self.users.clear();
+        self.changes.notify_one();
+    }
+
+    // worker broadcasts the changes shortly after the first one
+    fn mark_changed(&mut self) {
+        if !*self.changed.value() {
+            self.changes.notify_one();
+        }
+        self.changed.update(true);
+    }
+
+    // detaches every vote and item from its user; scores stay as is
+    fn anonymize(&mut self) {
+        // votes are kept with a random id for each user; so vote counts, averages and approval
+        // percentages are still available
+        let mut voter_ids: HashMap<Uuid, Uuid> = HashMap::new();
+        for item in self.items.values_mut() {
+            item.user_id = Uuid::nil();
+            item.votes = item
+                .votes
+                .drain()
+                .map(|(user_id, vote)| {
+                    (*voter_ids.entry(user_id).or_insert_with(Uuid::new_v4), vote)
+                })
+                .collect();
+        }
+        for record in self.audit_log.iter_mut() {
+            record.actor = Uuid::nil();
+            record.target = record.target.map(|_| Uuid::nil());
+        }
+        self.items_by_user.clear();
+        self.owner = None;
+    }
+
+    pub fn join(&mut self, user_details: UserDetails) -> Result<(Uuid, UserFeed), UserCreateError> {
+        // nothing changes this subscription; so the connection gets everything
+        let (_, subscription) = watch::channel(Subscription::default());
+        self.join_subscribed(user_details, subscription)
+    }
+
+    // joins with a connection that only gets the part of the state it's subscribed to; the
+    // subscription can be changed while it's connected
+    pub fn join_subscribed(
         &mut self,
         user_details: UserDetails,
-        user_sender: mpsc::UnboundedSender<PollState>,
-    ) -> Uuid {
-        // TODO make this func failable; return err if self.task finished
+        subscription: watch::Receiver<Subscription>,
+    ) -> Result<(Uuid, UserFeed), UserCreateError> {
+        let user_id = self.enter(user_details)?;
+        let view = subscription.borrow().view;
+        let updates = self.users.get_map()[&user_id].updates(view).subscribe();
+
+        // broadcasts skip users without connections; so their updates are made again here, and
+        // the new connection gets every section of them
+        let clock = Arc::new(self.clock_hint());
+        if view == StateView::Shared {
+            self.update_shared_questions();
+        }
+        let overlay = (self.users.get_map()[&user_id]
+            .shared_updates
+            .receiver_count()
+            > 0)
+        .then(|| self.item_overlay(&user_id));
+        self.update_user(&user_id, &clock, overlay);
+        feed::restamp(self.users.get_map()[&user_id].updates(view), &clock);
+
+        // TODO return a UserDetails instead
+        Ok((user_id, UserFeed::new(updates, subscription)))
+    }
+
+    // finds or creates the user without a connection; API clients use polls like this
+    pub fn enter(&mut self, user_details: UserDetails) -> Result<Uuid, UserCreateError> {
+        let ip = user_details.ip;
         let user_id = if let Some(user_id) = self.users.search_user(&user_details) {
             user_id
         } else {
-            self.users
-                .create_user(user_details)
-                .expect("this user does not exists in poll")
+            // banned users are not in the collection anymore; so they are only caught here
+            self.users.create_user(user_details)?
         };
+        self.number_user(&user_id);
+        let user = self
+            .users
+            .get_map_mut()
+            .get_mut(&user_id)
+            .expect("we just got/created this user");
+        user.ip = ip;
+        Ok(user_id)
+    }
 
-        if !*self.changed.value() {
-            // no need to examine error here, because sender is going to be
-            // dropped on next broadcast if it's erroneous
-            let _ = user_sender.send(self.get_state(&user_id));
+    // stores an image that the user can attach to their next item; returns its id
+    pub fn add_attachment(
+        &mut self,
+        user_details: &UserDetails,
+        data: Bytes,
+    ) -> Result<Uuid, AddAttachmentError> {
+        if self.closed {
+            return Err(AddAttachmentError::PollClosed);
+        }
+        let user_id = self
+            .users
+            .search_user(user_details)
+            .ok_or(AddAttachmentError::NotJoined)?;
+        let image_type = ImageType::detect(&data).ok_or(AddAttachmentError::UnsupportedType)?;
+        if self.attachments.len() >= MAX_ATTACHMENTS_PER_POLL {
+            return Err(AddAttachmentError::TooManyAttachments);
+        }
+        let attachment_id = Uuid::new_v4();
+        self.attachments.insert(
+            attachment_id,
+            Attachment {
+                image_type,
+                data,
+                user_id,
+            },
+        );
+        Ok(attachment_id)
+    }
+
+    pub fn attachment(&self, attachment_id: &Uuid) -> Option<Attachment> {
+        self.attachments.get(attachment_id).cloned()
+    }
+
+    // like `add_item`; with the optional parts of an item that only some clients send
+    pub fn add_item_with(
+        &mut self,
+        user_id: Uuid,
+        question_id: usize,
+        item_text: String,
+        references: Vec<ItemPreview>,
+        extras: ItemExtras,
+    ) -> Result<usize, AddPollItemError> {
+        if let Some(attachment_id) = extras.attachment {
+            match self.attachments.get(&attachment_id) {
+                Some(attachment) if attachment.user_id == user_id => {}
+                _ => return Err(AddPollItemError::AttachmentNotFound),
+            }
+            if self
+                .items
+                .values()
+                .chain(self.pending_items.values())
+                .any(|item| item.attachment == Some(attachment_id))
+            {
+                return Err(AddPollItemError::AttachmentInUse);
+            }
+        }
+        if extras
+            .section
+            .is_some_and(|section| section >= self.sections.len())
+        {
+            return Err(AddPollItemError::SectionNotFound);
+        }
+        let item_id = self.add_item(user_id, question_id, item_text, references)?;
+        let item = match self.items.get_mut(&item_id) {
+            Some(item) => item,
+            None => self
+                .pending_items
+                .get_mut(&item_id)
+                .expect("item is just added"),
+        };
+        item.attachment = extras.attachment;
+        item.section = extras.section;
+        Ok(item_id)
+    }
+
+    // replaces names of sections; items of removed sections are left without a section
+    pub fn set_sections(
+        &mut self,
+        user_id: Uuid,
+        sections: Vec<String>,
+    ) -> Result<(), SetSectionsError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(SetSectionsError::NotOwner);
+        }
+        if self.closed {
+            return Err(SetSectionsError::PollClosed);
+        }
+        if sections.len() > MAX_SECTIONS {
+            return Err(SetSectionsError::TooManySections);
+        }
+        let sections: Vec<String> = sections.iter().map(|name| text::normalize(name)).collect();
+        for (index, name) in sections.iter().enumerate() {
+            if name.is_empty() {
+                return Err(SetSectionsError::EmptyName);
+            }
+            if name.chars().count() > MAX_SECTION_NAME_LENGTH {
+                return Err(SetSectionsError::NameTooLong);
+            }
+            if sections[..index].contains(name) {
+                return Err(SetSectionsError::DuplicateName(name.clone()));
+            }
+        }
+        for item in self.items.values_mut() {
+            if item
+                .section
+                .is_some_and(|section| section >= sections.len())
+            {
+                item.section = None;
+            }
+        }
+        self.sections = sections;
+        self.mark_changed();
+        Ok(())
+    }
+
+    // preview of the first link in text of item is fetched in the background
+    fn request_link_preview(&self, item_id: usize) {
+        let Some((previewer, sender)) = &self.link_previews else {
+            return;
+        };
+        if let Some(url) = previewer.find_link(&self.items[&item_id].text) {
+            let _ = sender.send((item_id, url));
+        }
+    }
+
+    // previews that arrive after the item is deleted or its link is edited out are dropped
+    fn set_link_preview(&mut self, item_id: usize, url: &Uri, preview: LinkPreview) {
+        let Some((previewer, _)) = &self.link_previews else {
+            return;
+        };
+        let Some(item) = self.items.get_mut(&item_id) else {
+            return;
+        };
+        if previewer.find_link(&item.text).as_ref() == Some(url) {
+            item.link_preview = Some(preview);
+            self.mark_changed();
+        }
+    }
+
+    // `None` if the text is rejected; otherwise the text to store
+    fn filter_text(&self, text: String) -> Option<String> {
+        match self
+            .content_filter
+            .as_ref()
+            .map(|filter| filter.check(&text))
+        {
+            None | Some(Verdict::Allowed) => Some(text),
+            Some(Verdict::Masked(masked)) => Some(masked),
+            Some(Verdict::Rejected) => None,
+        }
+    }
+
+    // `None` unless this poll uses markdown
+    fn render_markdown(&self, text: &str) -> Option<String> {
+        self.markdown.then(|| markdown::render(text))
+    }
+
+    fn number_user(&mut self, user_id: &Uuid) {
+        if let Some(user) = self.users.get_map_mut().get_mut(user_id) {
+            if user.number == 0 {
+                self.user_count += 1;
+                user.number = self.user_count;
+            }
         }
+    }
+
+    // how the user is shown to other participants
+    fn display_name(&self, user_id: &Uuid) -> Option<String> {
         self.users
+            .get_map()
+            .get(user_id)
+            .map(PollUser::display_name)
+    }
+
+    // an empty name removes the name of the user; they're shown by their number again
+    pub fn set_name(&mut self, user_id: Uuid, name: &str) -> Result<(), SetNameError> {
+        if self.closed {
+            return Err(SetNameError::PollClosed);
+        }
+        let name = text::normalize(name);
+        if name.chars().count() > MAX_NAME_LENGTH {
+            return Err(SetNameError::TooLong);
+        }
+        // numbered names belong to users without a name
+        let lowercase = name.to_lowercase();
+        if lowercase.starts_with(&NUMBERED_NAME_PREFIX.to_lowercase()) {
+            return Err(SetNameError::InvalidName);
+        }
+        let taken = self.users.get_map().values().any(|user| {
+            user.id != user_id
+                && user
+                    .name
+                    .as_ref()
+                    .is_some_and(|other| other.to_lowercase() == lowercase)
+        });
+        if taken {
+            return Err(SetNameError::NameTaken);
+        }
+        let user = self
+            .users
             .get_map_mut()
             .get_mut(&user_id)
-            .expect("we just got/created this user")
-            .senders
-            .push(user_sender);
+            .ok_or(SetNameError::UserNotFound)?;
+        user.name = Some(name).filter(|name| !name.is_empty());
+        self.mark_changed();
+        Ok(())
+    }
 
-        // TODO return a UserDetails instead
-        user_id
+    pub fn ingest_secret(&self) -> Option<&str> {
+        self.ingest_secret.as_deref()
+    }
+
+    // synthetic user for a user of an external system; created on their first action
+    pub fn external_user(
+        &mut self,
+        source: &str,
+        external_id: &str,
+        ip: IpAddr,
+    ) -> Result<Uuid, IngestError> {
+        if self.ingest_secret.is_none() {
+            return Err(IngestError::NotAllowed);
+        }
+        if self.closed {
+            return Err(IngestError::PollClosed);
+        }
+        let key = (source.to_owned(), external_id.to_owned());
+        if let Some(user_id) = self.external_users.get(&key) {
+            return Ok(*user_id);
+        }
+        let users = self.users.get_map_mut();
+        let user_id = users.generate_key();
+        let mut user = PollUser::new(user_id, ip);
+        user.source = Some(source.to_owned());
+        users.insert(user_id, user);
+        self.external_users.insert(key, user_id);
+        self.number_user(&user_id);
+        self.mark_changed();
+        Ok(user_id)
     }
 
     // we don't need to check validity of `user_id` on add_item() & vote_item()
@@ -321,164 +1224,3860 @@ impl Poll {
     pub fn add_item(
         &mut self,
         user_id: Uuid,
+        question_id: usize,
         item_text: String,
+        references: Vec<ItemPreview>,
     ) -> Result<usize, AddPollItemError> {
-        if self.add_item_permit == AddItemPermit::OwnerOnly && user_id != self.owner {
+        if self.closed {
+            return Err(AddPollItemError::PollClosed);
+        }
+        if !self.users.get_map().contains_key(&user_id) {
+            return Err(AddPollItemError::Banned);
+        }
+        if self.add_item_permit == AddItemPermit::OwnerOnly && !self.is_owned_by(&user_id) {
             return Err(AddPollItemError::NotOwner);
         }
+        if question_id >= self.questions.len() {
+            return Err(AddPollItemError::QuestionNotFound);
+        }
+        if self
+            .max_items_total
+            .is_some_and(|max| self.item_count() >= max as usize)
+        {
+            return Err(AddPollItemError::PollFull);
+        }
+        if self.remaining_items(Some(&user_id)) == Some(0) {
+            return Err(AddPollItemError::ItemLimitReached);
+        }
+        let item_text = text::normalize(&item_text);
+        let length = item_text.chars().count();
+        if length < self.min_item_length {
+            return Err(AddPollItemError::TooShort(self.min_item_length));
+        }
+        if length > self.max_item_length {
+            return Err(AddPollItemError::TooLong(self.max_item_length));
+        }
+        let item_text = self
+            .filter_text(item_text)
+            .ok_or(AddPollItemError::Rejected)?;
 
-        let item_id = self.items.len();
+        let item_id = self.next_item_id;
+        self.next_item_id += 1;
         let item = Item {
             id: item_id,
+            question_id,
             user_id,
+            html: self.render_markdown(&item_text),
             text: item_text,
+            attachment: None,
+            section: None,
+            link_preview: None,
             score: 0,
             votes: HashMap::new(),
+            rank: OrderedF64(0.0),
+            references,
+            reactions: HashMap::new(),
+            comments: Vec::new(),
+            reports: HashMap::new(),
+            created_at: Instant::now(),
+            added_at: OffsetDateTime::now_utc(),
+            edited: false,
         };
 
+        self.record_audit(user_id, None, AuditEvent::ItemAdded { item_id });
+        // moderated polls keep items of participants aside until owner approves them
+        if self.moderate_items && !self.is_owned_by(&user_id) {
+            self.pending_items.insert(item_id, item);
+        } else {
+            self.publish_item(item);
+        }
+        self.record_action(&user_id);
+        self.mark_changed();
+        Ok(item_id)
+    }
+
+    // lists a new or approved item in its question
+    fn publish_item(&mut self, item: Item) {
+        let (item_id, user_id, question_id) = (item.id, item.user_id, item.question_id);
+        let question = &mut self.questions[question_id];
+        question
+            .items_by_score
+            .insert((item.rank, Reverse(item_id)));
+        question.last_items.push(item_id);
+        self.update_histogram(None, item.score);
         self.items.insert(item_id, item);
-        self.items_by_score.insert((0, item_id));
         self.items_by_user.insert_vec(user_id, item_id);
-        self.last_items.push(item_id);
 
         // TODO this vote_item call should be optional/poll specific
         // ok to ignore err; we just created the item & we know that vote value is OK
-        let _ = self.vote_item(user_id, item_id, 1);
-        self.changed.update(true);
-        Ok(item_id)
+        // only exception is that user may have no credits left on quadratic voting
+        // authors shouldn't rate their own items on behalf of them
+        if self.questions[question_id].vote_mode != VoteMode::Rating {
+            let _ = self.vote_item(user_id, item_id, 1);
+        }
+        self.request_link_preview(item_id);
+        // others never see items of shadow banned users
+        if self.is_shadow_banned(&user_id) {
+            return;
+        }
+        for user in self.users.get_map_mut().values_mut() {
+            user.unseen_items += 1;
+        }
+        let text = self.items.get(&item_id).unwrap().text.clone();
+        self.record_activity(ActivityKind::ItemAdded { item_id, text });
     }
 
-    pub fn vote_item(
-        &mut self,
-        user_id: Uuid,
-        item_id: usize,
-        value: isize,
-    ) -> Result<(), VotePollItemError> {
-        if !self.value_range.contains(&value) {
-            return Err(VotePollItemError::InvalidValue);
+    // items that are listed or waiting for approval
+    fn item_count(&self) -> usize {
+        self.items.len() + self.pending_items.len()
+    }
+
+    // count of items user can add more, by both of the limits; `None` if there is no limit
+    fn remaining_items(&self, viewer: Option<&Uuid>) -> Option<u32> {
+        let user_items = viewer
+            .and_then(|user_id| self.items_by_user.get(user_id))
+            .map_or(0, |items| items.len())
+            + self
+                .pending_items
+                .values()
+                .filter(|item| viewer == Some(&item.user_id))
+                .count();
+        let for_user = self
+            .max_items_per_user
+            .map(|max| (max as usize).saturating_sub(user_items) as u32);
+        let for_poll = self
+            .max_items_total
+            .map(|max| (max as usize).saturating_sub(self.item_count()) as u32);
+        match (for_user, for_poll) {
+            (Some(for_user), Some(for_poll)) => Some(for_user.min(for_poll)),
+            (for_user, for_poll) => for_user.or(for_poll),
         }
-        if let Some(item) = self.items.get_mut(&item_id) {
-            let old_score = item.score;
+    }
 
-            // `.insert()` method, updates current vote of this user as well.
-            // so, no need to remove existing <user id, value> entry from `item.votes`
-            match item.votes.insert(user_id, value) {
-                Some(old_value) => item.score += value - old_value,
-                None => item.score += value,
-            }
-            if old_score != item.score {
-                if !self.items_by_score.remove(&(old_score, item_id)) {
-                    panic!("vote tuple expected in by_score map");
-                }
-                self.items_by_score.insert((item.score, item_id));
+    // whether item waits for approval of poll owner
+    pub fn is_pending(&self, item_id: usize) -> bool {
+        self.pending_items.contains_key(&item_id)
+    }
 
-                self.changed.update(true);
-            }
-        } else {
-            return Err(VotePollItemError::ItemNotFound);
+    pub fn approve_item(&mut self, user_id: Uuid, item_id: usize) -> Result<(), ModerateItemError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(ModerateItemError::NotOwner);
+        }
+        if self.closed {
+            return Err(ModerateItemError::PollClosed);
         }
+        let item = self
+            .pending_items
+            .remove(&item_id)
+            .ok_or(ModerateItemError::ItemNotFound)?;
+        self.record_audit(
+            user_id,
+            Some(item.user_id),
+            AuditEvent::ItemApproved { item_id },
+        );
+        self.publish_item(item);
+        self.mark_changed();
         Ok(())
     }
 
-    fn get_state(&self, user_id: &Uuid) -> PollState {
-        PollState {
-            poll_title: self.title.clone(),
-            top_items: self
-                .items_by_score
-                .iter()
-                .rev()
-                .take(10)
-                .map(|(_, item_id)| self.items.get(item_id).unwrap().to_state(user_id))
-                .collect(),
-            latest_items: self
-                .last_items
-                .iter()
-                .map(|item_id| self.items.get(item_id).unwrap().to_state(user_id))
-                .collect(),
-            user_items: self
-                .items_by_user
-                .get(user_id)
-                .unwrap_or(&vec![])
-                .iter()
-                .rev()
-                .map(|item_id| self.items.get(item_id).unwrap().to_state(user_id))
-                .collect(),
+    pub fn reject_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        reason: Option<String>,
+    ) -> Result<(), ModerateItemError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(ModerateItemError::NotOwner);
         }
-    }
-
+        let item = self
+            .pending_items
+            .remove(&item_id)
+            .ok_or(ModerateItemError::ItemNotFound)?;
+        if let Some(attachment_id) = item.attachment {
+            self.attachments.remove(&attachment_id);
+        }
+        self.record_audit(
+            user_id,
+            Some(item.user_id),
+            AuditEvent::ItemRejected { item_id },
+        );
+        let author = self
+            .users
+            .get_map()
+            .get(&item.user_id)
+            .map_or(Uuid::nil(), |user| user.public_id);
+        self.record_moderation(
+            user_id,
+            ModerationAction::RejectItem {
+                target: author,
+                text: item.text,
+            },
+            reason,
+        );
+        self.mark_changed();
+        Ok(())
+    }
+
+    pub fn vote_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        value: isize,
+    ) -> Result<(), VotePollItemError> {
+        self.cast_vote(user_id, item_id, value, false)
+    }
+
+    // toggles a reaction of the user on an item
+    pub fn react_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        emoji: &str,
+    ) -> Result<(), ReactItemError> {
+        if self.closed {
+            return Err(ReactItemError::PollClosed);
+        }
+        if !REACTIONS.contains(&emoji) {
+            return Err(ReactItemError::InvalidReaction);
+        }
+        let item = self
+            .items
+            .get_mut(&item_id)
+            .ok_or(ReactItemError::ItemNotFound)?;
+        let users = item.reactions.entry(emoji.to_string()).or_default();
+        if !users.remove(&user_id) {
+            users.insert(user_id);
+        }
+        self.record_action(&user_id);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // reverts latest vote change of the user
+    pub fn undo_last_action(&mut self, user_id: Uuid) -> Result<(), UndoError> {
+        let change = self
+            .users
+            .get_map_mut()
+            .get_mut(&user_id)
+            .and_then(|user| user.vote_history.pop_latest())
+            .ok_or(UndoError::NothingToUndo)?;
+        self.cast_vote(user_id, change.item_id, change.previous, true)?;
+        Ok(())
+    }
+
+    // undoing votes are neither limited by cooldown nor recorded to vote history
+    fn cast_vote(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        value: isize,
+        undo: bool,
+    ) -> Result<(), VotePollItemError> {
+        if self.closed {
+            return Err(VotePollItemError::PollClosed);
+        }
+        // banned user may still send a few messages until their connection is closed
+        if !self.users.get_map().contains_key(&user_id) {
+            return Err(VotePollItemError::Banned);
+        }
+        let scoring = match self.items.get(&item_id) {
+            Some(item) => &self.questions[item.question_id].scoring,
+            None => return Err(VotePollItemError::ItemNotFound),
+        };
+        if !scoring.value_range().contains(&value) {
+            return Err(VotePollItemError::InvalidValue);
+        }
+        if let Some(item) = self.items.get_mut(&item_id) {
+            let old_rank = item.rank;
+            let old_score = item.score;
+            let had_vote = item.votes.contains_key(&user_id);
+            if self.duplicate_vote_check == DuplicateVoteCheck::Reject && !had_vote && value != 0 {
+                let users = self.users.get_map();
+                let user = users
+                    .get(&user_id)
+                    .expect("user joined the poll before voting");
+                // every user of an external system has the IP address of that system
+                if user.source.is_none()
+                    && item.votes.keys().any(|voter_id| {
+                        users
+                            .get(voter_id)
+                            .is_some_and(|voter| voter.source.is_none() && voter.ip == user.ip)
+                    })
+                {
+                    return Err(VotePollItemError::DuplicateVote);
+                }
+            }
+
+            let user = self
+                .users
+                .get_map_mut()
+                .get_mut(&user_id)
+                .expect("user joined the poll before voting");
+
+            let now = Instant::now();
+            user.last_votes
+                .retain(|_, voted_at| now.duration_since(*voted_at) < self.vote_cooldown);
+            if !undo && user.last_votes.contains_key(&item_id) {
+                return Err(VotePollItemError::VoteCooldown);
+            }
+
+            if let Some(max_votes) = self.max_votes_per_user {
+                if !had_vote && value != 0 && user.voted_items >= max_votes {
+                    return Err(VotePollItemError::VoteLimitReached);
+                }
+            }
+
+            // every quadratic question spends from the same budget
+            if let Some(credits) = scoring.credits() {
+                let old_cost = item
+                    .votes
+                    .get(&user_id)
+                    .map_or(0, |vote| scoring.vote_cost(vote.value));
+                let spent_credits = user.spent_credits - old_cost + scoring.vote_cost(value);
+                if spent_credits > credits {
+                    return Err(VotePollItemError::NotEnoughCredits);
+                }
+                user.spent_credits = spent_credits;
+            }
+
+            // `.insert()` method, updates current vote of this user as well.
+            // so, no need to remove existing <user id, value> entry from `item.votes`
+            // zero votes are removed, so `item.votes` only holds actual votes
+            let old_vote = if value == 0 {
+                item.votes.remove(&user_id)
+            } else {
+                let vote = Vote {
+                    value,
+                    weight: user.vote_weight(),
+                    at: now,
+                };
+                item.votes.insert(user_id, vote)
+            };
+            user.last_votes.insert(item_id, now);
+            let previous = old_vote.as_ref().map_or(0, |vote| vote.value);
+            if !undo && previous != value {
+                user.vote_history.push(VoteChange { item_id, previous });
+            }
+            item.score +=
+                value * user.vote_weight() as isize - old_vote.map_or(0, |vote| vote.weighted());
+            let new_score = item.score;
+
+            match (had_vote, value != 0) {
+                (false, true) => user.voted_items += 1,
+                (true, false) => user.voted_items -= 1,
+                _ => (),
+            }
+
+            self.update_rank(item_id, old_rank);
+            // votes of the user are shown to them; so there is a change even if ranks are same
+            self.mark_changed();
+            self.update_histogram(Some(old_score), new_score);
+        } else {
+            return Err(VotePollItemError::ItemNotFound);
+        }
+        self.record_audit(user_id, None, AuditEvent::VoteCast { item_id, value });
+        self.record_action(&user_id);
+        Ok(())
+    }
+
+    pub fn edit_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        text: String,
+    ) -> Result<(), EditItemError> {
+        if self.closed {
+            return Err(EditItemError::PollClosed);
+        }
+        let text = text::normalize(&text);
+        let length = text.chars().count();
+        if length < self.min_item_length {
+            return Err(EditItemError::TooShort(self.min_item_length));
+        }
+        if length > self.max_item_length {
+            return Err(EditItemError::TooLong(self.max_item_length));
+        }
+        let text = self.filter_text(text).ok_or(EditItemError::Rejected)?;
+        let html = self.render_markdown(&text);
+        let by_owner = self.is_owned_by(&user_id);
+        let item = self
+            .items
+            .get_mut(&item_id)
+            .ok_or(EditItemError::ItemNotFound)?;
+        let author_id = item.user_id;
+        if !by_owner {
+            if user_id != author_id {
+                return Err(EditItemError::NotAllowed);
+            }
+            if item.created_at.elapsed() > self.edit_window {
+                return Err(EditItemError::EditWindowPassed);
+            }
+        }
+        let old_text = std::mem::replace(&mut item.text, text);
+        item.html = html;
+        item.link_preview = None;
+        item.edited = true;
+        // cached translations belong to the old text
+        self.translations.retain(|(id, _), _| *id != item_id);
+        self.request_link_preview(item_id);
+        self.record_audit(user_id, Some(author_id), AuditEvent::ItemEdited { item_id });
+
+        if user_id != author_id {
+            let author = self
+                .users
+                .get_map()
+                .get(&author_id)
+                .map_or(Uuid::nil(), |user| user.public_id);
+            self.record_moderation(
+                user_id,
+                ModerationAction::EditItem {
+                    target: author,
+                    text: old_text,
+                },
+                None,
+            );
+        }
+        self.record_action(&user_id);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // only author of the item and poll owner can delete it; votes on it are taken back
+    pub fn delete_item(&mut self, user_id: Uuid, item_id: usize) -> Result<(), DeleteItemError> {
+        if self.closed {
+            return Err(DeleteItemError::PollClosed);
+        }
+        let author_id = self
+            .items
+            .get(&item_id)
+            .ok_or(DeleteItemError::ItemNotFound)?
+            .user_id;
+        if user_id != author_id && !self.is_owned_by(&user_id) {
+            return Err(DeleteItemError::NotAllowed);
+        }
+
+        let item = self.remove_item(item_id);
+        self.record_audit(
+            user_id,
+            Some(author_id),
+            AuditEvent::ItemDeleted { item_id },
+        );
+        if user_id != author_id {
+            let author = self
+                .users
+                .get_map()
+                .get(&author_id)
+                .map_or(Uuid::nil(), |user| user.public_id);
+            self.record_moderation(
+                user_id,
+                ModerationAction::DeleteItem {
+                    target: author,
+                    text: item.text,
+                },
+                None,
+            );
+        }
+        self.record_action(&user_id);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // takes the item out of every list and undoes its votes; item must exist
+    fn remove_item(&mut self, item_id: usize) -> Item {
+        let item = self.items.remove(&item_id).expect("item exists");
+        let author_id = item.user_id;
+        if let Some(attachment_id) = item.attachment {
+            self.attachments.remove(&attachment_id);
+        }
+        let question = &mut self.questions[item.question_id];
+        question
+            .items_by_score
+            .remove(&(item.rank, Reverse(item_id)));
+        question.last_items.retain(|id| *id != item_id);
+        self.pinned_items.retain(|id| *id != item_id);
+        if let Some(item_ids) = self.items_by_user.get_mut(&author_id) {
+            item_ids.retain(|id| *id != item_id);
+        }
+        if let Some(count) = self.score_histogram.get_mut(&score_bucket(item.score)) {
+            *count -= 1;
+            if *count == 0 {
+                self.score_histogram.remove(&score_bucket(item.score));
+            }
+        }
+        for user in self.users.get_map_mut().values_mut() {
+            if let Some(vote) = item.votes.get(&user.id) {
+                user.voted_items -= 1;
+                user.spent_credits -= question.scoring.vote_cost(vote.value);
+            }
+            user.last_votes.remove(&item_id);
+            user.vote_history.retain(|change| change.item_id != item_id);
+        }
+        self.translations.retain(|(id, _), _| *id != item_id);
+        // deleted items are mostly typos and junk; they shouldn't stay on the feed
+        self.activities.retain(|activity| {
+            !matches!(activity.kind, ActivityKind::ItemAdded { item_id: id, .. } if id == item_id)
+        });
+        item
+    }
+
+    // moves votes and reactions of `source_id` to `target_id`, then removes the source
+    // users that voted both items keep their vote on the target; their source vote is taken back
+    pub fn merge_items(
+        &mut self,
+        user_id: Uuid,
+        source_id: usize,
+        target_id: usize,
+    ) -> Result<(), MergeItemsError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(MergeItemsError::NotOwner);
+        }
+        if self.closed {
+            return Err(MergeItemsError::PollClosed);
+        }
+        if source_id == target_id {
+            return Err(MergeItemsError::SameItem);
+        }
+        let (source, target) = match (self.items.get(&source_id), self.items.get(&target_id)) {
+            (Some(source), Some(target)) => (source, target),
+            _ => return Err(MergeItemsError::ItemNotFound),
+        };
+        if source.question_id != target.question_id {
+            return Err(MergeItemsError::DifferentQuestions);
+        }
+
+        let source = self.items.remove(&source_id).expect("item exists");
+        // target keeps its own image; it only takes the image of source if it has none
+        if let Some(attachment_id) = source.attachment {
+            let target = self.items.get_mut(&target_id).expect("item exists");
+            if target.attachment.is_none() {
+                target.attachment = Some(attachment_id);
+            } else {
+                self.attachments.remove(&attachment_id);
+            }
+        }
+        let question = &mut self.questions[source.question_id];
+        question
+            .items_by_score
+            .remove(&(source.rank, Reverse(source_id)));
+        question.last_items.retain(|id| *id != source_id);
+        // target takes the pin of the source
+        if let Some(position) = self.pinned_items.iter().position(|id| *id == source_id) {
+            if self.pinned_items.contains(&target_id) {
+                self.pinned_items.remove(position);
+            } else {
+                self.pinned_items[position] = target_id;
+            }
+        }
+        if let Some(item_ids) = self.items_by_user.get_mut(&source.user_id) {
+            item_ids.retain(|id| *id != source_id);
+        }
+        if let Some(count) = self.score_histogram.get_mut(&score_bucket(source.score)) {
+            *count -= 1;
+            if *count == 0 {
+                self.score_histogram.remove(&score_bucket(source.score));
+            }
+        }
+
+        let target = self.items.get_mut(&target_id).expect("item exists");
+        let old_rank = target.rank;
+        let old_score = target.score;
+        for (voter_id, vote) in source.votes {
+            if target.votes.contains_key(&voter_id) {
+                if let Some(voter) = self.users.get_map_mut().get_mut(&voter_id) {
+                    voter.voted_items -= 1;
+                    voter.spent_credits -= question.scoring.vote_cost(vote.value);
+                }
+            } else {
+                target.score += vote.weighted();
+                target.votes.insert(voter_id, vote);
+            }
+        }
+        for (emoji, users) in source.reactions {
+            target.reactions.entry(emoji).or_default().extend(users);
+        }
+        // comment ids are positions; so comments of the source are renumbered after the others
+        let offset = target.comments.len();
+        target
+            .comments
+            .extend(source.comments.into_iter().map(|comment| Comment {
+                id: comment.id + offset,
+                reply_to: comment.reply_to.map(|id| id + offset),
+                ..comment
+            }));
+        let new_score = target.score;
+        for user in self.users.get_map_mut().values_mut() {
+            user.last_votes.remove(&source_id);
+            user.vote_history
+                .retain(|change| change.item_id != source_id);
+        }
+        self.translations.retain(|(id, _), _| *id != source_id);
+        // references to the source are followed to the target
+        for merged_into in self.merged_items.values_mut() {
+            if *merged_into == source_id {
+                *merged_into = target_id;
+            }
+        }
+        self.merged_items.insert(source_id, target_id);
+        self.update_rank(target_id, old_rank);
+        self.update_histogram(Some(old_score), new_score);
+
+        let author = self
+            .users
+            .get_map()
+            .get(&source.user_id)
+            .map_or(Uuid::nil(), |user| user.public_id);
+        self.record_audit(
+            user_id,
+            Some(source.user_id),
+            AuditEvent::ItemsMerged {
+                item_id: source_id,
+                into: target_id,
+            },
+        );
+        self.record_moderation(
+            user_id,
+            ModerationAction::MergeItems {
+                target: author,
+                text: source.text,
+                into: target_id,
+            },
+            None,
+        );
+        self.mark_changed();
+        Ok(())
+    }
+
+    // flags the item for poll owner; each user can report an item once
+    pub fn report_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        reason: Option<String>,
+    ) -> Result<(), ReportItemError> {
+        if self.closed {
+            return Err(ReportItemError::PollClosed);
+        }
+        let reason = reason
+            .map(|reason| text::normalize(&reason))
+            .filter(|reason| !reason.is_empty());
+        if reason
+            .as_ref()
+            .is_some_and(|reason| reason.chars().count() > MAX_REPORT_REASON_LENGTH)
+        {
+            return Err(ReportItemError::ReasonTooLong);
+        }
+        let item = self
+            .items
+            .get_mut(&item_id)
+            .ok_or(ReportItemError::ItemNotFound)?;
+        if item.user_id == user_id {
+            return Err(ReportItemError::OwnItem);
+        }
+        if item.reports.contains_key(&user_id) {
+            return Err(ReportItemError::AlreadyReported);
+        }
+        item.reports.insert(user_id, reason);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // owner decided that the item can stay; it's not shown as reported anymore
+    pub fn dismiss_reports(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+    ) -> Result<(), ReportItemError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(ReportItemError::NotOwner);
+        }
+        let item = self
+            .items
+            .get_mut(&item_id)
+            .ok_or(ReportItemError::ItemNotFound)?;
+        let count = std::mem::take(&mut item.reports).len();
+        let (author_id, text) = (item.user_id, item.text.clone());
+        if count > 0 {
+            let author = self
+                .users
+                .get_map()
+                .get(&author_id)
+                .map_or(Uuid::nil(), |user| user.public_id);
+            self.record_moderation(
+                user_id,
+                ModerationAction::DismissReports {
+                    target: author,
+                    text,
+                    count,
+                },
+                None,
+            );
+            self.mark_changed();
+        }
+        Ok(())
+    }
+
+    // `reply_to` is the id of another comment on the same item; so discussions form threads
+    pub fn comment_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        reply_to: Option<usize>,
+        text: String,
+    ) -> Result<(), CommentItemError> {
+        if self.closed {
+            return Err(CommentItemError::PollClosed);
+        }
+        let text = text::normalize(&text);
+        if text.is_empty() {
+            return Err(CommentItemError::Empty);
+        }
+        if text.chars().count() > MAX_COMMENT_LENGTH {
+            return Err(CommentItemError::TooLong);
+        }
+        let text = self.filter_text(text).ok_or(CommentItemError::Rejected)?;
+        let item = self
+            .items
+            .get_mut(&item_id)
+            .ok_or(CommentItemError::ItemNotFound)?;
+        if item.comments.len() >= MAX_COMMENTS_PER_ITEM {
+            return Err(CommentItemError::TooManyComments);
+        }
+        if reply_to.is_some_and(|comment_id| comment_id >= item.comments.len()) {
+            return Err(CommentItemError::CommentNotFound);
+        }
+        item.comments.push(Comment {
+            id: item.comments.len(),
+            user_id,
+            reply_to,
+            text,
+            at: OffsetDateTime::now_utc(),
+        });
+        self.record_action(&user_id);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // comments are not a part of the state; clients ask them when they're about to show them
+    // comments of shadow banned users are only shown to themselves
+    pub fn comments(
+        &self,
+        user_id: Uuid,
+        item_id: usize,
+    ) -> Result<Vec<CommentState>, CommentItemError> {
+        let item = self
+            .items
+            .get(&item_id)
+            .filter(|item| !self.is_hidden_from(item, Some(&user_id)))
+            .ok_or(CommentItemError::ItemNotFound)?;
+        Ok(item
+            .comments
+            .iter()
+            .filter(|comment| {
+                comment.user_id == user_id || !self.is_shadow_banned(&comment.user_id)
+            })
+            .map(|comment| CommentState {
+                id: comment.id,
+                reply_to: comment.reply_to,
+                text: comment.text.clone(),
+                at: comment.at.unix_timestamp(),
+                is_author: comment.user_id == user_id,
+                by_owner: self.is_owned_by(&comment.user_id),
+                author: self
+                    .display_name(&comment.user_id)
+                    .filter(|_| self.show_authors),
+            })
+            .collect())
+    }
+
+    // pinned items are shown above every question, regardless of their scores
+    pub fn pin_item(
+        &mut self,
+        user_id: Uuid,
+        item_id: usize,
+        pinned: bool,
+    ) -> Result<(), PinItemError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(PinItemError::NotOwner);
+        }
+        if self.closed {
+            return Err(PinItemError::PollClosed);
+        }
+        if !self.items.contains_key(&item_id) {
+            return Err(PinItemError::ItemNotFound);
+        }
+        let is_pinned = self.pinned_items.contains(&item_id);
+        if pinned && !is_pinned {
+            if self.pinned_items.len() >= MAX_PINNED_ITEMS {
+                return Err(PinItemError::TooManyPinnedItems);
+            }
+            self.pinned_items.push(item_id);
+        } else if !pinned && is_pinned {
+            self.pinned_items.retain(|id| *id != item_id);
+        } else {
+            return Ok(());
+        }
+        self.mark_changed();
+        Ok(())
+    }
+
+    // clears every vote but keeps the items; used for running another voting round
+    pub fn reset_votes(&mut self, user_id: Uuid) -> Result<(), ResetVotesError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(ResetVotesError::NotOwner);
+        }
+        if self.closed {
+            return Err(ResetVotesError::PollClosed);
+        }
+        for item in self.items.values_mut() {
+            item.votes.clear();
+            item.score = 0;
+        }
+        self.rebuild_ranks();
+        self.score_histogram.clear();
+        if !self.items.is_empty() {
+            self.score_histogram
+                .insert(score_bucket(0), self.items.len());
+        }
+        // there is nothing left to undo, and nothing to cool down from
+        for user in self.users.get_map_mut().values_mut() {
+            user.spent_credits = 0;
+            user.voted_items = 0;
+            user.last_votes.clear();
+            user.vote_history.clear();
+        }
+        self.record_activity(ActivityKind::VotesReset);
+        self.record_audit(user_id, None, AuditEvent::VotesReset);
+        self.record_moderation(user_id, ModerationAction::ResetVotes, None);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // moves an item between buckets of score histogram; `old_score` is `None` for new items
+    fn update_histogram(&mut self, old_score: Option<isize>, new_score: isize) {
+        if let Some(old_score) = old_score {
+            if score_bucket(old_score) == score_bucket(new_score) {
+                return;
+            }
+            if let Some(count) = self.score_histogram.get_mut(&score_bucket(old_score)) {
+                *count -= 1;
+                if *count == 0 {
+                    self.score_histogram.remove(&score_bucket(old_score));
+                }
+            }
+        }
+        *self
+            .score_histogram
+            .entry(score_bucket(new_score))
+            .or_default() += 1;
+    }
+
+    // users are cleared when poll closes; session of the owner is still recognized after that
+    pub fn is_owned_by(&self, user_id: &Uuid) -> bool {
+        self.owner == Some(*user_id)
+    }
+
+    fn is_owner(&self, user_details: &UserDetails) -> bool {
+        self.owner.is_some_and(|owner| {
+            user_details.id == Some(owner) || self.users.search_user(user_details) == Some(owner)
+        })
+    }
+
+    // every item and vote of the poll; users are identified by `mapper`
+    pub fn export(
+        &self,
+        user_details: &UserDetails,
+        mapper: &mut dyn IdentityMapper,
+    ) -> Result<PollExport, ExportError> {
+        if !self.is_owner(user_details) {
+            return Err(ExportError::NotOwner);
+        }
+        // chronological; items that are added in the same instant are ordered by their ids
+        let mut items: Vec<&Item> = self.items.values().collect();
+        items.sort_by_key(|item| (item.added_at, item.id));
+        let items = items
+            .into_iter()
+            .map(|item| {
+                let mut votes: Vec<(&Uuid, &Vote)> = item.votes.iter().collect();
+                votes.sort_by_key(|(_, vote)| vote.at);
+                ItemExport {
+                    id: item.id,
+                    question_id: item.question_id,
+                    text: item.text.clone(),
+                    created_at: item.added_at.unix_timestamp(),
+                    author: mapper.identify(&item.user_id),
+                    score: item.score,
+                    votes: votes
+                        .into_iter()
+                        .map(|(voter_id, vote)| VoteExport {
+                            voter: mapper.identify(voter_id),
+                            value: vote.value,
+                            weight: vote.weight,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(PollExport {
+            poll_id: self.id.clone(),
+            title: self.title.clone(),
+            questions: self
+                .questions
+                .iter()
+                .map(|question| QuestionExport {
+                    id: question.id,
+                    title: question.title.clone(),
+                    vote_mode: question.vote_mode.clone(),
+                })
+                .collect(),
+            closed: self.closed,
+            items,
+            audit_log: self
+                .audit_log
+                .iter()
+                .rev()
+                .map(|record| record.export(mapper))
+                .collect(),
+        })
+    }
+
+    // statistics of the poll for its owner; histogram is kept up to date, so this is cheap
+    pub fn insights(&self, user_details: &UserDetails) -> Result<PollInsights, InsightsError> {
+        if !self.is_owner(user_details) {
+            return Err(InsightsError::NotOwner);
+        }
+        Ok(PollInsights {
+            item_count: self.items.len(),
+            participant_count: self.users.get_map().len(),
+            score_histogram: self
+                .score_histogram
+                .iter()
+                .map(|(from, count)| ScoreBucket {
+                    from: *from,
+                    to: *from + SCORE_BUCKET_WIDTH - 1,
+                    count: *count,
+                })
+                .collect(),
+        })
+    }
+
+    // recalculates rank of the item after its votes changed; returns whether rank is changed
+    fn update_rank(&mut self, item_id: usize, old_rank: OrderedF64) -> bool {
+        let item = self.items.get_mut(&item_id).expect("item exists");
+        let question = &mut self.questions[item.question_id];
+        item.rank = OrderedF64(
+            question
+                .scoring
+                .rank(item.score, &item.votes, Instant::now()),
+        );
+        if old_rank == item.rank {
+            return false;
+        }
+        if !question
+            .items_by_score
+            .remove(&(old_rank, Reverse(item_id)))
+        {
+            panic!("vote tuple expected in by_score map");
+        }
+        question
+            .items_by_score
+            .insert((item.rank, Reverse(item_id)));
+        true
+    }
+
+    // recalculates ranks of every item from scratch
+    fn rebuild_ranks(&mut self) {
+        let now = Instant::now();
+        for question in self.questions.iter_mut() {
+            question.items_by_score.clear();
+        }
+        for item in self.items.values_mut() {
+            let question = &mut self.questions[item.question_id];
+            item.rank = OrderedF64(question.scoring.rank(item.score, &item.votes, now));
+            question
+                .items_by_score
+                .insert((item.rank, Reverse(item.id)));
+        }
+    }
+
+    pub fn add_question(
+        &mut self,
+        user_id: Uuid,
+        title: String,
+        vote_mode: VoteMode,
+    ) -> Result<usize, AddQuestionError> {
+        if !self.is_owned_by(&user_id) {
+            return Err(AddQuestionError::NotOwner);
+        }
+        if self.closed {
+            return Err(AddQuestionError::PollClosed);
+        }
+        if self.questions.len() >= MAX_QUESTIONS {
+            return Err(AddQuestionError::TooManyQuestions);
+        }
+        let mut settings = self.settings.clone();
+        settings.vote_mode = vote_mode;
+        // same rules with poll creation; ranking method and credits are shared by every question
+        let unsupported = match settings.vote_mode {
+            VoteMode::Quadratic => !(1..=MAX_VOTE_CREDITS).contains(&settings.vote_credits),
+            VoteMode::Rating => settings.ranking_method == RankingMethod::Hot,
+            VoteMode::UpDown => false,
+            VoteMode::Approval => false,
+        } || (settings.vote_mode != VoteMode::UpDown
+            && settings.ranking_method == RankingMethod::Wilson);
+        if unsupported {
+            return Err(AddQuestionError::UnsupportedVoteMode);
+        }
+        let question_id = self.questions.len();
+        self.questions
+            .push(Question::new(question_id, title, &settings));
+        self.mark_changed();
+        Ok(question_id)
+    }
+
+    // `participant_id` is the public id of the user
+    pub fn set_user_weight(
+        &mut self,
+        owner_id: Uuid,
+        participant_id: Uuid,
+        weight: u32,
+    ) -> Result<(), SetUserWeightError> {
+        if !self.is_owned_by(&owner_id) {
+            return Err(SetUserWeightError::NotOwner);
+        }
+        if self.closed {
+            return Err(SetUserWeightError::PollClosed);
+        }
+        if !(MIN_USER_WEIGHT..=MAX_USER_WEIGHT).contains(&weight) {
+            return Err(SetUserWeightError::InvalidWeight);
+        }
+        let user_id = match self
+            .users
+            .get_map_mut()
+            .values_mut()
+            .find(|user| user.public_id == participant_id)
+        {
+            Some(user) => {
+                user.weight = weight;
+                user.id
+            }
+            None => return Err(SetUserWeightError::UserNotFound),
+        };
+
+        self.reweigh_votes(user_id);
+        self.record_audit(
+            owner_id,
+            Some(user_id),
+            AuditEvent::UserWeightSet { weight },
+        );
+        self.record_moderation(
+            owner_id,
+            ModerationAction::SetUserWeight {
+                target: participant_id,
+                weight,
+            },
+            None,
+        );
+        // participant list of the owner changes in any case
+        self.mark_changed();
+        Ok(())
+    }
+
+    // items and votes of a shadow banned participant are accepted as usual, but only they see
+    // them; unlike a ban, they have no reason to join again with another identity
+    pub fn shadow_ban(
+        &mut self,
+        owner_id: Uuid,
+        participant_id: Uuid,
+        banned: bool,
+    ) -> Result<(), ShadowBanError> {
+        if !self.is_owned_by(&owner_id) {
+            return Err(ShadowBanError::NotOwner);
+        }
+        if self.closed {
+            return Err(ShadowBanError::PollClosed);
+        }
+        let user = self
+            .users
+            .get_map_mut()
+            .values_mut()
+            .find(|user| user.public_id == participant_id)
+            .ok_or(ShadowBanError::UserNotFound)?;
+        if user.id == owner_id {
+            return Err(ShadowBanError::Owner);
+        }
+        if user.shadow_banned == banned {
+            return Ok(());
+        }
+        user.shadow_banned = banned;
+        let user_id = user.id;
+        self.reweigh_votes(user_id);
+        self.record_audit(
+            owner_id,
+            Some(user_id),
+            AuditEvent::UserShadowBanned { banned },
+        );
+        self.record_moderation(
+            owner_id,
+            ModerationAction::ShadowBan {
+                target: participant_id,
+                banned,
+            },
+            None,
+        );
+        self.mark_changed();
+        Ok(())
+    }
+
+    // closes every connection of the participant; they can join again
+    pub fn kick_user(&mut self, owner_id: Uuid, participant_id: Uuid) -> Result<(), BanUserError> {
+        let user_id = self.find_participant(owner_id, participant_id)?;
+        let user = self
+            .users
+            .get_map_mut()
+            .get_mut(&user_id)
+            .expect("participant is just found");
+        user.close_connections();
+        self.record_audit(owner_id, Some(user_id), AuditEvent::UserKicked);
+        self.record_moderation(
+            owner_id,
+            ModerationAction::KickUser {
+                target: participant_id,
+            },
+            None,
+        );
+        self.mark_changed();
+        Ok(())
+    }
+
+    // removes the participant and rejects their future joins; their items and votes are only
+    // removed if `remove_content` is set
+    pub fn ban_user(
+        &mut self,
+        owner_id: Uuid,
+        participant_id: Uuid,
+        remove_content: bool,
+    ) -> Result<(), BanUserError> {
+        let user_id = self.find_participant(owner_id, participant_id)?;
+        if remove_content {
+            let item_ids = self.items_by_user.remove(&user_id).unwrap_or_default();
+            for item_id in item_ids {
+                self.remove_item(item_id);
+            }
+            self.pending_items.retain(|_, item| item.user_id != user_id);
+            self.attachments
+                .retain(|_, attachment| attachment.user_id != user_id);
+            let voted_items: Vec<usize> = self
+                .items
+                .values()
+                .filter(|item| item.votes.contains_key(&user_id))
+                .map(|item| item.id)
+                .collect();
+            for item_id in voted_items {
+                let item = self.items.get_mut(&item_id).expect("item exists");
+                let old_rank = item.rank;
+                let old_score = item.score;
+                let vote = item.votes.remove(&user_id).expect("user voted this item");
+                item.score -= vote.weighted();
+                let new_score = item.score;
+                self.update_rank(item_id, old_rank);
+                self.update_histogram(Some(old_score), new_score);
+            }
+        }
+        self.record_audit(
+            owner_id,
+            Some(user_id),
+            AuditEvent::UserBanned { remove_content },
+        );
+        self.record_moderation(
+            owner_id,
+            ModerationAction::BanUser {
+                target: participant_id,
+                remove_content,
+            },
+            None,
+        );
+        // dropping the user drops their update channels as well; so their connections are closed
+        let user = self
+            .users
+            .remove_user(&user_id)
+            .expect("participant is just found");
+        self.users.deny(&user);
+        self.mark_changed();
+        Ok(())
+    }
+
+    // session id of a participant that poll owner can kick or ban
+    fn find_participant(&self, owner_id: Uuid, participant_id: Uuid) -> Result<Uuid, BanUserError> {
+        if !self.is_owned_by(&owner_id) {
+            return Err(BanUserError::NotOwner);
+        }
+        if self.closed {
+            return Err(BanUserError::PollClosed);
+        }
+        let user = self
+            .users
+            .get_map()
+            .values()
+            .find(|user| user.public_id == participant_id)
+            .ok_or(BanUserError::UserNotFound)?;
+        if user.id == owner_id {
+            return Err(BanUserError::Owner);
+        }
+        Ok(user.id)
+    }
+
+    fn is_shadow_banned(&self, user_id: &Uuid) -> bool {
+        self.users
+            .get_map()
+            .get(user_id)
+            .is_some_and(|user| user.shadow_banned)
+    }
+
+    // items of shadow banned users are hidden from everyone but their authors
+    fn is_hidden_from(&self, item: &Item, viewer: Option<&Uuid>) -> bool {
+        viewer != Some(&item.user_id) && self.is_shadow_banned(&item.user_id)
+    }
+
+    // existing votes of the user are counted with their current weight
+    fn reweigh_votes(&mut self, user_id: Uuid) {
+        let weight = self
+            .users
+            .get_map()
+            .get(&user_id)
+            .map_or(0, |user| user.vote_weight());
+        let voted_items: Vec<usize> = self
+            .items
+            .values()
+            .filter(|item| item.votes.contains_key(&user_id))
+            .map(|item| item.id)
+            .collect();
+        for item_id in voted_items {
+            let item = self.items.get_mut(&item_id).expect("item exists");
+            let old_rank = item.rank;
+            let old_score = item.score;
+            let vote = item.votes.get_mut(&user_id).expect("user voted this item");
+            item.score -= vote.weighted();
+            vote.weight = weight;
+            item.score += vote.weighted();
+            let new_score = item.score;
+            self.update_rank(item_id, old_rank);
+            self.update_histogram(Some(old_score), new_score);
+        }
+    }
+
+    // participants of the poll by their join order; only visible to poll owner
+    fn participants(&self) -> Vec<ParticipantState> {
+        let mut users: Vec<&PollUser> = self.users.get_map().values().collect();
+        users.sort_by_key(|user| user.joined_at);
+        let mut users_by_ip: HashMap<IpAddr, usize> = HashMap::new();
+        if self.duplicate_vote_check == DuplicateVoteCheck::Flag {
+            for user in users.iter().filter(|user| user.source.is_none()) {
+                *users_by_ip.entry(user.ip).or_default() += 1;
+            }
+        }
+        users
+            .into_iter()
+            .map(|user| ParticipantState {
+                id: user.public_id,
+                name: user.display_name(),
+                weight: user.weight,
+                shadow_banned: user.shadow_banned,
+                item_count: self
+                    .items_by_user
+                    .get(&user.id)
+                    .map_or(0, |items| items.len()),
+                is_owner: self.is_owned_by(&user.id),
+                shared_ip: user.source.is_none()
+                    && users_by_ip.get(&user.ip).is_some_and(|count| *count > 1),
+                source: user.source.clone(),
+            })
+            .collect()
+    }
+
+    // items of the question that look like `text`, most similar first
+    pub fn similar_items(&self, user_id: Uuid, question_id: usize, text: &str) -> Vec<SimilarItem> {
+        if text.trim().chars().count() < similarity::MIN_SIMILARITY_QUERY_LENGTH {
+            return vec![];
+        }
+        let query = similarity::trigrams(text);
+        let mut items: Vec<SimilarItem> = self
+            .items
+            .values()
+            .filter(|item| {
+                item.question_id == question_id && !self.is_hidden_from(item, Some(&user_id))
+            })
+            .filter_map(|item| {
+                similarity::similarity(&query, &similarity::trigrams(&item.text)).map(
+                    |similarity| SimilarItem {
+                        id: item.id,
+                        text: item.text.clone(),
+                        similarity,
+                    },
+                )
+            })
+            .collect();
+        items.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then(a.id.cmp(&b.id)));
+        items.truncate(SIMILAR_ITEM_COUNT);
+        items
+    }
+
+    pub fn item_text(&self, user_id: Uuid, item_id: usize) -> Option<String> {
+        self.items
+            .get(&item_id)
+            .filter(|item| !self.is_hidden_from(item, Some(&user_id)))
+            .map(|item| item.text.clone())
+    }
+
+    pub fn cached_translation(&self, item_id: usize, lang: &str) -> Option<String> {
+        self.translations.get(&(item_id, lang.to_owned())).cloned()
+    }
+
+    pub fn cache_translation(&mut self, item_id: usize, lang: String, text: String) {
+        self.translations.insert((item_id, lang), text);
+    }
+
+    // preview of an item to embed in items of other polls
+    // previews of merged items show the item they're merged into
+    // nobody sees previews of the items of shadow banned users
+    pub fn item_preview(&self, item_id: usize) -> Option<ItemPreview> {
+        let item_id = self.merged_items.get(&item_id).copied().unwrap_or(item_id);
+        let item = self
+            .items
+            .get(&item_id)
+            .filter(|item| !self.is_hidden_from(item, None))?;
+        Some(ItemPreview {
+            poll_id: self.id.clone(),
+            poll_title: self.title.clone(),
+            item_id,
+            text: item.text.clone(),
+            score: if self.hide_results_until_close && !self.closed {
+                None
+            } else {
+                Some(item.score)
+            },
+        })
+    }
+
+    fn record_activity(&mut self, kind: ActivityKind) {
+        self.activity_count += 1;
+        self.activities.push(Activity {
+            id: self.activity_count,
+            at: OffsetDateTime::now_utc(),
+            kind,
+        });
+    }
+
+    // moderation decisions are both logged and kept to be reviewed by poll owner
+    // `actor` is the user id; it's logged by public id of the user
+    fn record_moderation(&mut self, actor: Uuid, action: ModerationAction, reason: Option<String>) {
+        let actor = self
+            .users
+            .get_map()
+            .get(&actor)
+            .map_or(Uuid::nil(), |user| user.public_id);
+        info!(
+            poll_id = %self.id,
+            actor = %actor,
+            target = action.target().map(tracing::field::display),
+            action = action.name(),
+            reason = reason.as_deref(),
+            "moderation decision",
+        );
+        self.moderation_log.push(ModerationEntry {
+            at: OffsetDateTime::now_utc().unix_timestamp(),
+            actor,
+            action,
+            reason,
+        });
+    }
+
+    fn record_audit(&mut self, actor: Uuid, target: Option<Uuid>, event: AuditEvent) {
+        self.audit_log.push(AuditRecord {
+            at: OffsetDateTime::now_utc(),
+            actor,
+            target,
+            event,
+        });
+    }
+
+    // changes in the poll, oldest first; users are identified by `mapper` like in exports
+    pub fn audit_log(
+        &self,
+        user_details: &UserDetails,
+        mapper: &mut dyn IdentityMapper,
+    ) -> Result<Vec<AuditEntry>, ExportError> {
+        if !self.is_owner(user_details) {
+            return Err(ExportError::NotOwner);
+        }
+        Ok(self
+            .audit_log
+            .iter()
+            .rev()
+            .map(|record| record.export(mapper))
+            .collect())
+    }
+
+    // latest activities of this poll, newest first
+    pub fn activities(&self) -> Vec<Activity> {
+        self.activities.iter().cloned().collect()
+    }
+
+    fn record_action(&mut self, user_id: &Uuid) {
+        if let Some(user) = self.users.get_map_mut().get_mut(user_id) {
+            user.last_action = Instant::now();
+            user.unseen_items = 0;
+            user.nudged = false;
+        }
+    }
+
+    fn send_nudges(&mut self) {
+        let clock = Arc::new(self.clock_hint());
+        for user in self.users.get_map_mut().values_mut() {
+            if user.nudged
+                || user.unseen_items < NUDGE_MIN_UNSEEN_ITEMS
+                || user.last_action.elapsed() < NUDGE_IDLE_TIME
+            {
+                continue;
+            }
+            let message = Arc::new(PollEvent::Nudge(format!(
+                "{} new items were added since you last voted.",
+                user.unseen_items
+            )));
+            for view in [StateView::Personal, StateView::Shared] {
+                feed::publish(user.updates(view), &clock, vec![message.clone()]);
+            }
+            user.nudged = true;
+        }
+    }
+
+    fn top_item_ids(&self) -> Vec<Vec<usize>> {
+        self.questions
+            .iter()
+            .map(|question| question.top_item_ids())
+            .collect()
+    }
+
+    // recalculates ranks of every item; needed for time dependent ranking methods
+    fn rerank(&mut self) {
+        let top_before = self.top_item_ids();
+        self.rebuild_ranks();
+        if top_before != self.top_item_ids() {
+            self.mark_changed();
+        }
+    }
+
+    // `viewer` is `None` for someone who didn't join the poll
+    fn results_hidden_for(&self, viewer: Option<&Uuid>) -> bool {
+        self.hide_results_until_close
+            && !self.closed
+            && !viewer.is_some_and(|user_id| self.is_owned_by(user_id))
+    }
+
+    // `total_votes` is count of votes on every item of the poll
+    fn item_state(&self, item_id: &usize, viewer: Option<&Uuid>, total_votes: usize) -> ItemState {
+        let item = self.items.get(item_id).unwrap();
+        let mut state = item.to_state(viewer);
+        if self.show_authors {
+            state.author = self.display_name(&item.user_id);
+        }
+        if viewer.is_some_and(|user_id| self.is_owned_by(user_id)) && !item.reports.is_empty() {
+            state.reports = Some(ReportState {
+                count: item.reports.len(),
+                reasons: item.reports.values().flatten().cloned().collect(),
+            });
+        }
+        // votes of a shadow banned user don't change the score; but they still see them counted
+        if let Some(user) = viewer
+            .and_then(|user_id| self.users.get_map().get(user_id))
+            .filter(|user| user.shadow_banned)
+        {
+            if let Some(vote) = item.votes.get(&user.id) {
+                state.score = state
+                    .score
+                    .map(|score| score + vote.value * user.weight as isize);
+            }
+        }
+        if self.results_hidden_for(viewer) {
+            state.score = None;
+            return state;
+        }
+        state.stats = Some(item.stats(total_votes));
+        let vote_mode = &self.questions[item.question_id].vote_mode;
+        if *vote_mode == VoteMode::Rating {
+            state.rating = Some(RatingState {
+                average: item.average(),
+                count: item.vote_count(),
+            });
+        }
+        if *vote_mode == VoteMode::Approval {
+            // score is weighted; so it's compared to the weights of the voters
+            let weight = self.voter_weight(item.question_id);
+            state.approval_percentage = Some(if weight > 0 {
+                item.score as f64 * 100.0 / weight as f64
+            } else {
+                0.0
+            });
+        }
+        state
+    }
+
+    // total weight of the users that voted any item of the question; votes of shadow banned
+    // users have no weight, and votes of anonymized polls are still told apart by their voters
+    fn voter_weight(&self, question_id: usize) -> u32 {
+        self.items
+            .values()
+            .filter(|item| item.question_id == question_id)
+            .flat_map(|item| item.votes.iter())
+            .map(|(user_id, vote)| (user_id, vote.weight))
+            .collect::<HashMap<_, _>>()
+            .values()
+            .sum()
+    }
+
+    fn question_state(
+        &self,
+        question: &Question,
+        viewer: Option<&Uuid>,
+        total_votes: usize,
+        shuffled: bool,
+    ) -> QuestionState {
+        let ranked = self.ranked_items(question, viewer, shuffled);
+        let top_items = ranked
+            .iter()
+            .take(10)
+            .map(|item_id| self.item_state(item_id, viewer, total_votes))
+            .collect();
+        let sections = self
+            .sections
+            .iter()
+            .enumerate()
+            .map(|(section, name)| SectionState {
+                id: section,
+                name: name.clone(),
+                items: ranked
+                    .iter()
+                    .filter(|item_id| self.items[**item_id].section == Some(section))
+                    .map(|item_id| self.item_state(item_id, viewer, total_votes))
+                    .collect(),
+            })
+            .collect();
+
+        QuestionState {
+            id: question.id,
+            title: question.title.clone(),
+            vote_mode: question.vote_mode.clone(),
+            // value range of scoring is limited when downvotes are disabled
+            allow_downvotes: *question.scoring.value_range().start() < 0,
+            top_items,
+            sections,
+            latest_items: {
+                let mut item_ids: Vec<&usize> = question
+                    .last_items
+                    .iter()
+                    .filter(|item_id| !self.is_hidden_from(&self.items[*item_id], viewer))
+                    .collect();
+                if shuffled {
+                    item_ids.sort_by_key(|item_id| shuffle_key(viewer, **item_id));
+                }
+                item_ids
+                    .into_iter()
+                    .map(|item_id| self.item_state(item_id, viewer, total_votes))
+                    .collect()
+            },
+        }
+    }
+
+    // every item of the question, in the order they're listed to the user
+    fn ranked_items<'a>(
+        &'a self,
+        question: &'a Question,
+        viewer: Option<&Uuid>,
+        shuffled: bool,
+    ) -> Vec<&'a usize> {
+        let question_items = || {
+            self.items
+                .values()
+                .filter(|item| item.question_id == question.id)
+                .map(|item| &item.id)
+        };
+        let mut ranked: Vec<&usize> = if shuffled {
+            let mut item_ids: Vec<&usize> = question_items().collect();
+            item_ids.sort_by_key(|item_id| shuffle_key(viewer, **item_id));
+            item_ids
+        } else if self.results_hidden_for(viewer) {
+            // ranking reveals the results as well; so items are listed by insertion order
+            let mut item_ids: Vec<&usize> = question_items().collect();
+            item_ids.sort();
+            item_ids
+        } else {
+            question
+                .items_by_score
+                .iter()
+                .rev()
+                .map(|(_, Reverse(item_id))| item_id)
+                .collect()
+        };
+        ranked.retain(|item_id| !self.is_hidden_from(&self.items[*item_id], viewer));
+        ranked
+    }
+
+    // a page of every listed item of the question; state only has the first few of them
+    pub fn list_items(
+        &self,
+        user_id: &Uuid,
+        question_id: usize,
+        offset: usize,
+        limit: usize,
+        order: ItemOrder,
+    ) -> Result<ItemPage, ListItemsError> {
+        let question = self
+            .questions
+            .get(question_id)
+            .ok_or(ListItemsError::QuestionNotFound)?;
+        let shuffled = self.shuffle_items && !self.closed;
+        let mut item_ids = self.ranked_items(question, Some(user_id), shuffled);
+        match order {
+            ItemOrder::Ranked => {}
+            ItemOrder::Newest => item_ids.sort_by(|a, b| b.cmp(a)),
+            ItemOrder::Oldest => item_ids.sort(),
+        }
+        let total_votes = self.items.values().map(|item| item.vote_count()).sum();
+        Ok(ItemPage {
+            question_id,
+            offset,
+            total: item_ids.len(),
+            items: item_ids
+                .into_iter()
+                .skip(offset)
+                .take(limit.min(MAX_ITEM_PAGE_SIZE))
+                .map(|item_id| self.item_state(item_id, Some(user_id), total_votes))
+                .collect(),
+        })
+    }
+
+    pub fn get_state(&self, user_id: &Uuid) -> PollState {
+        self.user_state(Some(user_id), true)
+    }
+
+    // `viewer` is `None` for someone who didn't join the poll, which nothing in the state is
+    // about; `questions` is false when connections of the user only get shared questions, and
+    // they're left empty then
+    fn user_state(&self, viewer: Option<&Uuid>, questions: bool) -> PollState {
+        let results_hidden = self.results_hidden_for(viewer);
+        let owner = viewer.is_some_and(|user_id| self.is_owned_by(user_id));
+        let user = viewer.and_then(|user_id| self.users.get_map().get(user_id));
+        let total_votes = self.items.values().map(|item| item.vote_count()).sum();
+        // first items of a list get more votes; so every user sees items in a different order
+        let shuffled = self.shuffle_items && !self.closed;
+
+        PollState {
+            meta: PollMeta {
+                poll_title: self.title.clone(),
+                description: self.description.clone(),
+                sections: self.sections.clone(),
+                closed: self.closed,
+                is_owner: owner,
+                name: user.and_then(|user| user.name.clone()),
+                min_item_length: self.min_item_length,
+                max_item_length: self.max_item_length,
+                participants: owner.then(|| self.participants()),
+                moderation_log: owner.then(|| self.moderation_log.iter().cloned().collect()),
+                ingest_secret: self.ingest_secret.clone().filter(|_| owner),
+                results_hidden,
+                shuffled,
+                can_undo: user.is_some_and(|user| !user.vote_history.is_empty()),
+                draft_token: user.map_or(Uuid::nil(), |user| user.public_id),
+                remaining_credits: self
+                    .questions
+                    .iter()
+                    .find_map(|question| question.scoring.credits())
+                    .and_then(|credits| user.map(|user| credits - user.spent_credits)),
+                remaining_votes: self
+                    .max_votes_per_user
+                    .and_then(|max_votes| user.map(|user| max_votes - user.voted_items)),
+                remaining_items: self.remaining_items(viewer),
+            },
+            questions: if questions {
+                self.question_states(viewer)
+            } else {
+                Vec::new()
+            },
+            user_votes: {
+                let mut votes: Vec<(&Item, &Vote)> = self
+                    .items
+                    .values()
+                    .filter_map(|item| {
+                        viewer
+                            .and_then(|user_id| item.votes.get(user_id))
+                            .map(|vote| (item, vote))
+                    })
+                    .collect();
+                votes.sort_by_key(|(_, vote)| std::cmp::Reverse(vote.at));
+                votes
+                    .into_iter()
+                    .map(|(item, vote)| UserVoteState {
+                        item_id: item.id,
+                        text: item.text.clone(),
+                        value: vote.value,
+                    })
+                    .collect()
+            },
+            pinned_items: self
+                .pinned_items
+                .iter()
+                .filter(|item_id| !self.is_hidden_from(&self.items[*item_id], viewer))
+                .map(|item_id| self.item_state(item_id, viewer, total_votes))
+                .collect(),
+            user_items: viewer
+                .and_then(|user_id| self.items_by_user.get(user_id))
+                .unwrap_or(&vec![])
+                .iter()
+                .rev()
+                .map(|item_id| self.item_state(item_id, viewer, total_votes))
+                .collect(),
+            pending_items: if owner {
+                self.pending_items
+                    .values()
+                    .map(|item| ItemState {
+                        author: self.display_name(&item.user_id),
+                        ..item.to_state(viewer)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    fn question_states(&self, viewer: Option<&Uuid>) -> Vec<QuestionState> {
+        let total_votes = self.items.values().map(|item| item.vote_count()).sum();
+        let shuffled = self.shuffle_items && !self.closed;
+        self.questions
+            .iter()
+            .map(|question| self.question_state(question, viewer, total_votes, shuffled))
+            .collect()
+    }
+
+    // questions of the user are the ones everyone sees, apart from what's in `ItemOverlay`
+    fn shares_questions(&self, user_id: &Uuid) -> bool {
+        !self.is_owned_by(user_id)
+            && !self.is_shadow_banned(user_id)
+            && (!self.shuffle_items || self.closed)
+    }
+
+    // questions as a participant without any votes sees them; events of the ones that didn't
+    // change since shared connections got them last time are kept
+    fn update_shared_questions(&mut self) {
+        let questions = self
+            .question_states(None)
+            .into_iter()
+            .enumerate()
+            .map(|(index, question)| {
+                let question = PollEvent::Question(question);
+                match self.shared_questions.get(index) {
+                    Some(event) if **event == question => event.clone(),
+                    _ => Arc::new(question),
+                }
+            })
+            .collect();
+        self.shared_questions = questions;
+    }
+
+    // overlays of the wanted users, found in one pass over the items
+    fn item_overlays(&self, wanted: impl Fn(&Uuid) -> bool) -> HashMap<Uuid, ItemOverlay> {
+        let mut overlays: HashMap<Uuid, ItemOverlay> = HashMap::new();
+        for item in self.items.values() {
+            if wanted(&item.user_id) {
+                overlays
+                    .entry(item.user_id)
+                    .or_default()
+                    .authored
+                    .push(item.id);
+            }
+            for (user_id, vote) in item.votes.iter().filter(|(user_id, _)| wanted(user_id)) {
+                overlays
+                    .entry(*user_id)
+                    .or_default()
+                    .votes
+                    .insert(item.id, vote.value);
+            }
+            for emoji in REACTIONS {
+                let Some(users) = item.reactions.get(emoji) else {
+                    continue;
+                };
+                for user_id in users.iter().filter(|user_id| wanted(user_id)) {
+                    overlays
+                        .entry(*user_id)
+                        .or_default()
+                        .reactions
+                        .entry(item.id)
+                        .or_default()
+                        .push(emoji.to_string());
+                }
+            }
+        }
+        for overlay in overlays.values_mut() {
+            overlay.authored.sort();
+        }
+        overlays
+    }
+
+    fn item_overlay(&self, user_id: &Uuid) -> ItemOverlay {
+        self.item_overlays(|other| other == user_id)
+            .remove(user_id)
+            .unwrap_or_default()
+    }
+
+    // state as someone who didn't join the poll sees it; nothing in it is about a user
+    pub fn public_state(&self) -> PollState {
+        self.user_state(None, true)
+    }
+
+    fn clock_hint(&self) -> PollEvent {
+        PollEvent::Clock(ClockHint {
+            server_time: unix_millis(OffsetDateTime::now_utc()),
+            tick: self.broadcast_tick,
+        })
+    }
+
+    // version of poll state; it only increases, when changes are broadcast
+    pub fn version(&self) -> u64 {
+        self.broadcast_tick
+    }
+
+    // every section of the state, for a client that may have missed changes after `have_version`;
+    // `None` when nothing is sent to the user after that version
+    pub fn resync(&self, user_id: &Uuid, have_version: u64) -> Option<Vec<PollEvent>> {
+        let user = self.users.get_map().get(user_id)?;
+        if have_version >= user.synced_version {
+            return None;
+        }
+        Some(self.state_events(user_id))
+    }
+
+    // every section of the current state of the user, as events; overlay is only kept for
+    // shared connections
+    pub fn state_events(&self, user_id: &Uuid) -> Vec<PollEvent> {
+        let mut events = vec![self.clock_hint()];
+        events.extend(self.get_state(user_id).into_events());
+        events.push(PollEvent::ItemOverlay(self.item_overlay(user_id)));
+        events
+    }
+
+    // changes on every new version; closes when poll is dropped
+    pub fn watch_version(&self) -> watch::Receiver<u64> {
+        self.versions.subscribe()
+    }
+
     fn broadcast(&mut self) {
-        let all_users: Vec<Uuid> = self.users.get_map().keys().copied().collect();
-        for user_id in all_users.iter() {
-            let state = self.get_state(user_id);
+        self.broadcast_tick += 1;
+        self.versions.send_replace(self.broadcast_tick);
+        let clock = Arc::new(self.clock_hint());
+        // users without a connection get every section when they join again
+        let connected_users: Vec<Uuid> = self
+            .users
+            .get_map()
+            .values()
+            .filter(|user| user.is_connected())
+            .map(|user| user.id)
+            .collect();
+        // questions are made once for every shared connection, and their users only get what's
+        // different for them in an overlay
+        let shared_users: HashSet<Uuid> = connected_users
+            .iter()
+            .filter(|user_id| {
+                self.users.get_map()[*user_id]
+                    .shared_updates
+                    .receiver_count()
+                    > 0
+            })
+            .copied()
+            .collect();
+        let mut overlays = HashMap::new();
+        if !shared_users.is_empty() {
+            self.update_shared_questions();
+            overlays = self.item_overlays(|user_id| shared_users.contains(user_id));
+        }
+        for user_id in connected_users.iter() {
+            let overlay = shared_users
+                .contains(user_id)
+                .then(|| overlays.remove(user_id).unwrap_or_default());
+            self.update_user(user_id, &clock, overlay);
+        }
+        self.changed.update(false);
+    }
+
+    // makes the next updates of the user from the current state; `overlay` is only given when
+    // the user has shared connections
+    fn update_user(
+        &mut self,
+        user_id: &Uuid,
+        clock: &Arc<PollEvent>,
+        overlay: Option<ItemOverlay>,
+    ) {
+        let user = &self.users.get_map()[user_id];
+        let personal = user.personal_updates.receiver_count() > 0;
+        let shares_questions = overlay.is_some() && self.shares_questions(user_id);
+        // questions of the user are only made when a connection gets them
+        let state = self.user_state(Some(user_id), personal || !shares_questions);
+        let events: Vec<Arc<PollEvent>> = state.into_events().into_iter().map(Arc::new).collect();
+        let mut changed = false;
+        if let Some(overlay) = overlay {
+            let mut shared_events: Vec<Arc<PollEvent>> = if shares_questions {
+                events
+                    .iter()
+                    .filter(|event| !matches!(***event, PollEvent::Question(_)))
+                    .chain(self.shared_questions.iter())
+                    .cloned()
+                    .collect()
+            } else {
+                events.clone()
+            };
+            shared_events.push(Arc::new(PollEvent::ItemOverlay(overlay)));
+            changed |= feed::publish(&user.shared_updates, clock, shared_events);
+        }
+        if personal {
+            changed |= feed::publish(&user.personal_updates, clock, events);
+        }
+        if changed {
             self.users
                 .get_map_mut()
                 .get_mut(user_id)
-                .expect("user exists because we iterate same map")
-                .senders
-                .retain(|sender| sender.send(state.clone()).is_ok());
+                .expect("user exists because we just got it")
+                .synced_version = self.broadcast_tick;
         }
-        self.changed.update(false);
+    }
+}
+
+struct Question {
+    id: usize,
+    title: String,
+    vote_mode: VoteMode,
+    // decides valid votes and ranks of items
+    scoring: Box<dyn ScoreStrategy>,
+    // BTreeSet<(rank of item, reversed id of item)>, iterated in reverse for top items
+    // rank is the score of item, or its average rating on rating mode
+    // item ids increase by creation; so items with equal ranks are listed oldest first and
+    // they keep their relative order across broadcasts
+    items_by_score: BTreeSet<(OrderedF64, Reverse<usize>)>,
+    // id of item
+    last_items: RingBuffer<usize>,
+}
+
+impl Question {
+    fn new(id: usize, title: String, settings: &PollSettings) -> Self {
+        Self {
+            id,
+            title,
+            vote_mode: settings.vote_mode.clone(),
+            scoring: scoring::from_settings(settings),
+            items_by_score: BTreeSet::new(),
+            last_items: RingBuffer::new(10),
+        }
+    }
+
+    fn top_item_ids(&self) -> Vec<usize> {
+        self.items_by_score
+            .iter()
+            .rev()
+            .take(10)
+            .map(|(_, Reverse(item_id))| *item_id)
+            .collect()
     }
 }
 
 #[derive(Debug)]
 struct Item {
-    id: usize, // item id
+    id: usize,          // item id
+    question_id: usize, // id of question that item belongs to
 
-    #[allow(dead_code)]
     user_id: Uuid, // author id
 
-    text: String,                // text of item
-    score: isize,                // computed total score of item
-    votes: HashMap<Uuid, isize>, // user id, user vote value
+    text: String,                              // text of item
+    html: Option<String>,                      // rendered `text`, if poll uses markdown
+    attachment: Option<Uuid>,                  // id of attached image in `Poll.attachments`
+    section: Option<usize>,                    // position in `Poll.sections`
+    link_preview: Option<LinkPreview>,         // page of first link in text, once it's fetched
+    score: isize,                              // computed weighted total score of item
+    votes: HashMap<Uuid, Vote>,                // user id, user vote
+    rank: OrderedF64,                          // current key of this item in `Poll.items_by_score`
+    references: Vec<ItemPreview>,              // items referenced in text, as they were on creation
+    reactions: HashMap<String, HashSet<Uuid>>, // emoji, users reacted with it
+    created_at: Instant,                       // authors can only edit items for a while
+    added_at: OffsetDateTime,                  // wall clock time of `created_at`, shown to users
+    edited: bool,                              // text is changed after item is added
+    comments: Vec<Comment>,                    // ordered by comment id, which is the position
+    reports: HashMap<Uuid, Option<String>>,    // user id, reason they gave
+}
+
+#[derive(Debug)]
+struct Comment {
+    id: usize,
+    user_id: Uuid,
+    // `None` for comments on the item itself
+    reply_to: Option<usize>,
+    text: String,
+    at: OffsetDateTime,
 }
 
 impl Item {
-    fn to_state(&self, user_id: &Uuid) -> ItemState {
+    fn average(&self) -> f64 {
+        match scoring::total_weight(&self.votes) {
+            0 => 0.0,
+            weight => self.score as f64 / weight as f64,
+        }
+    }
+
+    // votes without weight are from shadow banned users; they're not counted
+    fn counted_votes(&self) -> impl Iterator<Item = &Vote> {
+        self.votes.values().filter(|vote| vote.weight > 0)
+    }
+
+    fn vote_count(&self) -> usize {
+        self.counted_votes().count()
+    }
+
+    fn stats(&self, total_votes: usize) -> VoteStats {
+        VoteStats {
+            vote_count: self.vote_count(),
+            upvotes: self.counted_votes().filter(|vote| vote.value > 0).count(),
+            downvotes: self.counted_votes().filter(|vote| vote.value < 0).count(),
+            share_percentage: if total_votes > 0 {
+                self.vote_count() as f64 * 100.0 / total_votes as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn to_state(&self, viewer: Option<&Uuid>) -> ItemState {
         ItemState {
             id: self.id,
             text: self.text.clone(),
-            score: self.score,
-            user_vote: *self.votes.get(user_id).unwrap_or(&0),
+            html: self.html.clone(),
+            attachment: self.attachment,
+            section: self.section,
+            link_preview: self.link_preview.clone(),
+            score: Some(self.score),
+            user_vote: viewer
+                .and_then(|user_id| self.votes.get(user_id))
+                .map_or(0, |vote| vote.value),
+            approval_percentage: None,
+            rating: None,
+            stats: None,
+            references: self.references.clone(),
+            question_id: self.question_id,
+            is_author: viewer == Some(&self.user_id),
+            edited: self.edited,
+            author: None,
+            created_at: self.added_at.unix_timestamp(),
+            comment_count: self.comments.len(),
+            reports: None,
+            reactions: REACTIONS
+                .iter()
+                .map(|emoji| {
+                    let users = self.reactions.get(*emoji);
+                    ReactionState {
+                        emoji: emoji.to_string(),
+                        count: users.map_or(0, |users| users.len()),
+                        reacted: viewer.is_some_and(|user_id| {
+                            users.is_some_and(|users| users.contains(user_id))
+                        }),
+                    }
+                })
+                .collect(),
         }
     }
 }
 
+// optional parts of a new item
+#[derive(Clone, Debug, Default)]
+pub struct ItemExtras {
+    // id of an image uploaded by the author
+    pub attachment: Option<Uuid>,
+    pub section: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct VoteChange {
+    item_id: usize,
+    // vote value before the change; 0 if there wasn't any
+    previous: isize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Activity {
+    pub id: usize,
+    pub at: OffsetDateTime,
+    pub kind: ActivityKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum ActivityKind {
+    Created,
+    ItemAdded { item_id: usize, text: String },
+    VotesReset,
+    Closed,
+}
+
+pub fn unix_millis(at: OffsetDateTime) -> i64 {
+    (at.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PollSummary {
+    pub id: String,
+    pub title: String,
+    pub closed: bool,
+}
+
+// sort key of an item on shuffled lists; order is random but same for a user across broadcasts
+fn shuffle_key(viewer: Option<&Uuid>, item_id: usize) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (viewer, item_id).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TallyMethod {
+    // sum of votes
+    Score,
+    // count of approvals
+    Approvals,
+    // weighted average of ratings
+    AverageRating,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PollResult {
+    pub poll_id: String,
+    pub title: String,
+    pub question_id: usize,
+    // title of the question
+    pub question: String,
+    // results are final only if poll is closed
+    pub closed: bool,
+    pub tally_method: TallyMethod,
+    // ids of items that share the first place; empty if nothing is voted yet
+    pub winners: Vec<usize>,
+    // every item, best first
+    pub standings: Vec<Standing>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Standing {
+    pub item_id: usize,
+    pub text: String,
+    pub tally: f64,
+    pub vote_count: usize,
+}
+
+// start of the histogram bucket that score falls into
+fn score_bucket(score: isize) -> isize {
+    score.div_euclid(SCORE_BUCKET_WIDTH) * SCORE_BUCKET_WIDTH
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PollInsights {
+    pub item_count: usize,
+    pub participant_count: usize,
+    // only non-empty buckets, ordered by their scores
+    pub score_histogram: Vec<ScoreBucket>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ScoreBucket {
+    // inclusive range of scores
+    pub from: isize,
+    pub to: isize,
+    // count of items in this range
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PollBadge {
+    pub poll_id: String,
+    pub title: String,
+    pub closed: bool,
+    pub results_hidden: bool,
+    // `None` if results are hidden or there are no items yet
+    pub top_item: Option<BadgeItem>,
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct ItemState {
-    pub id: usize,
+pub struct BadgeItem {
     pub text: String,
     pub score: isize,
-    pub user_vote: isize,
-}
-
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct PollState {
-    pub poll_title: String,
-    // TODO add AddItemPermit
-    pub top_items: Vec<ItemState>,
-    pub latest_items: Vec<ItemState>,
-    pub user_items: Vec<ItemState>,
+    pub vote_count: usize,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, IntoStaticStr)]
 pub enum UserCreateError {
     #[error("You can't add this user to poll, this user already exists.")]
     UserAlreadyExists,
+    #[error("You are banned from this poll.")]
+    Banned,
     // TODO add not enough details provided error
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, IntoStaticStr)]
 pub enum AddPollItemError {
     #[error("You have to be owner of this poll to add item.")]
     NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("No such question exists with this question ID.")]
+    QuestionNotFound,
+    #[error("Item text must be at least {0} characters long.")]
+    TooShort(usize),
+    #[error("Item text must be at most {0} characters long.")]
+    TooLong(usize),
+    #[error("This text contains words that are not allowed here.")]
+    Rejected,
+    #[error("No such image is uploaded by you.")]
+    AttachmentNotFound,
+    #[error("This image is already attached to another item.")]
+    AttachmentInUse,
+    #[error("No such section exists in this poll.")]
+    SectionNotFound,
+    #[error("You reached the item limit of this poll, delete one of your items to add another.")]
+    ItemLimitReached,
+    #[error("This poll can't have any more items.")]
+    PollFull,
+    #[error("You are banned from this poll.")]
+    Banned,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum SetSectionsError {
+    #[error("You have to be owner of this poll to change its sections.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("This poll can't have more than {} sections.", MAX_SECTIONS)]
+    TooManySections,
+    #[error("Section names cannot be empty.")]
+    EmptyName,
+    #[error(
+        "Section names must be at most {} characters long.",
+        MAX_SECTION_NAME_LENGTH
+    )]
+    NameTooLong,
+    #[error("There is more than one section named {0:?}.")]
+    DuplicateName(String),
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ReportItemError {
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("You can't report your own item.")]
+    OwnItem,
+    #[error("You already reported this item.")]
+    AlreadyReported,
+    #[error(
+        "Reason of report must be at most {} characters long.",
+        MAX_REPORT_REASON_LENGTH
+    )]
+    ReasonTooLong,
+    #[error("You have to be owner of this poll to dismiss reports.")]
+    NotOwner,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ModerateItemError {
+    #[error("You have to be owner of this poll to approve or reject items.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("No such item waits for approval.")]
+    ItemNotFound,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum AddAttachmentError {
+    #[error("You have to join this poll to upload images.")]
+    NotJoined,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("Only PNG, JPEG, GIF and WebP images can be uploaded.")]
+    UnsupportedType,
+    #[error("This poll can't have more than {} images.", MAX_ATTACHMENTS_PER_POLL)]
+    TooManyAttachments,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum AddQuestionError {
+    #[error("You have to be owner of this poll to add question.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("This poll can't have more questions.")]
+    TooManyQuestions,
+    #[error("This vote mode can't be used with ranking method or credits of this poll.")]
+    UnsupportedVoteMode,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ClosePollError {
+    #[error("You have to be owner of this poll to close it.")]
+    NotOwner,
+    #[error("This poll is already closed.")]
+    AlreadyClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ListItemsError {
+    #[error("No such question exists with this question ID.")]
+    QuestionNotFound,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum PollResultError {
+    #[error("Results of this poll are hidden until it's closed.")]
+    ResultsHidden,
+    #[error("No such question exists with this question ID.")]
+    QuestionNotFound,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ExportError {
+    #[error("You have to be owner of this poll to export it.")]
+    NotOwner,
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum InsightsError {
+    #[error("You have to be owner of this poll to see its insights.")]
+    NotOwner,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum UndoError {
+    #[error("There is nothing to undo.")]
+    NothingToUndo,
+    #[error(transparent)]
+    Vote(#[from] VotePollItemError),
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ResetVotesError {
+    #[error("You have to be owner of this poll to reset votes.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum SetUserWeightError {
+    #[error("You have to be owner of this poll to change vote weights.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("No such user exists in this poll.")]
+    UserNotFound,
+    #[error("Vote weight must be between {MIN_USER_WEIGHT} and {MAX_USER_WEIGHT}.")]
+    InvalidWeight,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum BanUserError {
+    #[error("You have to be owner of this poll to remove participants.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("No such user exists in this poll.")]
+    UserNotFound,
+    #[error("You can't remove yourself.")]
+    Owner,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ShadowBanError {
+    #[error("You have to be owner of this poll to ban participants.")]
+    NotOwner,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("No such user exists in this poll.")]
+    UserNotFound,
+    #[error("You can't ban yourself.")]
+    Owner,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
 pub enum VotePollItemError {
     // TODO add more info fields to this enum branch
     #[error("Provided vote value is invalid for this poll item.")]
     InvalidValue,
     #[error("No such item exists with this item ID.")]
     ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+    #[error("You don't have enough credits left for this vote.")]
+    NotEnoughCredits,
+    #[error("You reached the vote limit of this poll, take back a vote to vote this item.")]
+    VoteLimitReached,
+    #[error("You are changing your vote too fast, try again in a moment.")]
+    VoteCooldown,
+    #[error("Someone else from your network has already voted this item.")]
+    DuplicateVote,
+    #[error("You are banned from this poll.")]
+    Banned,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum IngestError {
+    #[error("This poll doesn't accept actions from external systems.")]
+    NotAllowed,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum EditItemError {
+    #[error("Only author of this item and poll owner can edit it.")]
+    NotAllowed,
+    #[error("This item can't be edited anymore.")]
+    EditWindowPassed,
+    #[error("Item text must be at least {0} characters long.")]
+    TooShort(usize),
+    #[error("Item text must be at most {0} characters long.")]
+    TooLong(usize),
+    #[error("This text contains words that are not allowed here.")]
+    Rejected,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum DeleteItemError {
+    #[error("Only author of this item and poll owner can delete it.")]
+    NotAllowed,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum MergeItemsError {
+    #[error("Only poll owner can merge items.")]
+    NotOwner,
+    #[error("An item can't be merged into itself.")]
+    SameItem,
+    #[error("Only items of the same question can be merged.")]
+    DifferentQuestions,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum CommentItemError {
+    #[error("Comment text cannot be empty.")]
+    Empty,
+    #[error("Comment is too long.")]
+    TooLong,
+    #[error("This text contains words that are not allowed here.")]
+    Rejected,
+    #[error("This item can't have more comments.")]
+    TooManyComments,
+    #[error("No such comment exists on this item.")]
+    CommentNotFound,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum SetNameError {
+    #[error("Name is too long.")]
+    TooLong,
+    #[error("This name can't be used.")]
+    InvalidName,
+    #[error("Someone else in this poll uses this name.")]
+    NameTaken,
+    #[error("You need to join this poll first.")]
+    UserNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum PinItemError {
+    #[error("Only poll owner can pin items.")]
+    NotOwner,
+    #[error("No more items can be pinned; unpin one first.")]
+    TooManyPinnedItems,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum ReactItemError {
+    #[error("This reaction is not available.")]
+    InvalidReaction,
+    #[error("No such item exists with this item ID.")]
+    ItemNotFound,
+    #[error("This poll is closed.")]
+    PollClosed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FilterAction, Pseudonyms, WordList};
+    use crate::test_support::{pass, user, Inspector, PollBuilder, DAY, INACTIVITY};
+
+    #[tokio::test(start_paused = true)]
+    async fn inactive_poll_is_closed_and_purged() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .retention(RetentionPolicy::Purge)
+            .build(&polls);
+
+        pass(INACTIVITY - Duration::from_secs(60)).await;
+        assert!(!test.poll.lock().unwrap().closed);
+
+        pass(Duration::from_secs(2 * 60)).await;
+        assert!(test.poll.lock().unwrap().closed);
+        assert!(polls.lock().unwrap().get_poll(&test.id).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn activity_keeps_poll_open() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .retention(RetentionPolicy::Purge)
+            .build(&polls);
+
+        for _ in 0..3 {
+            pass(INACTIVITY / 2).await;
+            test.poll
+                .lock()
+                .unwrap()
+                .add_item(test.owner_id, 0, "item".to_string(), vec![])
+                .unwrap();
+        }
+        pass(INACTIVITY / 2).await;
+        assert!(!test.poll.lock().unwrap().closed);
+
+        pass(INACTIVITY).await;
+        assert!(test.poll.lock().unwrap().closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_are_broadcast_without_polling() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let mut inspector = test.connect_owner();
+        pass(Duration::from_secs(1)).await;
+        inspector.drain();
+
+        for text in ["first", "second"] {
+            test.poll
+                .lock()
+                .unwrap()
+                .add_item(test.owner_id, 0, text.to_string(), vec![])
+                .unwrap();
+        }
+        pass(BROADCAST_DELAY * 2).await;
+        assert_eq!(test.poll.lock().unwrap().version(), 1);
+        assert!(!inspector.drain().is_empty());
+
+        // quiet polls don't broadcast anything
+        pass(Duration::from_secs(60)).await;
+        assert_eq!(test.poll.lock().unwrap().version(), 1);
+        assert!(inspector.drain().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn polls_can_set_their_broadcast_delay() {
+        let polls = Polls::new(Duration::ZERO, None);
+        polls
+            .lock()
+            .unwrap()
+            .set_broadcast_delay(Duration::from_secs(5));
+        let slow = PollBuilder::default().build(&polls);
+        let immediate = PollBuilder::default()
+            .with(|settings| settings.broadcast_delay_ms = Some(0))
+            .build(&polls);
+        pass(Duration::from_secs(1)).await;
+
+        for test in [&slow, &immediate] {
+            test.poll
+                .lock()
+                .unwrap()
+                .add_item(test.owner_id, 0, "item".to_string(), vec![])
+                .unwrap();
+        }
+        pass(Duration::from_millis(1)).await;
+        assert_eq!(immediate.poll.lock().unwrap().version(), 1);
+        assert_eq!(slow.poll.lock().unwrap().version(), 0);
+
+        pass(Duration::from_secs(5)).await;
+        assert_eq!(slow.poll.lock().unwrap().version(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_connections_get_same_questions() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let join_shared = |ip| {
+            let view = Subscription {
+                view: StateView::Shared,
+                ..Default::default()
+            };
+            let (user_id, feed) = test
+                .poll
+                .lock()
+                .unwrap()
+                .join_subscribed(user(ip), watch::channel(view).1)
+                .unwrap();
+            (user_id, Inspector::new(feed))
+        };
+        let (voter_id, mut voter) = join_shared([10, 0, 0, 1]);
+        let (_, mut other) = join_shared([10, 0, 0, 2]);
+        let item_id = {
+            let mut poll = test.poll.lock().unwrap();
+            let item_id = poll
+                .add_item(test.owner_id, 0, "item".to_string(), vec![])
+                .unwrap();
+            poll.vote_item(voter_id, item_id, 1).unwrap();
+            item_id
+        };
+        voter.drain();
+        other.drain();
+
+        pass(Duration::from_secs(1)).await;
+        let (voter_events, other_events) = (voter.drain(), other.drain());
+        let questions = |events: &[PollEvent]| -> Vec<QuestionState> {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    PollEvent::Question(question) => Some(question.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(questions(&voter_events), questions(&other_events));
+        let item = &questions(&voter_events)[0].top_items[0];
+        assert_eq!((item.id, item.user_vote), (item_id, 0));
+        let overlay = voter_events
+            .iter()
+            .find_map(|event| match event {
+                PollEvent::ItemOverlay(overlay) => Some(overlay),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(overlay.votes.get(&item_id), Some(&1));
+        // overlay of the other user didn't change
+        assert!(!other_events
+            .iter()
+            .any(|event| matches!(event, PollEvent::ItemOverlay(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delta_has_only_changed_items() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let (voter_id, _inspector) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        let first = poll
+            .add_item(user_id, 0, "first item".to_string(), vec![])
+            .unwrap();
+        let second = poll
+            .add_item(user_id, 0, "second item".to_string(), vec![])
+            .unwrap();
+        let before = poll.get_state(&user_id).questions.remove(0);
+
+        poll.vote_item(voter_id, second, 1).unwrap();
+        let after = poll.get_state(&user_id).questions.remove(0);
+        let delta = after.delta_since(&before).unwrap();
+        // shares of votes change on every item
+        assert!(delta.upserts.iter().any(|item| item.id == second));
+        assert!(delta.removes.is_empty());
+        assert_eq!(delta.top_items, Some(vec![second, first]));
+        assert_eq!(delta.latest_items, None);
+
+        // nothing changed since the last state
+        let delta = after.delta_since(&after).unwrap();
+        assert!(delta.upserts.is_empty() && delta.top_items.is_none());
+
+        let mut renamed = after.clone();
+        renamed.title = "another title".to_string();
+        assert_eq!(renamed.delta_since(&after), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_poll_is_kept_for_retention_period() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .retention(RetentionPolicy::Keep)
+            .build(&polls);
+
+        test.poll.lock().unwrap().close_by(test.owner_id).unwrap();
+        pass(DAY - Duration::from_secs(60)).await;
+        assert!(polls.lock().unwrap().get_poll(&test.id).is_some());
+
+        // expired polls are purged on hourly checks
+        pass(Duration::from_secs(2 * 60 * 60)).await;
+        assert!(polls.lock().unwrap().get_poll(&test.id).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_poll_is_read_only() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let owner_id = test.owner_id;
+        let item_id = test
+            .poll
+            .lock()
+            .unwrap()
+            .add_item(owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+
+        pass(INACTIVITY + Duration::from_secs(60)).await;
+        let mut poll = test.poll.lock().unwrap();
+        assert!(poll.closed);
+        assert!(matches!(
+            poll.add_item(owner_id, 0, "late".to_string(), vec![]),
+            Err(AddPollItemError::PollClosed)
+        ));
+        assert!(matches!(
+            poll.vote_item(owner_id, item_id, -1),
+            Err(VotePollItemError::PollClosed)
+        ));
+    }
+
+    #[test]
+    fn plain_users_are_found_by_session() {
+        let mut users = PlainUsers::new();
+        let user_id = users.create_user(user([10, 0, 0, 1])).unwrap();
+
+        let session = UserDetails {
+            ip: IpAddr::from([10, 0, 0, 2]),
+            id: Some(user_id),
+        };
+        assert_eq!(users.search_user(&session), Some(user_id));
+        // users of same network are different users
+        assert_eq!(users.search_user(&user([10, 0, 0, 1])), None);
+        assert!(users.create_user(user([10, 0, 0, 1])).is_ok());
+
+        users.clear();
+        assert_eq!(users.search_user(&session), None);
+    }
+
+    #[test]
+    fn ip_based_users_are_found_by_ip() {
+        let mut users = IPBasedUsers::new();
+        let user_id = users.create_user(user([10, 0, 0, 1])).unwrap();
+
+        assert_eq!(users.search_user(&user([10, 0, 0, 1])), Some(user_id));
+        assert_eq!(users.search_user(&user([10, 0, 0, 2])), None);
+        assert!(matches!(
+            users.create_user(user([10, 0, 0, 1])),
+            Err(UserCreateError::UserAlreadyExists)
+        ));
+
+        users.clear();
+        assert_eq!(users.search_user(&user([10, 0, 0, 1])), None);
+        assert!(users.get_map().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn users_get_every_section_on_join_then_only_changes() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (_, mut inspector) = test.join([10, 0, 0, 1]);
+        assert_eq!(
+            inspector.drain_kinds(),
+            [
+                "Clock",
+                "Meta",
+                "Question",
+                "PinnedItems",
+                "UserItems",
+                "PendingItems",
+                "UserVotes"
+            ]
+        );
+
+        test.poll
+            .lock()
+            .unwrap()
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        pass(Duration::from_secs(1)).await;
+        assert_eq!(inspector.drain_kinds(), ["Clock", "Question"]);
+
+        // nothing is sent when nothing changes
+        pass(Duration::from_secs(1)).await;
+        assert!(inspector.drain().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ip_based_poll_knows_users_by_ip() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _first_tab) = test.join([10, 0, 0, 1]);
+        let (same_id, _second_tab) = test.join([10, 0, 0, 1]);
+        let (other_id, _other) = test.join([10, 0, 0, 2]);
+        assert_eq!(user_id, same_id);
+        assert_ne!(user_id, other_id);
+        assert_eq!(
+            test.poll.lock().unwrap().users.get_map()[&user_id]
+                .personal_updates
+                .receiver_count(),
+            2
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn approval_votes_are_zero_or_one() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .vote_mode(VoteMode::Approval)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+
+        assert!(matches!(
+            poll.vote_item(user_id, item_id, -1),
+            Err(VotePollItemError::InvalidValue)
+        ));
+        poll.vote_item(user_id, item_id, 1).unwrap();
+        // authors approve their own items
+        assert_eq!(poll.items[&item_id].score, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn approval_percentages_are_relative_to_voters() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .vote_mode(VoteMode::Approval)
+            .user_lookup(UserLookupMethod::IPBased)
+            .retention(RetentionPolicy::Anonymize)
+            .build(&polls);
+        let (voter, _voter) = test.join([10, 0, 0, 1]);
+        let (idle, _idle) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        let idle_public_id = poll.users.get_map()[&idle].public_id;
+        let first = poll
+            .add_item(test.owner_id, 0, "first".to_string(), vec![])
+            .unwrap();
+        let second = poll
+            .add_item(test.owner_id, 0, "second".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(voter, first, 1).unwrap();
+
+        let percentages = |state: PollState| -> Vec<(usize, Option<f64>)> {
+            state.questions[0]
+                .top_items
+                .iter()
+                .map(|item| (item.id, item.approval_percentage))
+                .collect()
+        };
+        let expected = vec![(first, Some(100.0)), (second, Some(50.0))];
+        // users that didn't approve anything don't count
+        assert_eq!(percentages(poll.public_state()), expected);
+        poll.kick_user(test.owner_id, idle_public_id).unwrap();
+        assert_eq!(percentages(poll.public_state()), expected);
+        // nor does closing the poll change them, even after its votes are anonymized
+        poll.close_by(test.owner_id).unwrap();
+        assert_eq!(percentages(poll.public_state()), expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn approval_percentages_are_weighted() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .vote_mode(VoteMode::Approval)
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (voter, _voter) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let voter_public_id = poll.users.get_map()[&voter].public_id;
+        let first = poll
+            .add_item(test.owner_id, 0, "first".to_string(), vec![])
+            .unwrap();
+        let second = poll
+            .add_item(test.owner_id, 0, "second".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(voter, first, 1).unwrap();
+        poll.set_user_weight(test.owner_id, voter_public_id, 3)
+            .unwrap();
+
+        let percentage =
+            |poll: &Poll, item_id: usize| poll.item_state(&item_id, None, 0).approval_percentage;
+        assert_eq!(percentage(&poll, first), Some(100.0));
+        assert_eq!(percentage(&poll, second), Some(25.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn authors_are_shown_by_join_order() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .with(|settings| settings.show_authors = true)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(user_id, 0, "item".to_string(), vec![])
+            .unwrap();
+
+        let state = poll.item_state(&item_id, Some(&test.owner_id), 0);
+        assert_eq!(state.author.as_deref(), Some("Participant 2"));
+        poll.show_authors = false;
+        assert_eq!(
+            poll.item_state(&item_id, Some(&test.owner_id), 0).author,
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn names_are_unique_in_a_poll() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (first_id, _first) = test.join([10, 0, 0, 1]);
+        let (second_id, _second) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+
+        poll.set_name(first_id, " Ada ").unwrap();
+        assert_eq!(poll.display_name(&first_id).as_deref(), Some("Ada"));
+        assert!(matches!(
+            poll.set_name(second_id, "ada"),
+            Err(SetNameError::NameTaken)
+        ));
+        assert!(matches!(
+            poll.set_name(second_id, "Participant 2"),
+            Err(SetNameError::InvalidName)
+        ));
+
+        // removed names are free again
+        poll.set_name(first_id, "").unwrap();
+        assert_eq!(
+            poll.display_name(&first_id).as_deref(),
+            Some("Participant 2")
+        );
+        poll.set_name(second_id, "Ada").unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn votes_reorder_top_items() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let first = poll
+            .add_item(test.owner_id, 0, "first".to_string(), vec![])
+            .unwrap();
+        let second = poll
+            .add_item(test.owner_id, 0, "second".to_string(), vec![])
+            .unwrap();
+        // equal ranks are listed oldest first
+        assert_eq!(poll.questions[0].top_item_ids(), [first, second]);
+
+        poll.vote_item(user_id, second, 1).unwrap();
+        assert_eq!(poll.items[&second].score, 2);
+        assert_eq!(poll.questions[0].top_item_ids(), [second, first]);
+
+        poll.vote_item(user_id, first, 2).unwrap_err();
+        poll.vote_item(user_id, second, 0).unwrap();
+        assert_eq!(poll.questions[0].top_item_ids(), [first, second]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn vote_cooldown_limits_changes_on_same_item() {
+        let polls = Polls::new(Duration::from_secs(1), None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let item_id = test
+            .poll
+            .lock()
+            .unwrap()
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+
+        test.poll
+            .lock()
+            .unwrap()
+            .vote_item(user_id, item_id, 1)
+            .unwrap();
+        assert!(matches!(
+            test.poll.lock().unwrap().vote_item(user_id, item_id, -1),
+            Err(VotePollItemError::VoteCooldown)
+        ));
+
+        pass(Duration::from_secs(2)).await;
+        assert!(test
+            .poll
+            .lock()
+            .unwrap()
+            .vote_item(user_id, item_id, -1)
+            .is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn undo_reverts_latest_vote() {
+        let polls = Polls::new(Duration::from_secs(60), None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+
+        poll.vote_item(user_id, item_id, 1).unwrap();
+        // undoing is not limited by cooldown
+        poll.undo_last_action(user_id).unwrap();
+        // only the upvote of the author is left
+        assert_eq!(poll.items[&item_id].score, 1);
+        assert_eq!(poll.users.get_map()[&user_id].voted_items, 0);
+        assert!(matches!(
+            poll.undo_last_action(user_id),
+            Err(UndoError::NothingToUndo)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn votes_before_reset_cannot_be_undone() {
+        let polls = Polls::new(Duration::from_secs(60), None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(user_id, item_id, 1).unwrap();
+
+        poll.reset_votes(test.owner_id).unwrap();
+        assert!(!poll.get_state(&user_id).meta.can_undo);
+        assert!(matches!(
+            poll.undo_last_action(user_id),
+            Err(UndoError::NothingToUndo)
+        ));
+        assert_eq!(poll.items[&item_id].score, 0);
+        // cooldown of the reset vote is gone too
+        poll.vote_item(user_id, item_id, 1).unwrap();
+        assert_eq!(poll.items[&item_id].score, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn item_texts_are_limited() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .with(|settings| {
+                settings.min_item_length = 3;
+                settings.max_item_length = 5;
+            })
+            .build(&polls);
+        let mut poll = test.poll.lock().unwrap();
+
+        assert!(matches!(
+            poll.add_item(test.owner_id, 0, "ab".to_string(), vec![]),
+            Err(AddPollItemError::TooShort(3))
+        ));
+        assert!(matches!(
+            poll.add_item(test.owner_id, 0, "abcdef".to_string(), vec![]),
+            Err(AddPollItemError::TooLong(5))
+        ));
+        // limits are in characters, not bytes
+        let item_id = poll
+            .add_item(test.owner_id, 0, "çğışö".to_string(), vec![])
+            .unwrap();
+        assert!(matches!(
+            poll.edit_item(test.owner_id, item_id, "çğışöü".to_string()),
+            Err(EditItemError::TooLong(5))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn authors_can_edit_only_within_edit_window() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .with(|settings| settings.edit_window_minutes = 5)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let item_id = test
+            .poll
+            .lock()
+            .unwrap()
+            .add_item(user_id, 0, "tpyo".to_string(), vec![])
+            .unwrap();
+
+        test.poll
+            .lock()
+            .unwrap()
+            .edit_item(user_id, item_id, "typo".to_string())
+            .unwrap();
+        pass(Duration::from_secs(6 * 60)).await;
+        let mut poll = test.poll.lock().unwrap();
+        assert!(matches!(
+            poll.edit_item(user_id, item_id, "type".to_string()),
+            Err(EditItemError::EditWindowPassed)
+        ));
+        poll.edit_item(test.owner_id, item_id, "type".to_string())
+            .unwrap();
+        assert!(poll.items[&item_id].edited);
+        assert_eq!(poll.moderation_log.iter().count(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deleted_item_takes_its_votes_back() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (author_id, _author) = test.join([10, 0, 0, 1]);
+        let (voter_id, _voter) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(author_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(voter_id, item_id, 1).unwrap();
+
+        assert!(matches!(
+            poll.delete_item(voter_id, item_id),
+            Err(DeleteItemError::NotAllowed)
+        ));
+        poll.delete_item(author_id, item_id).unwrap();
+        assert!(poll.items.is_empty());
+        assert!(poll.questions[0].top_item_ids().is_empty());
+        assert!(poll.score_histogram.is_empty());
+        assert_eq!(poll.users.get_map()[&voter_id].voted_items, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pinned_items_are_limited_and_follow_deletions() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let mut poll = test.poll.lock().unwrap();
+        let item_ids: Vec<usize> = (0..=MAX_PINNED_ITEMS)
+            .map(|index| {
+                poll.add_item(test.owner_id, 0, format!("item {}", index), vec![])
+                    .unwrap()
+            })
+            .collect();
+
+        for item_id in &item_ids[..MAX_PINNED_ITEMS] {
+            poll.pin_item(test.owner_id, *item_id, true).unwrap();
+        }
+        assert!(matches!(
+            poll.pin_item(test.owner_id, item_ids[MAX_PINNED_ITEMS], true),
+            Err(PinItemError::TooManyPinnedItems)
+        ));
+
+        poll.delete_item(test.owner_id, item_ids[0]).unwrap();
+        poll.pin_item(test.owner_id, item_ids[MAX_PINNED_ITEMS], true)
+            .unwrap();
+        let state = poll.get_state(&test.owner_id);
+        assert_eq!(state.pinned_items.len(), MAX_PINNED_ITEMS);
+        assert_eq!(state.pinned_items[0].id, item_ids[1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn comment_replies_stay_in_their_threads_after_merge() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let target = poll
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        let source = poll
+            .add_item(user_id, 0, "same item".to_string(), vec![])
+            .unwrap();
+        poll.comment_item(user_id, target, None, "why?".to_string())
+            .unwrap();
+        poll.comment_item(user_id, source, None, "agreed".to_string())
+            .unwrap();
+        poll.comment_item(test.owner_id, source, Some(0), "me too".to_string())
+            .unwrap();
+        assert!(matches!(
+            poll.comment_item(user_id, source, Some(5), "lost".to_string()),
+            Err(CommentItemError::CommentNotFound)
+        ));
+        assert_eq!(
+            poll.items[&source].to_state(Some(&user_id)).comment_count,
+            2
+        );
+
+        poll.merge_items(test.owner_id, source, target).unwrap();
+        let comments = poll.comments(user_id, target).unwrap();
+        assert_eq!(comments.len(), 3);
+        assert_eq!(comments[2].text, "me too");
+        assert_eq!(comments[2].reply_to, Some(1));
+        assert!(comments[2].by_owner && !comments[2].is_author);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn merged_items_keep_one_vote_per_user() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (both_id, _both) = test.join([10, 0, 0, 1]);
+        let (source_only_id, _source_only) = test.join([10, 0, 0, 2]);
+        let mut owner = test.connect_owner();
+        let mut poll = test.poll.lock().unwrap();
+        let target = poll
+            .add_item(test.owner_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        let source = poll
+            .add_item(both_id, 0, "same item".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(both_id, target, 1).unwrap();
+        poll.vote_item(both_id, source, 1).unwrap();
+        poll.vote_item(source_only_id, source, 1).unwrap();
+
+        assert!(matches!(
+            poll.merge_items(both_id, source, target),
+            Err(MergeItemsError::NotOwner)
+        ));
+        poll.merge_items(test.owner_id, source, target).unwrap();
+        assert!(!poll.items.contains_key(&source));
+        // upvote of the owner as author, and one vote from each user
+        assert_eq!(poll.items[&target].score, 3);
+        assert_eq!(poll.users.get_map()[&both_id].voted_items, 1);
+        assert_eq!(poll.users.get_map()[&source_only_id].voted_items, 1);
+        assert_eq!(poll.item_preview(source).unwrap().item_id, target);
+
+        // owner reviews the merge on moderation log
+        owner.drain();
+        poll.broadcast();
+        let log = owner.drain().into_iter().find_map(|event| match event {
+            PollEvent::Meta(meta) => meta.moderation_log,
+            _ => None,
+        });
+        assert!(matches!(
+            log.as_deref(),
+            Some([ModerationEntry {
+                action: ModerationAction::MergeItems { into, .. },
+                ..
+            }]) if *into == target
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn filtered_words_are_masked_or_rejected() {
+        let masking = WordList::parse("heck", FilterAction::Mask);
+        let polls = Polls::new(Duration::ZERO, Some(Arc::new(masking)));
+        let test = PollBuilder::default().build(&polls);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(test.owner_id, 0, "what the heck".to_string(), vec![])
+            .unwrap();
+        assert_eq!(poll.items[&item_id].text, "what the ****");
+        poll.comment_item(test.owner_id, item_id, None, "HECK yes".to_string())
+            .unwrap();
+        assert_eq!(
+            poll.comments(test.owner_id, item_id).unwrap()[0].text,
+            "**** yes"
+        );
+
+        let rejecting = WordList::parse("heck", FilterAction::Reject);
+        let polls = Polls::new(Duration::ZERO, Some(Arc::new(rejecting)));
+        let test = PollBuilder::default().build(&polls);
+        let mut poll = test.poll.lock().unwrap();
+        assert!(matches!(
+            poll.add_item(test.owner_id, 0, "what the heck".to_string(), vec![]),
+            Err(AddPollItemError::Rejected)
+        ));
+        assert!(poll.items.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn markdown_is_rendered_only_when_enabled() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let plain = PollBuilder::default().build(&polls);
+        let mut poll = plain.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(plain.owner_id, 0, "**more** coffee".to_string(), vec![])
+            .unwrap();
+        assert_eq!(
+            poll.items[&item_id].to_state(Some(&plain.owner_id)).html,
+            None
+        );
+        drop(poll);
+
+        let formatted = PollBuilder::default()
+            .with(|settings| settings.markdown = true)
+            .build(&polls);
+        let mut poll = formatted.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(formatted.owner_id, 0, "**more** coffee".to_string(), vec![])
+            .unwrap();
+        let html = |poll: &Poll| {
+            poll.items[&item_id]
+                .to_state(Some(&formatted.owner_id))
+                .html
+        };
+        assert_eq!(html(&poll).unwrap(), "<p><strong>more</strong> coffee</p>");
+        poll.edit_item(formatted.owner_id, item_id, "_less_ coffee".to_string())
+            .unwrap();
+        assert_eq!(html(&poll).unwrap(), "<p><em>less</em> coffee</p>");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attachments_belong_to_one_item_of_their_uploader() {
+        const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let (other_id, _other) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        assert!(matches!(
+            poll.add_attachment(&user([10, 0, 0, 3]), Bytes::from_static(PNG)),
+            Err(AddAttachmentError::NotJoined)
+        ));
+        assert!(matches!(
+            poll.add_attachment(&user([10, 0, 0, 1]), Bytes::from_static(b"<svg/>")),
+            Err(AddAttachmentError::UnsupportedType)
+        ));
+        let attachment_id = poll
+            .add_attachment(&user([10, 0, 0, 1]), Bytes::from_static(PNG))
+            .unwrap();
+
+        let add = |poll: &mut Poll, user_id| {
+            let extras = ItemExtras {
+                attachment: Some(attachment_id),
+                ..Default::default()
+            };
+            poll.add_item_with(user_id, 0, "logo".to_string(), vec![], extras)
+        };
+        assert!(matches!(
+            add(&mut poll, other_id),
+            Err(AddPollItemError::AttachmentNotFound)
+        ));
+        let item_id = add(&mut poll, user_id).unwrap();
+        assert!(matches!(
+            add(&mut poll, user_id),
+            Err(AddPollItemError::AttachmentInUse)
+        ));
+        let state = poll.items[&item_id].to_state(Some(&other_id));
+        assert_eq!(state.attachment, Some(attachment_id));
+        assert_eq!(
+            poll.attachment(&attachment_id)
+                .unwrap()
+                .image_type
+                .content_type(),
+            "image/png"
+        );
+
+        poll.delete_item(user_id, item_id).unwrap();
+        assert!(poll.attachment(&attachment_id).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn blank_description_is_not_shown() {
+        let polls = Polls::new(Duration::ZERO, None);
+        for (description, shown) in [
+            (
+                Some("  Rules:\n  one item per person \n"),
+                Some("Rules:\n  one item per person"),
+            ),
+            (Some("   "), None),
+            (None, None),
+        ] {
+            let test = PollBuilder::default()
+                .with(|settings| settings.description = description.map(str::to_owned))
+                .build(&polls);
+            let state = test.poll.lock().unwrap().get_state(&test.owner_id);
+            assert_eq!(state.meta.description.as_deref(), shown);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn items_are_grouped_by_sections() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default().build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let sections = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        assert!(matches!(
+            poll.set_sections(user_id, sections(&["Went well"])),
+            Err(SetSectionsError::NotOwner)
+        ));
+        assert!(matches!(
+            poll.set_sections(test.owner_id, sections(&["Went well", " Went  well"])),
+            Err(SetSectionsError::DuplicateName(_))
+        ));
+        poll.set_sections(test.owner_id, sections(&["Went well", "Needs improvement"]))
+            .unwrap();
+
+        let add = |poll: &mut Poll, text: &str, section| {
+            let extras = ItemExtras {
+                section,
+                ..Default::default()
+            };
+            poll.add_item_with(user_id, 0, text.to_string(), vec![], extras)
+        };
+        let good = add(&mut poll, "pairing", Some(0)).unwrap();
+        let bad = add(&mut poll, "flaky tests", Some(1)).unwrap();
+        add(&mut poll, "no section", None).unwrap();
+        assert!(matches!(
+            add(&mut poll, "lost", Some(2)),
+            Err(AddPollItemError::SectionNotFound)
+        ));
+        let question = &poll.get_state(&user_id).questions[0];
+        assert_eq!(question.sections.len(), 2);
+        assert_eq!(question.sections[0].name, "Went well");
+        assert_eq!(question.sections[0].items[0].id, good);
+        assert_eq!(question.sections[1].items.len(), 1);
+        assert_eq!(question.sections[1].items[0].id, bad);
+
+        poll.set_sections(test.owner_id, sections(&["Went well"]))
+            .unwrap();
+        assert_eq!(poll.items[&bad].section, None);
+        assert_eq!(poll.items[&good].section, Some(0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn moderated_items_wait_for_approval() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .with(|settings| settings.moderate_items = true)
+            .build(&polls);
+        let (user_id, mut inspector) = test.join([10, 0, 0, 1]);
+        let mut owner = test.connect_owner();
+        let mut poll = test.poll.lock().unwrap();
+        let owners = poll
+            .add_item(test.owner_id, 0, "from owner".to_string(), vec![])
+            .unwrap();
+        assert!(!poll.is_pending(owners));
+        let approved = poll
+            .add_item(user_id, 0, "approve me".to_string(), vec![])
+            .unwrap();
+        let rejected = poll
+            .add_item(user_id, 0, "reject me".to_string(), vec![])
+            .unwrap();
+        assert!(poll.is_pending(approved) && poll.is_pending(rejected));
+        assert!(poll.vote_item(user_id, approved, 1).is_err());
+
+        inspector.drain();
+        owner.drain();
+        poll.broadcast();
+        assert!(!inspector.drain_kinds().contains(&"PendingItems"));
+        assert!(owner.drain_kinds().contains(&"PendingItems"));
+        assert_eq!(poll.get_state(&test.owner_id).pending_items.len(), 2);
+        assert!(poll.get_state(&user_id).pending_items.is_empty());
+
+        assert!(matches!(
+            poll.approve_item(user_id, approved),
+            Err(ModerateItemError::NotOwner)
+        ));
+        poll.approve_item(test.owner_id, approved).unwrap();
+        poll.reject_item(test.owner_id, rejected, Some("off topic".to_string()))
+            .unwrap();
+        assert!(!poll.is_pending(approved) && poll.items.contains_key(&approved));
+        assert!(!poll.items.contains_key(&rejected));
+        // author upvotes their item once it's listed
+        assert_eq!(poll.items[&approved].score, 1);
+        let state = poll.get_state(&test.owner_id);
+        assert!(state.pending_items.is_empty());
+        assert_eq!(state.questions[0].latest_items.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_are_shown_to_owner_until_dismissed() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (author, _author) = test.join([10, 0, 0, 1]);
+        let (reporter, _reporter) = test.join([10, 0, 0, 2]);
+        let (other, _other) = test.join([10, 0, 0, 3]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(author, 0, "buy a pony".to_string(), vec![])
+            .unwrap();
+
+        assert!(matches!(
+            poll.report_item(author, item_id, None),
+            Err(ReportItemError::OwnItem)
+        ));
+        poll.report_item(reporter, item_id, Some("  spam ".to_string()))
+            .unwrap();
+        assert!(matches!(
+            poll.report_item(reporter, item_id, None),
+            Err(ReportItemError::AlreadyReported)
+        ));
+        poll.report_item(other, item_id, Some(" ".to_string()))
+            .unwrap();
+
+        let reports = |poll: &Poll, user_id: &Uuid| {
+            poll.get_state(user_id).questions[0].latest_items[0]
+                .reports
+                .clone()
+        };
+        assert_eq!(reports(&poll, &reporter), None);
+        assert_eq!(
+            reports(&poll, &test.owner_id),
+            Some(ReportState {
+                count: 2,
+                reasons: vec!["spam".to_string()],
+            })
+        );
+
+        assert!(matches!(
+            poll.dismiss_reports(reporter, item_id),
+            Err(ReportItemError::NotOwner)
+        ));
+        poll.dismiss_reports(test.owner_id, item_id).unwrap();
+        assert_eq!(reports(&poll, &test.owner_id), None);
+        assert!(poll.items.contains_key(&item_id));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shadow_banned_users_only_see_themselves() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (troll, _troll) = test.join([10, 0, 0, 1]);
+        let (other, _other) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        let troll_public_id = poll.users.get_map()[&troll].public_id;
+        let item_id = poll
+            .add_item(other, 0, "fine item".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(troll, item_id, 1).unwrap();
+        assert_eq!(poll.items[&item_id].score, 2);
+
+        assert!(matches!(
+            poll.shadow_ban(other, troll_public_id, true),
+            Err(ShadowBanError::NotOwner)
+        ));
+        poll.shadow_ban(test.owner_id, troll_public_id, true)
+            .unwrap();
+        // existing votes don't count anymore
+        assert_eq!(poll.items[&item_id].score, 1);
+        let spam_id = poll.add_item(troll, 0, "spam".to_string(), vec![]).unwrap();
+        poll.vote_item(troll, item_id, -1).unwrap();
+        assert_eq!(poll.items[&item_id].score, 1);
+
+        let latest = |poll: &Poll, user_id: &Uuid| -> Vec<(usize, Option<isize>)> {
+            poll.get_state(user_id).questions[0]
+                .latest_items
+                .iter()
+                .map(|item| (item.id, item.score))
+                .collect()
+        };
+        assert_eq!(latest(&poll, &other), vec![(item_id, Some(1))]);
+        assert_eq!(latest(&poll, &test.owner_id), vec![(item_id, Some(1))]);
+        // troll sees their item and vote as if they counted
+        assert_eq!(
+            latest(&poll, &troll),
+            vec![(spam_id, Some(1)), (item_id, Some(0))]
+        );
+        // nor anywhere else
+        poll.comment_item(troll, item_id, None, "spam".to_string())
+            .unwrap();
+        assert!(poll.comments(other, item_id).unwrap().is_empty());
+        assert_eq!(poll.comments(troll, item_id).unwrap().len(), 1);
+        assert!(matches!(
+            poll.comments(other, spam_id),
+            Err(CommentItemError::ItemNotFound)
+        ));
+        let similar = |poll: &Poll, user_id: Uuid| -> Vec<usize> {
+            poll.similar_items(user_id, 0, "spam")
+                .iter()
+                .map(|item| item.id)
+                .collect()
+        };
+        assert!(similar(&poll, other).is_empty());
+        assert_eq!(similar(&poll, troll), vec![spam_id]);
+        assert_eq!(poll.item_text(other, spam_id), None);
+        assert_eq!(poll.item_text(troll, spam_id), Some("spam".to_string()));
+        assert!(poll.item_preview(spam_id).is_none());
+
+        poll.shadow_ban(test.owner_id, troll_public_id, false)
+            .unwrap();
+        assert_eq!(poll.items[&item_id].score, 0);
+        assert_eq!(latest(&poll, &other).len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn banned_users_are_disconnected_and_cant_join_again() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (kicked, mut kicked_tab) = test.join([10, 0, 0, 1]);
+        let (banned, mut banned_tab) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        let kicked_public_id = poll.users.get_map()[&kicked].public_id;
+        let banned_public_id = poll.users.get_map()[&banned].public_id;
+        let item_id = poll
+            .add_item(kicked, 0, "stays".to_string(), vec![])
+            .unwrap();
+        let spam_id = poll
+            .add_item(banned, 0, "spam".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(banned, item_id, 1).unwrap();
+
+        poll.kick_user(test.owner_id, kicked_public_id).unwrap();
+        kicked_tab.drain();
+        assert!(kicked_tab.is_disconnected());
+        assert_eq!(poll.join(user([10, 0, 0, 1])).unwrap().0, kicked);
+
+        assert!(matches!(
+            poll.ban_user(kicked, banned_public_id, true),
+            Err(BanUserError::NotOwner)
+        ));
+        poll.ban_user(test.owner_id, banned_public_id, true)
+            .unwrap();
+        banned_tab.drain();
+        assert!(banned_tab.is_disconnected());
+        assert!(!poll.items.contains_key(&spam_id));
+        assert_eq!(poll.items[&item_id].score, 1);
+        assert!(matches!(
+            poll.vote_item(banned, item_id, -1),
+            Err(VotePollItemError::Banned)
+        ));
+        assert!(matches!(
+            poll.join(user([10, 0, 0, 2])),
+            Err(UserCreateError::Banned)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changes_are_recorded_in_audit_log() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let owner = user([127, 0, 0, 1]);
+        let item_id = poll
+            .add_item(user_id, 0, "item".to_string(), vec![])
+            .unwrap();
+        poll.vote_item(test.owner_id, item_id, -1).unwrap();
+        poll.delete_item(test.owner_id, item_id).unwrap();
+
+        assert!(poll
+            .audit_log(&user([10, 0, 0, 1]), &mut Pseudonyms::default())
+            .is_err());
+        let log = poll.audit_log(&owner, &mut Pseudonyms::default()).unwrap();
+        let events: Vec<(&str, Option<&str>, &AuditEvent)> = log
+            .iter()
+            .map(|entry| (entry.actor.as_str(), entry.target.as_deref(), &entry.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("Voter-1", None, &AuditEvent::ItemAdded { item_id }),
+                ("Voter-1", None, &AuditEvent::VoteCast { item_id, value: 1 }),
+                (
+                    "Voter-2",
+                    None,
+                    &AuditEvent::VoteCast { item_id, value: -1 }
+                ),
+                (
+                    "Voter-2",
+                    Some("Voter-1"),
+                    &AuditEvent::ItemDeleted { item_id }
+                ),
+            ]
+        );
+        let export = poll.export(&owner, &mut Pseudonyms::default()).unwrap();
+        assert_eq!(export.audit_log, log);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn items_are_limited_per_user_and_per_poll() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .with(|settings| {
+                settings.max_items_per_user = 1;
+                settings.max_items_total = 2;
+            })
+            .build(&polls);
+        let (first, _first) = test.join([10, 0, 0, 1]);
+        let (second, _second) = test.join([10, 0, 0, 2]);
+        let mut poll = test.poll.lock().unwrap();
+        assert_eq!(poll.get_state(&first).meta.remaining_items, Some(1));
+
+        let item_id = poll.add_item(first, 0, "one".to_string(), vec![]).unwrap();
+        assert_eq!(poll.get_state(&first).meta.remaining_items, Some(0));
+        assert!(matches!(
+            poll.add_item(first, 0, "two".to_string(), vec![]),
+            Err(AddPollItemError::ItemLimitReached)
+        ));
+        poll.add_item(second, 0, "two".to_string(), vec![]).unwrap();
+        assert!(matches!(
+            poll.add_item(test.owner_id, 0, "three".to_string(), vec![]),
+            Err(AddPollItemError::PollFull)
+        ));
+
+        // deleted items don't count
+        poll.delete_item(first, item_id).unwrap();
+        assert_eq!(poll.get_state(&first).meta.remaining_items, Some(1));
+        poll.add_item(first, 0, "one again".to_string(), vec![])
+            .unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn version_increases_when_changes_are_broadcast() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut versions = test.poll.lock().unwrap().watch_version();
+        let version = test.poll.lock().unwrap().version();
+
+        pass(Duration::from_secs(1)).await;
+        assert!(!versions.has_changed().unwrap());
+
+        test.poll
+            .lock()
+            .unwrap()
+            .add_item(user_id, 0, "an item".to_string(), vec![])
+            .unwrap();
+        pass(Duration::from_secs(1)).await;
+        assert!(versions.has_changed().unwrap());
+        assert!(*versions.borrow_and_update() > version);
+        assert_eq!(*versions.borrow(), test.poll.lock().unwrap().version());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn public_state_is_not_personalized() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(user_id, 0, "an item".to_string(), vec![])
+            .unwrap();
+
+        let state = poll.public_state();
+        let item = &state.questions[0].top_items[0];
+        assert_eq!(item.id, item_id);
+        assert_eq!(item.score, Some(1));
+        assert_eq!(item.user_vote, 0);
+        assert!(!item.is_author);
+        assert!(!state.meta.is_owner);
+        assert!(state.user_items.is_empty());
+        assert!(state.user_votes.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn anonymized_poll_is_not_owned_by_anyone() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .retention(RetentionPolicy::Anonymize)
+            .with(|settings| settings.allow_ingestion = true)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_id = poll
+            .add_item(user_id, 0, "an item".to_string(), vec![])
+            .unwrap();
+        poll.report_item(test.owner_id, item_id, Some("spam".to_string()))
+            .unwrap();
+        poll.close_by(test.owner_id).unwrap();
+
+        let state = poll.public_state();
+        assert!(!state.meta.is_owner);
+        assert_eq!(state.meta.participants, None);
+        assert_eq!(state.meta.moderation_log, None);
+        assert_eq!(state.meta.ingest_secret, None);
+        let item = &state.questions[0].top_items[0];
+        assert!(!item.is_author);
+        assert_eq!(item.reports, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn every_item_can_be_listed_page_by_page() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let mut poll = test.poll.lock().unwrap();
+        let item_ids: Vec<usize> = (0..25)
+            .map(|n| {
+                poll.add_item(user_id, 0, format!("item number {}", n), vec![])
+                    .unwrap()
+            })
+            .collect();
+
+        let page = poll
+            .list_items(&user_id, 0, 20, 10, ItemOrder::Oldest)
+            .unwrap();
+        assert_eq!(page.total, 25);
+        let ids: Vec<usize> = page.items.iter().map(|item| item.id).collect();
+        assert_eq!(ids, item_ids[20..]);
+
+        let page = poll
+            .list_items(&user_id, 0, 0, 3, ItemOrder::Newest)
+            .unwrap();
+        let ids: Vec<usize> = page.items.iter().map(|item| item.id).collect();
+        assert_eq!(ids, [item_ids[24], item_ids[23], item_ids[22]]);
+
+        let page = poll
+            .list_items(&user_id, 0, 0, 1000, ItemOrder::Ranked)
+            .unwrap();
+        assert_eq!(page.items.len(), 25);
+        assert!(matches!(
+            poll.list_items(&user_id, 1, 0, 10, ItemOrder::Ranked),
+            Err(ListItemsError::QuestionNotFound)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_clients_that_missed_changes_are_resynced() {
+        let polls = Polls::new(Duration::ZERO, None);
+        let test = PollBuilder::default()
+            .user_lookup(UserLookupMethod::IPBased)
+            .build(&polls);
+        let (user_id, _inspector) = test.join([10, 0, 0, 1]);
+        let joined_at = test.poll.lock().unwrap().version();
+        assert!(test
+            .poll
+            .lock()
+            .unwrap()
+            .resync(&user_id, joined_at)
+            .is_none());
+
+        test.poll
+            .lock()
+            .unwrap()
+            .add_item(user_id, 0, "an item".to_string(), vec![])
+            .unwrap();
+        pass(Duration::from_secs(1)).await;
+        let poll = test.poll.lock().unwrap();
+        let events = poll.resync(&user_id, joined_at).unwrap();
+        assert!(
+            matches!(events[0], PollEvent::Clock(ClockHint { tick, .. }) if tick == poll.version())
+        );
+        assert!(events.iter().any(
+            |event| matches!(event, PollEvent::Question(question) if question.top_items.len() == 1)
+        ));
+        assert!(poll.resync(&user_id, poll.version()).is_none());
+    }
 }
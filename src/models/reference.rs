@@ -0,0 +1,68 @@
+use super::{ItemPreview, Polls};
+
+use std::sync::Mutex;
+
+// an item can't reference more items than this
+const MAX_REFERENCES: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemReference {
+    pub poll_id: String,
+    pub item_id: usize,
+}
+
+impl ItemReference {
+    fn new(poll_id: &str, item_id: &str) -> Option<Self> {
+        if poll_id.is_empty() || !poll_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        // ignore punctuation that follows the reference in a sentence
+        let item_id = item_id.trim_end_matches(|c: char| !c.is_ascii_digit());
+        Some(Self {
+            poll_id: poll_id.to_owned(),
+            item_id: item_id.parse().ok()?,
+        })
+    }
+
+    // accepts `anket://poll/<poll id>/item/<item id>` and `http(s)://<host>/p/<poll id>#item-<item id>`
+    fn parse(token: &str) -> Option<Self> {
+        if let Some(rest) = token.strip_prefix("anket://poll/") {
+            let (poll_id, item_id) = rest.split_once("/item/")?;
+            return Self::new(poll_id, item_id);
+        }
+        if token.starts_with("http://") || token.starts_with("https://") {
+            let (_, path) = token.split_once("/p/")?;
+            let (poll_id, item_id) = path.split_once("#item-")?;
+            return Self::new(poll_id, item_id);
+        }
+        None
+    }
+}
+
+pub fn find_references(text: &str) -> Vec<ItemReference> {
+    let mut references: Vec<ItemReference> = vec![];
+    for reference in text.split_whitespace().filter_map(ItemReference::parse) {
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+        if references.len() == MAX_REFERENCES {
+            break;
+        }
+    }
+    references
+}
+
+/// Finds item references in given text and creates previews of the existing ones.
+///
+/// Referenced polls are locked one by one; so this shouldn't be called while holding a lock of
+/// any poll.
+pub fn resolve_references(polls: &Mutex<Polls>, text: &str) -> Vec<ItemPreview> {
+    find_references(text)
+        .into_iter()
+        .filter_map(|reference| {
+            let poll = polls.lock().unwrap().get_poll(&reference.poll_id)?;
+            let preview = poll.lock().unwrap().item_preview(reference.item_id);
+            preview
+        })
+        .collect()
+}
@@ -0,0 +1,306 @@
+use super::{PollSettings, RankingMethod, VoteMode};
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+const HOT_HALF_LIFE: Duration = Duration::from_secs(60 * 60);
+// z value of 95% confidence level
+const WILSON_Z: f64 = 1.96;
+
+#[derive(Clone, Debug)]
+pub struct Vote {
+    pub value: isize,
+    // weight of the voter, assigned by poll owner
+    pub weight: u32,
+    pub at: Instant,
+}
+
+impl Vote {
+    pub fn weighted(&self) -> isize {
+        self.value * self.weight as isize
+    }
+}
+
+// total weight of the votes; votes are counted this many times
+pub fn total_weight(votes: &HashMap<Uuid, Vote>) -> u32 {
+    votes.values().map(|vote| vote.weight).sum()
+}
+
+/// Decides which votes are valid and how items are ranked by their votes.
+///
+/// `Poll` keeps score of an item as the weighted sum of its votes; strategies compute the rank that items
+/// are ordered by in `top_items`.
+pub trait ScoreStrategy: Send + Sync {
+    // valid value range for a single vote
+    fn value_range(&self) -> RangeInclusive<isize>;
+
+    // credit budget of every user; `None` means votes are free
+    fn credits(&self) -> Option<u32> {
+        None
+    }
+
+    // credits that a vote with this value costs
+    fn vote_cost(&self, _value: isize) -> u32 {
+        0
+    }
+
+    fn rank(&self, score: isize, votes: &HashMap<Uuid, Vote>, now: Instant) -> f64;
+
+    // ranks of time dependent strategies change without any votes; so they need to be recalculated
+    fn is_time_dependent(&self) -> bool {
+        false
+    }
+}
+
+pub fn from_settings(settings: &PollSettings) -> Box<dyn ScoreStrategy> {
+    let value_range = match settings.vote_mode {
+        VoteMode::UpDown => -1..=1,
+        VoteMode::Approval => 0..=1,
+        VoteMode::Quadratic => {
+            let max_votes = (settings.vote_credits as f64).sqrt() as isize;
+            -max_votes..=max_votes
+        }
+        // 0 means user takes back the rating
+        VoteMode::Rating => 0..=5,
+    };
+    let value_range = if settings.allow_downvotes {
+        value_range
+    } else {
+        0.max(*value_range.start())..=*value_range.end()
+    };
+
+    let strategy: Box<dyn ScoreStrategy> = match (&settings.vote_mode, &settings.ranking_method) {
+        (VoteMode::Rating, _) => Box::new(Average { value_range }),
+        (_, RankingMethod::Score) => Box::new(Sum { value_range }),
+        (_, RankingMethod::Hot) => Box::new(Hot {
+            value_range,
+            half_life: HOT_HALF_LIFE,
+        }),
+        (_, RankingMethod::Wilson) => Box::new(Wilson { value_range }),
+    };
+
+    match settings.vote_mode {
+        VoteMode::Quadratic => Box::new(Budgeted {
+            credits: settings.vote_credits,
+            inner: strategy,
+        }),
+        _ => strategy,
+    }
+}
+
+// ranks items by sum of their votes
+pub struct Sum {
+    pub value_range: RangeInclusive<isize>,
+}
+
+impl ScoreStrategy for Sum {
+    fn value_range(&self) -> RangeInclusive<isize> {
+        self.value_range.clone()
+    }
+    fn rank(&self, score: isize, _votes: &HashMap<Uuid, Vote>, _now: Instant) -> f64 {
+        score as f64
+    }
+}
+
+// ranks items by weighted average of their votes
+pub struct Average {
+    pub value_range: RangeInclusive<isize>,
+}
+
+impl ScoreStrategy for Average {
+    fn value_range(&self) -> RangeInclusive<isize> {
+        self.value_range.clone()
+    }
+    fn rank(&self, score: isize, votes: &HashMap<Uuid, Vote>, _now: Instant) -> f64 {
+        match total_weight(votes) {
+            0 => 0.0,
+            weight => score as f64 / weight as f64,
+        }
+    }
+}
+
+// ranks items by sum of their votes, where weight of a vote halves every `half_life`
+pub struct Hot {
+    pub value_range: RangeInclusive<isize>,
+    pub half_life: Duration,
+}
+
+impl ScoreStrategy for Hot {
+    fn value_range(&self) -> RangeInclusive<isize> {
+        self.value_range.clone()
+    }
+    fn rank(&self, _score: isize, votes: &HashMap<Uuid, Vote>, now: Instant) -> f64 {
+        votes
+            .values()
+            .map(|vote| {
+                let age = now.duration_since(vote.at).as_secs_f64();
+                vote.weighted() as f64 * 0.5_f64.powf(age / self.half_life.as_secs_f64())
+            })
+            .sum()
+    }
+    fn is_time_dependent(&self) -> bool {
+        true
+    }
+}
+
+// ranks items by lower bound of Wilson score confidence interval of their upvote ratio
+// an item with few votes can't outrank an item with a lot of mostly positive votes
+pub struct Wilson {
+    pub value_range: RangeInclusive<isize>,
+}
+
+impl ScoreStrategy for Wilson {
+    fn value_range(&self) -> RangeInclusive<isize> {
+        self.value_range.clone()
+    }
+    fn rank(&self, _score: isize, votes: &HashMap<Uuid, Vote>, _now: Instant) -> f64 {
+        let total = total_weight(votes) as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        let upvotes = votes
+            .values()
+            .filter(|vote| vote.value > 0)
+            .map(|vote| vote.weight)
+            .sum::<u32>() as f64;
+        let ratio = upvotes / total;
+        let z2 = WILSON_Z * WILSON_Z;
+        (ratio + z2 / (2.0 * total)
+            - WILSON_Z * ((ratio * (1.0 - ratio) + z2 / (4.0 * total)) / total).sqrt())
+            / (1.0 + z2 / total)
+    }
+}
+
+// every user has a credit budget, casting k votes on an item costs k² credits
+// ranking is left to the inner strategy
+pub struct Budgeted {
+    pub credits: u32,
+    pub inner: Box<dyn ScoreStrategy>,
+}
+
+impl ScoreStrategy for Budgeted {
+    fn value_range(&self) -> RangeInclusive<isize> {
+        self.inner.value_range()
+    }
+    fn credits(&self) -> Option<u32> {
+        Some(self.credits)
+    }
+    fn vote_cost(&self, value: isize) -> u32 {
+        value.pow(2) as u32
+    }
+    fn rank(&self, score: isize, votes: &HashMap<Uuid, Vote>, now: Instant) -> f64 {
+        self.inner.rank(score, votes, now)
+    }
+    fn is_time_dependent(&self) -> bool {
+        self.inner.is_time_dependent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategy(
+        vote_mode: VoteMode,
+        change: impl FnOnce(&mut PollSettings),
+    ) -> Box<dyn ScoreStrategy> {
+        let mut settings = PollSettings::new("poll");
+        settings.vote_mode = vote_mode;
+        change(&mut settings);
+        from_settings(&settings)
+    }
+
+    // votes by (value, weight), all cast at `at`
+    fn votes(votes: &[(isize, u32)], at: Instant) -> HashMap<Uuid, Vote> {
+        votes
+            .iter()
+            .map(|&(value, weight)| (Uuid::new_v4(), Vote { value, weight, at }))
+            .collect()
+    }
+
+    #[test]
+    fn value_ranges_follow_vote_mode() {
+        assert_eq!(strategy(VoteMode::UpDown, |_| {}).value_range(), -1..=1);
+        assert_eq!(strategy(VoteMode::Approval, |_| {}).value_range(), 0..=1);
+        assert_eq!(strategy(VoteMode::Rating, |_| {}).value_range(), 0..=5);
+        let quadratic = |credits| {
+            strategy(VoteMode::Quadratic, |settings| {
+                settings.vote_credits = credits
+            })
+        };
+        assert_eq!(quadratic(100).value_range(), -10..=10);
+        // votes that can't be afforded are out of range
+        assert_eq!(quadratic(50).value_range(), -7..=7);
+        let upvotes_only = strategy(VoteMode::Quadratic, |settings| {
+            settings.vote_credits = 50;
+            settings.allow_downvotes = false;
+        });
+        assert_eq!(upvotes_only.value_range(), 0..=7);
+    }
+
+    #[test]
+    fn quadratic_votes_cost_credits() {
+        let free = strategy(VoteMode::UpDown, |_| {});
+        assert_eq!(free.credits(), None);
+        assert_eq!(free.vote_cost(1), 0);
+
+        let budgeted = strategy(VoteMode::Quadratic, |settings| settings.vote_credits = 50);
+        assert_eq!(budgeted.credits(), Some(50));
+        assert_eq!(budgeted.vote_cost(3), 9);
+        assert_eq!(budgeted.vote_cost(-7), 49);
+        assert_eq!(budgeted.vote_cost(0), 0);
+    }
+
+    #[test]
+    fn sums_and_averages_are_weighted() {
+        let now = Instant::now();
+        let sum = Sum {
+            value_range: -1..=1,
+        };
+        assert_eq!(sum.rank(0, &HashMap::new(), now), 0.0);
+        assert_eq!(sum.rank(-3, &votes(&[(-1, 3)], now), now), -3.0);
+
+        let average = Average { value_range: 0..=5 };
+        assert_eq!(average.rank(0, &HashMap::new(), now), 0.0);
+        // votes of shadow banned users have no weight
+        assert_eq!(average.rank(0, &votes(&[(5, 0)], now), now), 0.0);
+        assert_eq!(average.rank(10, &votes(&[(4, 1), (2, 3)], now), now), 2.5);
+    }
+
+    #[test]
+    fn hot_ranks_halve_every_half_life() {
+        let hot = Hot {
+            value_range: -1..=1,
+            half_life: HOT_HALF_LIFE,
+        };
+        assert!(hot.is_time_dependent());
+        let now = Instant::now();
+        assert_eq!(hot.rank(0, &HashMap::new(), now), 0.0);
+        let votes = votes(&[(1, 2), (1, 2)], now);
+        assert_eq!(hot.rank(4, &votes, now), 4.0);
+        assert_eq!(hot.rank(4, &votes, now + HOT_HALF_LIFE), 2.0);
+        assert_eq!(hot.rank(4, &votes, now + HOT_HALF_LIFE * 2), 1.0);
+    }
+
+    #[test]
+    fn wilson_ranks_favor_more_votes() {
+        let wilson = Wilson {
+            value_range: -1..=1,
+        };
+        let now = Instant::now();
+        assert_eq!(wilson.rank(0, &HashMap::new(), now), 0.0);
+        assert_eq!(wilson.rank(0, &votes(&[(1, 0)], now), now), 0.0);
+
+        let few = wilson.rank(1, &votes(&[(1, 1)], now), now);
+        let mut many = vec![(1, 1); 10];
+        many.push((-1, 1));
+        let many = wilson.rank(9, &votes(&many, now), now);
+        assert!(0.0 < few && few < many && many < 1.0);
+        // weights count as that many votes
+        let weighted = wilson.rank(10, &votes(&[(1, 10)], now), now);
+        assert_eq!(weighted, wilson.rank(10, &votes(&[(1, 1); 10], now), now));
+    }
+}
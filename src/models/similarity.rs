@@ -0,0 +1,50 @@
+use std::collections::HashSet;
+
+// texts shorter than this aren't compared; a few letters look like too many items
+pub const MIN_SIMILARITY_QUERY_LENGTH: usize = 4;
+// items that share less than this ratio of their trigrams with the text are not suggested
+const SIMILARITY_THRESHOLD: f64 = 0.3;
+
+pub type Trigrams = HashSet<[char; 3]>;
+
+// trigrams of lowercased words; punctuation and extra whitespace don't make texts different
+pub fn trigrams(text: &str) -> Trigrams {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    if words.is_empty() {
+        return Trigrams::new();
+    }
+    // padding lets first letters of words count as much as the others
+    let padded: Vec<char> = format!("  {} ", words.join(" ")).chars().collect();
+    padded
+        .windows(3)
+        .map(|window| [window[0], window[1], window[2]])
+        .collect()
+}
+
+// Jaccard index of trigram sets; `None` if the texts are not similar enough
+pub fn similarity(a: &Trigrams, b: &Trigrams) -> Option<f64> {
+    let union = a.union(b).count();
+    if union == 0 {
+        return None;
+    }
+    let similarity = a.intersection(b).count() as f64 / union as f64;
+    (similarity >= SIMILARITY_THRESHOLD).then_some(similarity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rephrased_texts_are_similar() {
+        let item = trigrams("More coffee in the kitchen!");
+        assert!(similarity(&item, &trigrams("more coffee in kitchen")).is_some());
+        assert!(similarity(&item, &trigrams("MORE COFFEE, in the kitchen")).is_some());
+        assert!(similarity(&item, &trigrams("Remote work on Fridays")).is_none());
+        assert!(similarity(&trigrams(""), &trigrams("...")).is_none());
+    }
+}
@@ -0,0 +1,63 @@
+use unicode_normalization::UnicodeNormalization;
+
+// invisible formatting characters; they make same looking texts different, or reorder the text
+// around them. zero width joiners are kept, emoji sequences are built with them
+const INVISIBLE_CHARS: [char; 12] = [
+    '\u{200B}', // zero width space
+    '\u{2060}', // word joiner
+    '\u{FEFF}', // zero width no-break space
+    '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}', // bidirectional embeddings
+    '\u{2066}', '\u{2067}', '\u{2068}', '\u{2069}', // bidirectional isolates
+];
+
+// texts that look the same are stored the same; applied to every text users submit
+//
+// text is composed into NFC, control and invisible characters are removed, and every run of
+// whitespace becomes a single space
+pub fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for word in text
+        .nfc()
+        .filter(|c| !INVISIBLE_CHARS.contains(c))
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect::<String>()
+        .split_whitespace()
+    {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        normalized.push_str(word);
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed() {
+        assert_eq!(
+            normalize("  more \t coffee\n\nplease "),
+            "more coffee please"
+        );
+        assert_eq!(normalize("\u{00A0}coffee\u{3000}"), "coffee");
+        assert_eq!(normalize(" \n\t "), "");
+    }
+
+    #[test]
+    fn decomposed_letters_are_composed() {
+        // `e` followed by a combining acute accent
+        assert_eq!(normalize("cafe\u{0301}"), "caf\u{00E9}");
+        assert_eq!(normalize("cafe\u{0301}"), normalize("caf\u{00E9}"));
+    }
+
+    #[test]
+    fn control_and_invisible_characters_are_removed() {
+        assert_eq!(normalize("tea\u{0000}time"), "tea time");
+        assert_eq!(normalize("tea\u{200B}time"), "teatime");
+        assert_eq!(normalize("\u{202E}emit aet"), "emit aet");
+        // zero width joiner belongs to the emoji
+        assert_eq!(normalize("👩\u{200D}💻"), "👩\u{200D}💻");
+    }
+}
@@ -0,0 +1,190 @@
+use crate::models::LinkPreview;
+use hyper::{client::HttpConnector, Body, Client, Request, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use regex::Regex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use thiserror::Error;
+
+// pages that don't answer in this long are not previewed
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+// title and description are in the head of pages; rest of the page is not read
+const MAX_PAGE_SIZE: usize = 256 * 1024;
+const MAX_TITLE_LENGTH: usize = 120;
+const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+/// Fetches titles and descriptions of pages that items link to.
+///
+/// Only pages on allowed hosts are fetched; so items can't make this instance request arbitrary
+/// addresses, like services in its private network.
+pub struct LinkPreviewer {
+    client: Client<HttpsConnector<HttpConnector>>,
+    // domains like `example.com`; their subdomains are allowed as well
+    allowed_hosts: Vec<String>,
+}
+
+impl LinkPreviewer {
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder().build(connector),
+            allowed_hosts,
+        }
+    }
+
+    // first link in `text` that can be previewed
+    pub fn find_link(&self, text: &str) -> Option<Uri> {
+        text.split_whitespace()
+            .map(|word| word.trim_start_matches(|c: char| "([{<'\"".contains(c)))
+            .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+            .map(|word| word.trim_end_matches(|c: char| ".,;:!?)]}>'\"".contains(c)))
+            .filter_map(|word| word.parse::<Uri>().ok())
+            .find(|url| self.is_allowed(url))
+    }
+
+    fn is_allowed(&self, url: &Uri) -> bool {
+        let Some(host) = url.host().map(|host| host.to_ascii_lowercase()) else {
+            return false;
+        };
+        self.allowed_hosts.iter().any(|allowed| {
+            host == *allowed
+                || host
+                    .strip_suffix(allowed.as_str())
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        })
+    }
+
+    pub async fn fetch(&self, url: Uri) -> Result<LinkPreview, PreviewError> {
+        let request = Request::get(url.clone())
+            .header(hyper::header::USER_AGENT, "anket link preview")
+            .header(hyper::header::ACCEPT, "text/html")
+            .body(Body::empty())
+            .expect("request parts are valid");
+        let page = tokio::time::timeout(FETCH_TIMEOUT, async {
+            let mut response = self.client.request(request).await?;
+            if !response.status().is_success() {
+                return Err(PreviewError::Status(response.status().as_u16()));
+            }
+            let mut page = Vec::new();
+            while let Some(chunk) = hyper::body::HttpBody::data(response.body_mut()).await {
+                page.extend_from_slice(&chunk?);
+                if page.len() >= MAX_PAGE_SIZE {
+                    break;
+                }
+            }
+            Ok(page)
+        })
+        .await
+        .map_err(|_| PreviewError::Timeout)??;
+
+        let page = String::from_utf8_lossy(&page);
+        let title = page_title(&page).ok_or(PreviewError::NoTitle)?;
+        Ok(LinkPreview {
+            url: url.to_string(),
+            title: shorten(&title, MAX_TITLE_LENGTH),
+            description: page_description(&page)
+                .map(|description| shorten(&description, MAX_DESCRIPTION_LENGTH)),
+        })
+    }
+}
+
+// `og:title` is preferred; it usually doesn't have the site name appended
+fn page_title(page: &str) -> Option<String> {
+    meta_content(page, "og:title").or_else(|| {
+        static TITLE: OnceLock<Regex> = OnceLock::new();
+        let title = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+        title
+            .captures(page)
+            .map(|captures| unescape(&captures[1]))
+            .filter(|title| !title.is_empty())
+    })
+}
+
+fn page_description(page: &str) -> Option<String> {
+    meta_content(page, "og:description").or_else(|| meta_content(page, "description"))
+}
+
+// content of `<meta property="name">` or `<meta name="name">`
+fn meta_content(page: &str, name: &str) -> Option<String> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+    let attribute = ATTRIBUTE
+        .get_or_init(|| Regex::new(r#"(?is)([a-z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+    meta.find_iter(page).find_map(|tag| {
+        let mut matches = false;
+        let mut content = None;
+        for captures in attribute.captures_iter(tag.as_str()) {
+            let value = captures.get(2).or_else(|| captures.get(3))?.as_str();
+            match captures[1].to_ascii_lowercase().as_str() {
+                "property" | "name" => matches |= value.eq_ignore_ascii_case(name),
+                "content" => content = Some(unescape(value)),
+                _ => {}
+            }
+        }
+        content.filter(|content| matches && !content.is_empty())
+    })
+}
+
+// entities that are common in titles; clients show the result as text, not as HTML
+fn unescape(text: &str) -> String {
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn shorten(text: &str, length: usize) -> String {
+    if text.chars().count() > length {
+        text.chars().take(length - 1).chain(['…']).collect()
+    } else {
+        text.to_owned()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PreviewError {
+    #[error("page is not reachable: {0}")]
+    Connection(#[from] hyper::Error),
+    #[error("page failed with status {0}")]
+    Status(u16),
+    #[error("page didn't answer in time")]
+    Timeout,
+    #[error("page doesn't have a title")]
+    NoTitle,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_links_to_allowed_hosts_are_found() {
+        let previewer = LinkPreviewer::new(vec!["example.com".to_string()]);
+        let link = previewer.find_link("see http://evil.com and (https://docs.Example.com/a?b=1).");
+        assert_eq!(link.unwrap().to_string(), "https://docs.Example.com/a?b=1");
+        assert!(previewer.find_link("https://notexample.com/").is_none());
+        assert!(previewer.find_link("no links here").is_none());
+    }
+
+    #[test]
+    fn titles_and_descriptions_are_read_from_head() {
+        let page = r#"<html><head><title>Site &amp; Co |
+            Page</title><meta content='A page about &quot;things&quot;' name="description">
+            </head></html>"#;
+        assert_eq!(page_title(page).unwrap(), "Site & Co | Page");
+        assert_eq!(page_description(page).unwrap(), "A page about \"things\"");
+
+        let page = r#"<meta property="og:title" content="Page"><title>Page - Site</title>"#;
+        assert_eq!(page_title(page).unwrap(), "Page");
+        assert_eq!(page_description(page), None);
+    }
+}
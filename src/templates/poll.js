@@ -3,6 +3,43 @@ window.onload = anket_main;
 var anket = {
     canvas: {},
     socket: null,
+    // version of the websocket protocol that this page speaks
+    protocol_version: 2,
+    // settings of the server from `/api/config`; fetched before connecting
+    config: null,
+    // optional parts of the protocol that both this page and the server support
+    features: [],
+    closed: false,
+    is_owner: false,
+    // unsent item text is kept in local storage under this; given by server
+    draft_token: null,
+    // id of the question that is shown
+    question: 0,
+    // id of the uploaded image that is sent with the next item
+    attachment: null,
+    // server time minus client time in milliseconds; countdowns use server time
+    clock_offset: 0,
+    // tick of the latest broadcast that is received
+    tick: 0,
+    // batch of events that is being received is older than the state from `GetState`
+    stale_batch: false,
+    // last received sections of the poll state; server only sends the changed ones
+    questions: [],
+    items: { pinned: [], user: [] },
+    // votes, reactions and items of this user; questions are the same for everyone on shared view
+    overlay: null,
+    // translations of items to the language of this browser, by item id
+    translations: {},
+    // similar items are asked after user stops typing
+    suggest_timer: null,
+    // comments are asked when they're opened, by item id
+    comments: {},
+    comments_pending: {},
+    open_comments: {},
+    // items are rendered again on every update; comment forms are restored from these
+    comment_drafts: {},
+    comment_replies: {},
+    comment_focus: null,
 };
 
 function anket_getPollID() {
@@ -13,35 +50,199 @@ function anket_getPollID() {
     return urlArr[1];
 }
 
+// puts item level changes in place on the last state of the question
+function anket_applyQuestionDelta(delta) {
+    var question = anket.questions[delta.id];
+    var items = {};
+    var lists = [question.top_items, question.latest_items]
+        .concat(question.sections.map(section => section.items));
+    lists.forEach(list => list.forEach(item => { items[item.id] = item; }));
+    delta.removes.forEach(itemID => { delete items[itemID]; });
+    delta.upserts.forEach(item => { items[item.id] = item; });
+
+    // lists without a new order keep their order, with new states of their items
+    var listOf = (ids, list) => (ids === null ? list.map(item => item.id) : ids)
+        .filter(itemID => itemID in items)
+        .map(itemID => items[itemID]);
+    question.top_items = listOf(delta.top_items, question.top_items);
+    question.latest_items = listOf(delta.latest_items, question.latest_items);
+    question.sections.forEach(section => {
+        var order = delta.sections.find(changed => changed.id === section.id);
+        section.items = listOf(order ? order.items : null, section.items);
+    });
+}
+
 function anket_getWSUrl(pollID) {
-    var scheme = (window.location.protocol == "https:") ? "wss" : "ws";
-    return `${scheme}://${window.location.host}/p/${pollID}/ws`;
+    var config = anket.config;
+    // pages served over https can't open insecure sockets, even behind a plain proxy
+    var secure = config.secure || window.location.protocol == "https:";
+    var scheme = secure ? "wss" : "ws";
+    var host = config.host || window.location.host;
+    // questions are updated with deltas after they're received once
+    var options = "updates=delta";
+    if (config.features.includes("shared-view")) {
+        options += "&view=shared";
+    }
+    if (config.features.includes("compression") && "DecompressionStream" in window) {
+        options += "&compression=deflate";
+    }
+    return `${scheme}://${host}${config.base_path}/p/${pollID}/ws?${options}`;
 }
 
 function anket_initCanvas() {
     anket.canvas.self.innerHTML = anket_HTML.canvas_init;
     anket.canvas.title = anket.canvas.self.querySelector("#anket-pollTitle");
+    anket.canvas.description = anket.canvas.self.querySelector("#anket-pollDescription");
+    anket.canvas.closed = anket.canvas.self.querySelector("#anket-pollClosed");
+    anket.canvas.credits = anket.canvas.self.querySelector("#anket-pollCredits");
+    anket.canvas.votes = anket.canvas.self.querySelector("#anket-pollVotes");
+    anket.canvas.remaining_items = anket.canvas.self.querySelector("#anket-pollItems");
+    anket.canvas.hidden = anket.canvas.self.querySelector("#anket-pollHidden");
+    anket.canvas.shuffled = anket.canvas.self.querySelector("#anket-pollShuffled");
+    anket.canvas.owner = anket.canvas.self.querySelector("#anket-pollOwner");
+    anket.canvas.owner.querySelector("#anket-closePoll").onclick = function () {
+        if (confirm("Poll will be closed and nobody will be able to vote anymore.")) {
+            anket_sendOwnerMsg("ClosePoll");
+        }
+    };
+    anket.canvas.owner.querySelector("#anket-resetVotes").onclick = function () {
+        if (confirm("Every vote will be removed, items will stay for another round.")) {
+            anket_sendOwnerMsg("ResetVotes");
+        }
+    };
+    anket.canvas.owner.querySelector("#anket-insights").href = anket_url(`/p/${anket_getPollID()}/insights`);
+    anket.canvas.owner.querySelector("#anket-export").href = anket_url(`/p/${anket_getPollID()}/export`);
+    anket.canvas.owner.querySelector("#anket-auditLog").href = anket_url(`/p/${anket_getPollID()}/audit`);
+    anket.canvas.participants = anket.canvas.owner.querySelector("#anket-participants");
+    anket.canvas.moderation_log = anket.canvas.owner.querySelector("#anket-moderationLog");
+    anket.canvas.ingest = anket.canvas.owner.querySelector("#anket-ingest");
+    anket.canvas.ingest.querySelector("#anket-ingestUrl").innerText =
+        window.location.origin + anket_url(`/p/${anket_getPollID()}/ingest`);
+
+    anket.canvas.question_form = anket.canvas.owner.querySelector("#anket-questionForm");
+    anket.canvas.question_form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        var input = anket.canvas.question_form.querySelector("#anket-questionTitle");
+        var voteMode = anket.canvas.question_form.querySelector("#anket-questionVoteMode");
+        if (input.value.length > 0) {
+            anket_sendOwnerMsg("AddQuestion", { title: input.value, vote_mode: voteMode.value });
+        }
+        input.value = "";
+    });
+
+    anket.canvas.sections_form = anket.canvas.owner.querySelector("#anket-sectionsForm");
+    anket.canvas.sections_form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        var input = anket.canvas.sections_form.querySelector("#anket-sectionNames");
+        var sections = input.value.split("\n")
+            .map(function (name) { return name.trim(); })
+            .filter(function (name) { return name.length > 0; });
+        anket_sendOwnerMsg("SetSections", { sections: sections });
+        input.blur();
+    });
+    anket.canvas.sections = anket.canvas.self.querySelector("#anket-sections");
 
+    anket.canvas.name_form = anket.canvas.self.querySelector("#anket-nameForm");
+    anket.canvas.name_form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        var input = anket.canvas.name_form.querySelector("#anket-userName");
+        anket.socket.send(JSON.stringify({
+            type: "SetName",
+            content: { name: input.value },
+        }));
+        input.blur();
+    });
+
+    anket.canvas.questions = anket.canvas.self.querySelector("#anket-questions");
+    anket.canvas.question_title = anket.canvas.self.querySelector("#anket-questionTitleText");
     anket.canvas.form = anket.canvas.self.querySelector("#anket-pollForm");
     anket.canvas.form.addEventListener("submit", function (event) {
         event.preventDefault();
         var input = anket.canvas.form.querySelector("#anket-itemText");
+        // draft stays if connection is lost; so it can be sent after page is reloaded
+        if (anket.socket.readyState != WebSocket.OPEN) {
+            return;
+        }
         if (input.value.length > 0) {
             anket.socket.send(JSON.stringify({
                 type: "AddItem",
-                content: { text: input.value },
+                content: {
+                    text: input.value,
+                    question_id: anket.question,
+                    attachment: anket.attachment,
+                    section: anket_selectedSection(),
+                },
             }));
         }
         input.value = "";
+        anket.attachment = null;
+        anket.canvas.form.querySelector("#anket-itemImage").value = "";
+        anket_saveDraft("");
+        anket_renderSimilarItems("", []);
     });
+    anket.canvas.form.querySelector("#anket-itemImage").addEventListener("change", function (event) {
+        anket.attachment = null;
+        var file = event.target.files[0];
+        if (file === undefined) {
+            return;
+        }
+        fetch(anket_url(`/p/${anket_getPollID()}/attachments`), {
+            method: "POST",
+            headers: { "Content-Type": file.type },
+            body: file,
+        }).then(function (response) {
+            if (response.status == 413) {
+                throw new Error("This image is too big.");
+            }
+            if (!response.ok) {
+                return response.text().then(function (text) { throw new Error(text); });
+            }
+            return response.json();
+        }).then(function (attachment) {
+            anket.attachment = attachment.id;
+        }).catch(function (error) {
+            event.target.value = "";
+            alert(error.message);
+        });
+    });
+    anket.canvas.form.querySelector("#anket-itemText").addEventListener("input", function (event) {
+        anket_saveDraft(event.target.value);
+        anket_suggestSimilar(event.target.value);
+    });
+    anket.canvas.similar_items = anket.canvas.form.querySelector("#anket-similarItems");
 
     anket.canvas.top_items = anket.canvas.self.querySelector("#anket-itemsTop");
     anket.canvas.latest_items = anket.canvas.self.querySelector("#anket-itemsLatest");
     anket.canvas.user_items = anket.canvas.self.querySelector("#anket-itemsUser");
+    anket.canvas.pinned = anket.canvas.self.querySelector("#anket-pinned");
+    anket.canvas.pinned_items = anket.canvas.self.querySelector("#anket-itemsPinned");
+    anket.canvas.user_votes = anket.canvas.self.querySelector("#anket-userVotes");
+    anket.canvas.undo = anket.canvas.self.querySelector("#anket-undo");
+    anket.canvas.undo.onclick = function () {
+        anket.socket.send(JSON.stringify({ type: "UndoLastAction" }));
+    };
+}
+
+// items of shared questions don't have anything about this user; overlay puts it on them
+function anket_personalize(details) {
+    var overlay = anket.overlay;
+    if (overlay === null) {
+        return details;
+    }
+    var reacted = overlay.reactions[details.id] || [];
+    return Object.assign({}, details, {
+        user_vote: overlay.votes[details.id] || 0,
+        is_author: overlay.authored.includes(details.id),
+        reactions: details.reactions.map(reaction => Object.assign({}, reaction, {
+            reacted: reacted.includes(reaction.emoji),
+        })),
+    });
 }
 
 function anket_makeItem(details) {
+    details = anket_personalize(details);
     var id = details.id;
+    var question = anket.questions[details.question_id];
 
     var item = document.createElement("div");
     item.innerHTML = anket_HTML.item;
@@ -53,11 +254,40 @@ function anket_makeItem(details) {
     var buttonUp = item.querySelector(".option-vote-up");
     var buttonDown = item.querySelector(".option-vote-down");
 
-    score.innerText = details.score;
-    content.innerText = details.text;
+    score.innerText = (details.score === null) ? "?" : details.score;
+    // html is rendered and sanitized by the server; it's only sent when poll uses markdown
+    if (details.html === null) {
+        content.innerText = details.text;
+    } else {
+        content.innerHTML = details.html;
+    }
+    if (details.attachment !== null) {
+        content.appendChild(anket_makeAttachment(details.attachment));
+    }
+    if (details.link_preview !== null) {
+        content.appendChild(anket_makeLinkPreview(details.link_preview));
+    }
+    details.references.forEach(function (reference) {
+        content.appendChild(anket_makeReference(reference));
+    });
+    if (anket.canvas.self.dataset.translation == "available") {
+        content.appendChild(anket_makeTranslation(id));
+    }
     buttonUp.innerText = (details.user_vote == 1) ? "⬆" : "⇧";
     buttonDown.innerText = (details.user_vote == -1) ? "⬇" : "⇩";
 
+    if (question.vote_mode == "Approval") {
+        buttonUp.innerText = (details.user_vote == 1) ? "✔" : "✓";
+        buttonDown.remove();
+        if (details.approval_percentage !== null) {
+            var percentage = document.createElement("small");
+            percentage.className = "option-percentage";
+            percentage.innerText = Math.round(details.approval_percentage) + "%";
+            score.appendChild(document.createElement("br"));
+            score.appendChild(percentage);
+        }
+    }
+
     buttonUp.onclick = function () {
         var current = item.getAttribute("anket-itemUserVote");
         var value = (current == "1") ? 0 : 1;
@@ -69,13 +299,793 @@ function anket_makeItem(details) {
         anket_sendVoteItemMsg(id, value);
     }
 
+    if (question.vote_mode == "UpDown" && !question.allow_downvotes) {
+        buttonDown.remove();
+    }
+
+    if (question.vote_mode == "Quadratic") {
+        // every click adds or takes back a single vote
+        buttonUp.innerText = (details.user_vote > 0) ? "⬆" : "⇧";
+        buttonDown.innerText = (details.user_vote < 0) ? "⬇" : "⇩";
+        buttonUp.onclick = function () {
+            anket_sendVoteItemMsg(id, details.user_vote + 1);
+        }
+        buttonDown.onclick = function () {
+            anket_sendVoteItemMsg(id, details.user_vote - 1);
+        }
+        // without downvotes, down button only takes back votes
+        if (!question.allow_downvotes && details.user_vote <= 0) {
+            buttonDown.remove();
+        }
+        if (details.user_vote != 0) {
+            var userVote = document.createElement("small");
+            userVote.innerText = "you: " + details.user_vote;
+            score.appendChild(document.createElement("br"));
+            score.appendChild(userVote);
+        }
+    }
+
+    if (details.vote_count !== undefined) {
+        var stats = document.createElement("small");
+        stats.className = "option-stats";
+        stats.innerText = `${details.vote_count} votes, ${Math.round(details.share_percentage)}% of all`;
+        content.appendChild(stats);
+    }
+
+    content.appendChild(anket_makeReactions(id, details.reactions));
+    content.appendChild(anket_makeComments(id, details.comment_count));
+    if (details.author !== null) {
+        var author = document.createElement("small");
+        author.className = "option-stats";
+        author.innerText = `by ${details.author}`;
+        content.appendChild(author);
+    }
+    if (details.edited) {
+        var edited = document.createElement("small");
+        edited.className = "option-stats";
+        edited.innerText = "(edited)";
+        content.appendChild(edited);
+    }
+    if ((details.is_author || anket.is_owner) && !anket.closed) {
+        content.appendChild(anket_makeEditLink(id, details.text));
+        content.appendChild(anket_makeDeleteLink(id, details.is_author));
+    }
+    if (anket.is_owner && !anket.closed) {
+        content.appendChild(anket_makePinLink(id));
+        content.appendChild(anket_makeMergeLink(id));
+    }
+    if (!details.is_author && !anket.is_owner && !anket.closed) {
+        content.appendChild(anket_makeReportLink(id));
+    }
+    // reports are only sent to poll owner
+    if (details.reports !== null) {
+        item.querySelector(".option-card").classList.add("option-reported");
+        content.appendChild(anket_makeReports(id, details.reports));
+    }
+
+    if (question.vote_mode == "Rating") {
+        buttonUp.remove();
+        buttonDown.remove();
+        if (details.rating !== null) {
+            score.innerText = details.rating.average.toFixed(1);
+            var count = document.createElement("small");
+            count.innerText = `(${details.rating.count})`;
+            score.appendChild(document.createElement("br"));
+            score.appendChild(count);
+        }
+
+        var stars = document.createElement("div");
+        stars.className = "option-stars";
+        for (let star = 1; star <= 5; star++) {
+            var button = document.createElement("button");
+            button.className = "pure-button option-star-button";
+            button.innerText = (star <= details.user_vote) ? "★" : "☆";
+            button.onclick = function () {
+                // clicking current rating takes it back
+                anket_sendVoteItemMsg(id, (details.user_vote == star) ? 0 : star);
+            };
+            stars.appendChild(button);
+        }
+        item.querySelector(".option-vote").appendChild(stars);
+    }
+
     return item;
 }
 
-function anket_renderItems(items, target) {
+function anket_makeReactions(itemID, reactions) {
+    var container = document.createElement("div");
+    container.className = "option-reactions";
+    reactions.forEach(function (reaction) {
+        var button = document.createElement("button");
+        button.className = "pure-button option-reaction-button";
+        if (reaction.reacted) {
+            button.classList.add("pure-button-active");
+        }
+        button.innerText = (reaction.count > 0) ? `${reaction.emoji} ${reaction.count}` : reaction.emoji;
+        button.disabled = anket.closed;
+        button.onclick = function () {
+            anket.socket.send(JSON.stringify({
+                type: "ReactItem",
+                content: { item_id: itemID, emoji: reaction.emoji },
+            }));
+        };
+        container.appendChild(button);
+    });
+    return container;
+}
+
+function anket_requestComments(itemID) {
+    if (anket.comments_pending[itemID]) {
+        return;
+    }
+    anket.comments_pending[itemID] = true;
+    anket.socket.send(JSON.stringify({
+        type: "GetComments",
+        content: { item_id: itemID },
+    }));
+}
+
+function anket_makeComments(itemID, count) {
+    var container = document.createElement("div");
+    container.className = "option-comments";
+    var open = anket.open_comments[itemID] === true;
+    var toggle = document.createElement("a");
+    toggle.href = "#";
+    toggle.innerText = `${open ? "Hide" : "Show"} comments (${count})`;
+    toggle.onclick = function (event) {
+        event.preventDefault();
+        anket.open_comments[itemID] = !open;
+        anket_renderAllItems();
+    };
+    container.appendChild(toggle);
+    if (!open) {
+        return container;
+    }
+
+    var comments = anket.comments[itemID];
+    if (comments === undefined || comments.length != count) {
+        anket_requestComments(itemID);
+    }
+    if (comments !== undefined) {
+        container.appendChild(anket_makeCommentThread(itemID, comments, null));
+    }
+    if (!anket.closed) {
+        container.appendChild(anket_makeCommentForm(itemID));
+    }
+    return container;
+}
+
+// replies are listed under the comment they answer
+function anket_makeCommentThread(itemID, comments, replyTo) {
+    var list = document.createElement("ul");
+    comments.filter(function (comment) {
+        return comment.reply_to === replyTo;
+    }).forEach(function (comment) {
+        var entry = document.createElement("li");
+        var text = document.createElement("span");
+        text.innerText = comment.text;
+        entry.appendChild(text);
+        if (comment.author !== null) {
+            var author = document.createElement("small");
+            author.innerText = ` — ${comment.author}`;
+            entry.appendChild(author);
+        }
+        if (comment.is_author || comment.by_owner) {
+            var label = document.createElement("small");
+            label.innerText = comment.is_author ? " (you)" : " (poll owner)";
+            entry.appendChild(label);
+        }
+        if (!anket.closed) {
+            var reply = document.createElement("a");
+            reply.href = "#";
+            reply.className = "option-comment-reply";
+            reply.innerText = "Reply";
+            reply.onclick = function (event) {
+                event.preventDefault();
+                anket.comment_replies[itemID] = comment.id;
+                anket.comment_focus = itemID;
+                anket_renderAllItems();
+            };
+            entry.appendChild(reply);
+        }
+        entry.appendChild(anket_makeCommentThread(itemID, comments, comment.id));
+        list.appendChild(entry);
+    });
+    return list;
+}
+
+function anket_makeCommentForm(itemID) {
+    var form = document.createElement("form");
+    form.className = "pure-form";
+    var input = document.createElement("input");
+    input.type = "text";
+    input.maxLength = 500;
+    input.value = anket.comment_drafts[itemID] || "";
+    var replyTo = anket.comment_replies[itemID];
+    input.placeholder = (replyTo === undefined) ? "Add a comment" : "Reply to the comment";
+    input.addEventListener("input", function () {
+        anket.comment_drafts[itemID] = input.value;
+    });
+    form.appendChild(input);
+    if (replyTo !== undefined) {
+        var cancel = document.createElement("a");
+        cancel.href = "#";
+        cancel.innerText = "Cancel reply";
+        cancel.onclick = function (event) {
+            event.preventDefault();
+            delete anket.comment_replies[itemID];
+            anket_renderAllItems();
+        };
+        form.appendChild(cancel);
+    }
+    form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        if (input.value.trim().length == 0) {
+            return;
+        }
+        anket.socket.send(JSON.stringify({
+            type: "CommentItem",
+            content: {
+                item_id: itemID,
+                reply_to: (replyTo === undefined) ? null : replyTo,
+                text: input.value,
+            },
+        }));
+        delete anket.comment_drafts[itemID];
+        delete anket.comment_replies[itemID];
+        input.value = "";
+    });
+    input.dataset.commentItem = itemID;
+    if (anket.comment_focus == itemID) {
+        anket.comment_focus = null;
+        setTimeout(function () { input.focus(); }, 0);
+    }
+    return form;
+}
+
+function anket_makeEditLink(itemID, currentText) {
+    var link = document.createElement("a");
+    link.href = "#";
+    link.className = "option-edit";
+    link.innerText = "Edit";
+    link.onclick = function (event) {
+        event.preventDefault();
+        var text = prompt("Edit this item", currentText);
+        if (text !== null && text.length > 0 && text != currentText) {
+            anket.socket.send(JSON.stringify({
+                type: "EditItem",
+                content: { item_id: itemID, text: text },
+            }));
+        }
+    };
+    return link;
+}
+
+// poll owner deletes items of others with an owner command
+function anket_makeDeleteLink(itemID, isAuthor) {
+    var link = document.createElement("a");
+    link.href = "#";
+    link.className = "option-delete";
+    link.innerText = "Delete";
+    link.onclick = function (event) {
+        event.preventDefault();
+        if (confirm("This item and every vote on it will be removed.")) {
+            if (isAuthor) {
+                anket.socket.send(JSON.stringify({
+                    type: "DeleteItem",
+                    content: { item_id: itemID },
+                }));
+            } else {
+                anket_sendOwnerMsg("DeleteItem", { item_id: itemID });
+            }
+        }
+    };
+    return link;
+}
+
+function anket_makeReportLink(itemID) {
+    var link = document.createElement("a");
+    link.href = "#";
+    link.className = "option-report";
+    link.innerText = "Report";
+    link.onclick = function (event) {
+        event.preventDefault();
+        var reason = prompt("Why should the poll owner look at this item? (optional)", "");
+        if (reason !== null) {
+            anket.socket.send(JSON.stringify({
+                type: "ReportItem",
+                content: { item_id: itemID, reason: reason.length > 0 ? reason : null },
+            }));
+        }
+    };
+    return link;
+}
+
+function anket_makeReports(itemID, reports) {
+    var box = document.createElement("div");
+    box.className = "option-reports";
+    var summary = document.createElement("small");
+    summary.innerText = `⚑ reported ${reports.count} ${reports.count == 1 ? "time" : "times"}`;
+    box.appendChild(summary);
+    reports.reasons.forEach(function (reason) {
+        var line = document.createElement("small");
+        line.className = "option-report-reason";
+        line.innerText = reason;
+        box.appendChild(line);
+    });
+    if (!anket.closed) {
+        var dismiss = document.createElement("a");
+        dismiss.href = "#";
+        dismiss.innerText = "Dismiss";
+        dismiss.onclick = function (event) {
+            event.preventDefault();
+            anket_sendOwnerMsg("DismissReports", { item_id: itemID });
+        };
+        box.appendChild(dismiss);
+        box.appendChild(anket_makeDeleteLink(itemID, false));
+    }
+    return box;
+}
+
+function anket_makePinLink(itemID) {
+    var pinned = anket.items.pinned.some(function (item) {
+        return item.id == itemID;
+    });
+    var link = document.createElement("a");
+    link.href = "#";
+    link.className = "option-pin";
+    link.innerText = pinned ? "Unpin" : "Pin";
+    link.onclick = function (event) {
+        event.preventDefault();
+        anket_sendOwnerMsg("PinItem", { item_id: itemID, pinned: !pinned });
+    };
+    return link;
+}
+
+function anket_makeMergeLink(itemID) {
+    var link = document.createElement("a");
+    link.href = "#";
+    link.className = "option-merge";
+    // ids aren't shown anywhere else; owner picks the target by its id on this link
+    link.innerText = `Merge #${itemID}`;
+    link.onclick = function (event) {
+        event.preventDefault();
+        var target = prompt(`Merge #${itemID} into item #`);
+        if (target === null) {
+            return;
+        }
+        var targetID = parseInt(target.replace("#", ""), 10);
+        if (!isNaN(targetID) && targetID != itemID) {
+            anket_sendOwnerMsg("MergeItems", { source_id: itemID, target_id: targetID });
+        }
+    };
+    return link;
+}
+
+function anket_makeTranslation(itemID) {
+    var translation = document.createElement("small");
+    translation.className = "option-translation";
+    if (anket.translations[itemID] !== undefined) {
+        translation.innerText = anket.translations[itemID];
+        return translation;
+    }
+    var link = document.createElement("a");
+    link.href = "#";
+    link.innerText = "Translate";
+    link.onclick = function (event) {
+        event.preventDefault();
+        anket.socket.send(JSON.stringify({
+            type: "TranslateItem",
+            content: { item_id: itemID, lang: navigator.language },
+        }));
+    };
+    translation.appendChild(link);
+    return translation;
+}
+
+function anket_makeLinkPreview(preview) {
+    var link = document.createElement("a");
+    link.className = "option-link-preview";
+    link.href = preview.url;
+    link.target = "_blank";
+    link.rel = "nofollow noopener noreferrer";
+    var title = document.createElement("strong");
+    title.innerText = preview.title;
+    link.appendChild(title);
+    if (preview.description !== null) {
+        var description = document.createElement("small");
+        description.innerText = preview.description;
+        link.appendChild(description);
+    }
+    return link;
+}
+
+function anket_makeAttachment(attachmentID) {
+    var link = document.createElement("a");
+    link.href = anket_url(`/p/${anket_getPollID()}/attachments/${attachmentID}`);
+    link.target = "_blank";
+    var image = document.createElement("img");
+    image.className = "option-image";
+    image.src = link.href;
+    image.loading = "lazy";
+    image.alt = "attached image";
+    link.appendChild(image);
+    return link;
+}
+
+function anket_makeReference(reference) {
+    var quote = document.createElement("blockquote");
+    quote.className = "option-reference";
+
+    var link = document.createElement("a");
+    link.href = anket_url(`/p/${reference.poll_id}`);
+    link.innerText = reference.poll_title;
+    quote.appendChild(link);
+
+    var text = document.createElement("div");
+    var score = (reference.score === null) ? "?" : reference.score;
+    text.innerText = `${reference.text} (${score})`;
+    quote.appendChild(text);
+
+    return quote;
+}
+
+// `showAge` adds how long ago items are added
+function anket_renderItems(items, target, showAge) {
+    // comment that is being written keeps the focus
+    var focused = document.activeElement;
+    if (focused !== null && focused.dataset !== undefined && focused.dataset.commentItem !== undefined) {
+        anket.comment_focus = Number(focused.dataset.commentItem);
+    }
     target.innerHTML = "";
     items.forEach(function (details) {
-        target.appendChild(anket_makeItem(details));
+        var item = anket_makeItem(details);
+        if (showAge) {
+            item.querySelector(".option-content").appendChild(anket_makeAge(details.created_at));
+        }
+        target.appendChild(item);
+    });
+}
+
+function anket_makeAge(createdAt) {
+    var age = document.createElement("small");
+    age.className = "option-age";
+    age.dataset.createdAt = createdAt;
+    age.innerText = anket_relativeTime(createdAt);
+    return age;
+}
+
+// `at` is a unix timestamp of the server
+function anket_relativeTime(at) {
+    var seconds = Math.max(0, Math.round((Date.now() + anket.clock_offset) / 1000 - at));
+    if (seconds < 60) {
+        return "just now";
+    }
+    var minutes = Math.floor(seconds / 60);
+    if (minutes < 60) {
+        return `${minutes} min ago`;
+    }
+    var hours = Math.floor(minutes / 60);
+    if (hours < 24) {
+        return `${hours} h ago`;
+    }
+    return `${Math.floor(hours / 24)} d ago`;
+}
+
+// items are not rendered again when nothing changes; ages are updated in place
+function anket_updateAges() {
+    anket.canvas.self.querySelectorAll(".option-age").forEach(function (age) {
+        age.innerText = anket_relativeTime(Number(age.dataset.createdAt));
+    });
+}
+
+function anket_renderAllItems() {
+    anket_renderQuestion();
+    anket_renderPinnedItems();
+    anket_renderItems(anket.items.user, anket.canvas.user_items);
+}
+
+function anket_renderPinnedItems() {
+    anket.canvas.pinned.hidden = anket.items.pinned.length == 0;
+    anket_renderItems(anket.items.pinned, anket.canvas.pinned_items);
+}
+
+function anket_renderQuestion() {
+    var question = anket.questions[anket.question];
+    if (question === undefined) {
+        return;
+    }
+    // single question polls don't need any navigation
+    anket.canvas.questions.hidden = anket.questions.length < 2;
+    anket.canvas.question_title.hidden = anket.questions.length < 2;
+    anket.canvas.question_title.innerText = question.title;
+    anket.canvas.questions.innerHTML = "";
+    anket.questions.forEach(function (other) {
+        var button = document.createElement("button");
+        button.className = "pure-button";
+        if (other.id == anket.question) {
+            button.classList.add("pure-button-active");
+        }
+        button.innerText = `${other.id + 1}. ${other.title}`;
+        button.onclick = function () {
+            anket.question = other.id;
+            anket_renderQuestion();
+        };
+        anket.canvas.questions.appendChild(button);
+    });
+    anket_renderItems(question.top_items, anket.canvas.top_items);
+    anket_renderItems(question.latest_items, anket.canvas.latest_items, true);
+    anket_renderSections(question.sections);
+}
+
+// every section is a column of its items, like boards of retrospectives
+function anket_renderSections(sections) {
+    var target = anket.canvas.sections;
+    target.hidden = sections.length == 0;
+    target.innerHTML = "";
+    sections.forEach(function (section) {
+        var column = document.createElement("div");
+        column.className = "pure-u-1 pure-u-md-1-" + Math.min(sections.length, 4) + " poll-section";
+        var title = document.createElement("h2");
+        title.className = "text-center";
+        title.innerText = section.name;
+        column.appendChild(title);
+        var items = document.createElement("div");
+        anket_renderItems(section.items, items);
+        column.appendChild(items);
+        target.appendChild(column);
+    });
+}
+
+// items that wait for approval; only poll owner receives these
+function anket_renderPendingItems(items) {
+    var target = anket.canvas.owner.querySelector("#anket-pendingItems");
+    target.parentElement.hidden = items.length == 0;
+    target.innerHTML = "";
+    items.forEach(function (item) {
+        var entry = document.createElement("li");
+        var text = document.createElement("span");
+        text.innerText = `${item.author}: ${item.text} `;
+        entry.appendChild(text);
+        var approve = document.createElement("button");
+        approve.className = "pure-button";
+        approve.innerText = "Approve";
+        approve.onclick = function () {
+            anket_sendOwnerMsg("ApproveItem", { item_id: item.id });
+        };
+        entry.appendChild(approve);
+        var reject = document.createElement("button");
+        reject.className = "pure-button";
+        reject.innerText = "Reject";
+        reject.onclick = function () {
+            var reason = prompt("Reason of rejection (optional)");
+            if (reason === null) {
+                return;
+            }
+            anket_sendOwnerMsg("RejectItem", { item_id: item.id, reason: reason.trim() || null });
+        };
+        entry.appendChild(reject);
+        target.appendChild(entry);
+    });
+}
+
+function anket_renderSectionChoices(sections) {
+    var names = anket.canvas.sections_form.querySelector("#anket-sectionNames");
+    if (document.activeElement !== names) {
+        names.value = sections.join("\n");
+    }
+    var select = anket.canvas.form.querySelector("#anket-itemSection");
+    var selected = select.value;
+    select.hidden = sections.length == 0;
+    select.innerHTML = "";
+    var none = document.createElement("option");
+    none.value = "";
+    none.innerText = "No section";
+    select.appendChild(none);
+    sections.forEach(function (name, index) {
+        var option = document.createElement("option");
+        option.value = index;
+        option.innerText = name;
+        select.appendChild(option);
+    });
+    if (selected !== "" && Number(selected) < sections.length) {
+        select.value = selected;
+    }
+}
+
+function anket_selectedSection() {
+    var select = anket.canvas.form.querySelector("#anket-itemSection");
+    return (select.hidden || select.value === "") ? null : Number(select.value);
+}
+
+function anket_renderParticipants(participants) {
+    var target = anket.canvas.participants;
+    target.innerHTML = "";
+    participants.forEach(function (participant, index) {
+        var row = document.createElement("tr");
+        row.innerHTML = anket_HTML.participant;
+        var label = participant.name + (participant.is_owner ? " (you)" : "");
+        if (participant.shared_ip) {
+            label += " ⚑ same network as others";
+        }
+        if (participant.source !== null) {
+            label += ` (via ${participant.source})`;
+        }
+        if (participant.shadow_banned) {
+            label += " (shadow banned)";
+        }
+        row.querySelector(".participant-name").innerText = label;
+        row.querySelector(".participant-items").innerText = participant.item_count;
+        var input = row.querySelector(".participant-weight");
+        input.value = participant.weight;
+        input.onchange = function () {
+            anket_sendOwnerMsg("SetUserWeight", { participant_id: participant.id, weight: parseInt(input.value) });
+        };
+        var actions = row.querySelector(".participant-actions");
+        if (participant.is_owner) {
+            actions.innerHTML = "";
+        } else {
+            var shadowBan = actions.querySelector(".participant-shadowBan");
+            shadowBan.innerText = participant.shadow_banned ? "Lift shadow ban" : "Shadow ban";
+            shadowBan.onclick = function (event) {
+                event.preventDefault();
+                anket_sendOwnerMsg("ShadowBan", { participant_id: participant.id, banned: !participant.shadow_banned });
+            };
+            actions.querySelector(".participant-kick").onclick = function (event) {
+                event.preventDefault();
+                anket_sendOwnerMsg("KickUser", { participant_id: participant.id });
+            };
+            actions.querySelector(".participant-ban").onclick = function (event) {
+                event.preventDefault();
+                if (!confirm(`${participant.name} won't be able to join this poll again.`)) {
+                    return;
+                }
+                var removeContent = confirm("Remove their items and votes as well?");
+                anket_sendOwnerMsg("BanUser", { participant_id: participant.id, remove_content: removeContent });
+            };
+        }
+        target.appendChild(row);
+    });
+}
+
+function anket_participantName(participants, userID) {
+    var participant = participants.find(function (participant) {
+        return participant.id == userID;
+    });
+    return (participant === undefined) ? "A former participant" : participant.name;
+}
+
+function anket_renderModerationLog(entries, participants) {
+    var target = anket.canvas.moderation_log;
+    target.innerHTML = "";
+    entries.forEach(function (entry) {
+        var line = document.createElement("li");
+        var at = new Date(entry.at * 1000).toLocaleTimeString();
+        var description;
+        switch (entry.action.type) {
+            case "SetUserWeight":
+                var name = anket_participantName(participants, entry.action.target);
+                description = `vote weight of ${name} set to ${entry.action.weight}`;
+                break;
+            case "ShadowBan":
+                var bannedName = anket_participantName(participants, entry.action.target);
+                description = entry.action.banned ? `${bannedName} shadow banned` : `shadow ban of ${bannedName} lifted`;
+                break;
+            case "KickUser":
+                description = `${anket_participantName(participants, entry.action.target)} kicked`;
+                break;
+            case "BanUser":
+                description = "a participant banned";
+                if (entry.action.remove_content) {
+                    description += ", their items and votes removed";
+                }
+                break;
+            case "EditItem":
+                var editedAuthor = anket_participantName(participants, entry.action.target);
+                description = `item of ${editedAuthor} edited, it was: "${entry.action.text}"`;
+                break;
+            case "DeleteItem":
+                var author = anket_participantName(participants, entry.action.target);
+                description = `item of ${author} deleted: "${entry.action.text}"`;
+                break;
+            case "RejectItem":
+                var rejectedAuthor = anket_participantName(participants, entry.action.target);
+                description = `item of ${rejectedAuthor} rejected: "${entry.action.text}"`;
+                break;
+            case "DismissReports":
+                var reportedAuthor = anket_participantName(participants, entry.action.target);
+                description = `${entry.action.count} reports on item of ${reportedAuthor} dismissed: "${entry.action.text}"`;
+                break;
+            case "MergeItems":
+                var mergedAuthor = anket_participantName(participants, entry.action.target);
+                description = `item of ${mergedAuthor} merged into #${entry.action.into}: "${entry.action.text}"`;
+                break;
+            case "ResetVotes":
+                description = "votes reset";
+                break;
+            case "ClosePoll":
+                description = "poll closed";
+                break;
+        }
+        if (entry.reason !== null) {
+            description += ` (${entry.reason})`;
+        }
+        line.innerText = `${at}: ${description}`;
+        target.appendChild(line);
+    });
+}
+
+function anket_calibrateClock() {
+    var sentAt = Date.now();
+    fetch(anket_url("/time")).then(function (response) {
+        return response.json();
+    }).then(function (data) {
+        // server time is assumed to be in the middle of the round trip
+        var receivedAt = Date.now();
+        anket.clock_offset = data.server_time - (sentAt + receivedAt) / 2;
+    });
+}
+
+function anket_renderUserVotes(votes) {
+    var target = anket.canvas.user_votes;
+    target.innerHTML = "";
+    votes.forEach(function (vote) {
+        var entry = document.createElement("li");
+        entry.innerText = `${vote.value > 0 ? "+" : ""}${vote.value} ${vote.text}`;
+        target.appendChild(entry);
+    });
+}
+
+function anket_draftKey() {
+    return `anket_draft:${anket_getPollID()}:${anket.draft_token}`;
+}
+
+function anket_saveDraft(text) {
+    if (anket.draft_token === null) {
+        return;
+    }
+    if (text.length > 0) {
+        window.localStorage.setItem(anket_draftKey(), text);
+    } else {
+        window.localStorage.removeItem(anket_draftKey());
+    }
+}
+
+function anket_restoreDraft() {
+    var input = anket.canvas.form.querySelector("#anket-itemText");
+    var draft = window.localStorage.getItem(anket_draftKey());
+    if (draft !== null && input.value.length == 0) {
+        input.value = draft;
+    }
+}
+
+// asked once user stops typing for a moment, not on every key stroke
+function anket_suggestSimilar(text) {
+    clearTimeout(anket.suggest_timer);
+    anket.suggest_timer = setTimeout(function () {
+        if (anket.socket.readyState == WebSocket.OPEN) {
+            anket.socket.send(JSON.stringify({
+                type: "SuggestSimilar",
+                content: { text: text, question_id: anket.question },
+            }));
+        }
+    }, 300);
+}
+
+function anket_renderSimilarItems(text, items) {
+    var input = anket.canvas.form.querySelector("#anket-itemText");
+    var target = anket.canvas.similar_items;
+    // answer of an older query
+    if (text != input.value) {
+        return;
+    }
+    target.hidden = items.length == 0;
+    var list = target.querySelector("ul");
+    list.innerHTML = "";
+    items.forEach(function (item) {
+        var entry = document.createElement("li");
+        entry.innerText = item.text;
+        list.appendChild(entry);
     });
 }
 
@@ -86,42 +1096,255 @@ function anket_sendVoteItemMsg(itemID, voteValue) {
     }));
 }
 
+// server rejects these unless this user is poll owner; `content` is left out for commands
+// without any details
+function anket_sendOwnerMsg(type, content) {
+    anket.socket.send(JSON.stringify({
+        type: "Owner",
+        content: (content === undefined) ? { type: type } : { type: type, content: content },
+    }));
+}
+
+function anket_showNudge(message) {
+    var nudge = document.createElement("p");
+    nudge.className = "nudge-box";
+    nudge.innerText = message;
+    anket.canvas.self.prepend(nudge);
+    setTimeout(function () { nudge.remove(); }, 10000);
+}
+
 function anket_main() {
     anket.canvas.self = document.getElementById("poll-canvas");
     anket.canvas.self.innerHTML = "";
     var pollID = anket_getPollID();
 
     anket.canvas.self.innerHTML = "<p>connecting to poll...</p>";
+    anket_calibrateClock();
+    setInterval(anket_updateAges, 30 * 1000);
+    fetch(anket_url("/api/config")).then(function (response) {
+        return response.json();
+    }).then(function (config) {
+        anket.config = config;
+        anket_connect(pollID);
+    }).catch(function () {
+        alert("couldn't load settings of anket");
+    });
+}
+
+function anket_connect(pollID) {
     anket.socket = new WebSocket(anket_getWSUrl(pollID));
 
     anket.socket.addEventListener("close", function (event) {
-        alert("websocket connection closed");
+        // server drops connections of closed polls
+        if (!anket.closed) {
+            alert("websocket connection closed");
+        }
     });
 
     anket.socket.addEventListener("error", function (event) {
-        alert("websocket connection lost");
+        if (!anket.closed) {
+            alert("websocket connection lost");
+        }
     });
 
     anket.socket.addEventListener("open", function (event) {
+        var features = ["delta", "resync", "message-ids", "list-items", "compression"]
+            .filter(feature => anket.config.features.includes(feature))
+            .filter(feature => feature != "compression" || "DecompressionStream" in window);
+        anket.socket.send(JSON.stringify({
+            type: "Hello",
+            content: { protocol_version: anket.protocol_version, features: features },
+        }));
         anket_initCanvas();
     });
 
+    // timers of background tabs are throttled; state is asked again when the tab is shown
+    document.addEventListener("visibilitychange", function () {
+        if (!document.hidden && anket.socket.readyState == WebSocket.OPEN) {
+            anket.socket.send(JSON.stringify({ type: "GetState" }));
+        }
+    });
+
+    // large messages may arrive compressed; every message is handled in the order it arrives
+    anket.socket.binaryType = "arraybuffer";
+    var received = Promise.resolve();
     anket.socket.addEventListener("message", function (event) {
-        var data = JSON.parse(event.data);
-        switch (data.type) {
-            case "ActionResponse":
-                alert(data.content);
-                break;
+        received = received
+            .then(() => anket_readMessage(event.data))
+            .then(anket_handleMessage)
+            .catch(console.error);
+    });
+}
 
-            case "PollStateUpdate":
-                anket.canvas.title.innerText = data.content.poll_title;
+// binary frames are zlib compressed JSON
+function anket_readMessage(data) {
+    if (typeof data === "string") {
+        return JSON.parse(data);
+    }
+    var stream = new Blob([data]).stream().pipeThrough(new DecompressionStream("deflate"));
+    return new Response(stream).text().then(JSON.parse);
+}
+
+// sections of state; they're skipped when they're older than what's shown
+var anket_stateSections = [
+    "Meta", "Question", "QuestionDelta", "PinnedItems", "UserItems", "PendingItems", "UserVotes",
+    "ItemOverlay",
+];
 
-                anket_renderItems(data.content.top_items, anket.canvas.top_items);
-                anket_renderItems(data.content.latest_items, anket.canvas.latest_items);
-                anket_renderItems(data.content.user_items, anket.canvas.user_items);
+function anket_handleMessage(data) {
+    if (anket.stale_batch && anket_stateSections.includes(data.type)) {
+        return;
+    }
+    switch (data.type) {
+        case "Hello":
+            // server can't serve this page anymore; it closes the connection
+            if (data.content.min_protocol_version > anket.protocol_version) {
+                anket.closed = true;
+                alert("anket is updated, please reload the page");
                 break;
-        }
-    });
+            }
+            anket.features = data.content.features;
+            break;
+
+        case "Error":
+            if (data.content.code == "PollClosed") {
+                anket.closed = true;
+            }
+            alert(data.content.message);
+            break;
+
+        case "ItemReported":
+            alert("Item is reported to the poll owner.");
+            break;
+
+        case "OwnerDone":
+            // results of owner commands arrive with the next state update
+            break;
+
+        case "TranslateItem":
+            anket.translations[data.content.item_id] = data.content.text;
+            anket_renderAllItems();
+            break;
+
+        case "Comments":
+            anket.comments[data.content.item_id] = data.content.comments;
+            anket.comments_pending[data.content.item_id] = false;
+            anket_renderAllItems();
+            break;
+
+        case "SimilarItems":
+            anket_renderSimilarItems(data.content.text, data.content.items);
+            break;
+
+        case "Clock":
+            anket.stale_batch = data.content.tick < anket.tick;
+            if (!anket.stale_batch) {
+                anket.tick = data.content.tick;
+            }
+            anket.clock_offset = data.content.server_time - Date.now();
+            break;
+
+        case "Nudge":
+            anket_showNudge(data.content);
+            break;
+
+        case "Meta":
+            anket.canvas.title.innerText = data.content.poll_title;
+            anket.canvas.description.hidden = data.content.description === null;
+            anket.canvas.description.innerText = data.content.description || "";
+            var nameInput = anket.canvas.name_form.querySelector("#anket-userName");
+            if (document.activeElement !== nameInput) {
+                nameInput.value = data.content.name || "";
+            }
+            anket.canvas.name_form.hidden = data.content.closed;
+            anket_renderSectionChoices(data.content.sections);
+            var itemInput = anket.canvas.form.querySelector("#anket-itemText");
+            itemInput.minLength = data.content.min_item_length;
+            itemInput.maxLength = data.content.max_item_length;
+            anket.is_owner = data.content.is_owner;
+            if (anket.draft_token === null) {
+                anket.draft_token = data.content.draft_token;
+                anket_restoreDraft();
+            }
+            anket.closed = data.content.closed;
+            anket.canvas.closed.hidden = !data.content.closed;
+            anket.canvas.hidden.hidden = !data.content.results_hidden;
+            anket.canvas.shuffled.hidden = !data.content.shuffled;
+            anket.canvas.owner.hidden = !data.content.is_owner || data.content.closed;
+            anket.canvas.form.hidden = data.content.closed;
+            anket.canvas.undo.hidden = !data.content.can_undo || data.content.closed;
+            anket.canvas.ingest.hidden = data.content.ingest_secret === null;
+            if (data.content.ingest_secret !== null) {
+                anket.canvas.ingest.querySelector("#anket-ingestSecret").innerText = data.content.ingest_secret;
+            }
+            if (data.content.participants !== null) {
+                anket_renderParticipants(data.content.participants);
+                anket_renderModerationLog(data.content.moderation_log, data.content.participants);
+            }
+            anket.canvas.credits.hidden = data.content.remaining_credits === null;
+            anket.canvas.credits.innerText = `Remaining credits: ${data.content.remaining_credits}`;
+            anket.canvas.votes.hidden = data.content.remaining_votes === null;
+            anket.canvas.votes.innerText = `Remaining votes: ${data.content.remaining_votes}`;
+            anket.canvas.remaining_items.hidden = data.content.remaining_items === null;
+            anket.canvas.remaining_items.innerText = `Remaining items: ${data.content.remaining_items}`;
+
+            // items are rendered according to vote mode and state of the poll
+            anket_renderAllItems();
+            break;
+
+        case "State":
+            // sections are handled as if they were sent one by one
+            anket.stale_batch = false;
+            var state = data.content.state;
+            anket_handleMessage({ type: "Meta", content: state });
+            state.questions.forEach(function (question) {
+                anket_handleMessage({ type: "Question", content: question });
+            });
+            anket_handleMessage({ type: "PinnedItems", content: state.pinned_items });
+            anket_handleMessage({ type: "UserItems", content: state.user_items });
+            anket_handleMessage({ type: "PendingItems", content: state.pending_items });
+            anket_handleMessage({ type: "UserVotes", content: state.user_votes });
+            anket.tick = data.content.version;
+            break;
+
+        case "Question":
+            anket.questions[data.content.id] = data.content;
+            anket_renderQuestion();
+            break;
+
+        case "QuestionDelta":
+            anket_applyQuestionDelta(data.content.delta);
+            anket_renderQuestion();
+            break;
+
+        case "PinnedItems":
+            anket.items.pinned = data.content;
+            // pin links of other items depend on this list
+            anket_renderAllItems();
+            break;
+
+        case "UserItems":
+            anket.items.user = data.content;
+            anket_renderItems(anket.items.user, anket.canvas.user_items);
+            break;
+
+        case "UserVotes":
+            anket_renderUserVotes(data.content);
+            break;
+
+        case "ItemOverlay":
+            anket.overlay = data.content;
+            anket_renderAllItems();
+            break;
+
+        case "PendingItems":
+            anket_renderPendingItems(data.content);
+            break;
+
+        case "ItemPending":
+            alert("Your item will be listed after poll owner approves it.");
+            break;
+    }
 }
 
 var anket_HTML = {
@@ -129,17 +1352,89 @@ var anket_HTML = {
 <div class="pure-g">
   <div class="pure-u-1">
     <h1 id="anket-pollTitle"></h1>
+    <p id="anket-pollDescription" class="poll-description" hidden></p>
+    <form id="anket-nameForm" class="pure-form" hidden>
+      <input id="anket-userName" type="text" maxlength="32" placeholder="Your name in this poll" />
+      <button type="submit" class="pure-button">Set Name</button>
+    </form>
+    <p id="anket-pollClosed" class="error-box" hidden>This poll is closed, results are read-only.</p>
+    <p id="anket-pollCredits" hidden></p>
+    <p id="anket-pollVotes" hidden></p>
+    <p id="anket-pollItems" hidden></p>
+    <p id="anket-pollHidden" hidden>Scores are hidden until the poll is closed.</p>
+    <p id="anket-pollShuffled" hidden>Items are shown in random order until the poll is closed.</p>
+    <div id="anket-pollOwner" hidden>
+      <details class="pending-items" open hidden>
+        <summary>Waiting for approval</summary>
+        <ul id="anket-pendingItems"></ul>
+      </details>
+      <button id="anket-closePoll" class="pure-button">Close Poll</button>
+      <button id="anket-resetVotes" class="pure-button">Reset Votes</button>
+      <a id="anket-insights" class="pure-button" target="_blank">Insights</a>
+      <a id="anket-export" class="pure-button" target="_blank">Export</a>
+      <a id="anket-auditLog" class="pure-button" target="_blank">Audit Log</a>
+      <table class="pure-table">
+        <caption>Participants</caption>
+        <thead>
+          <tr><th>Participant</th><th>Items</th><th>Weight</th><th></th></tr>
+        </thead>
+        <tbody id="anket-participants"></tbody>
+      </table>
+      <form id="anket-questionForm" class="pure-form">
+        <input id="anket-questionTitle" type="text" placeholder="Another question" />
+        <select id="anket-questionVoteMode">
+          <option value="UpDown">Upvote &amp; Downvote</option>
+          <option value="Approval">Approval</option>
+          <option value="Quadratic">Quadratic</option>
+          <option value="Rating">Star Rating</option>
+        </select>
+        <button type="submit" class="pure-button">Add Question</button>
+      </form>
+      <form id="anket-sectionsForm" class="pure-form">
+        <textarea id="anket-sectionNames" rows="3" placeholder="Sections, one per line; like Went well, Needs improvement"></textarea>
+        <button type="submit" class="pure-button">Save Sections</button>
+      </form>
+      <details id="anket-ingest" hidden>
+        <summary>External systems</summary>
+        <p>Send signed requests to <code id="anket-ingestUrl"></code> with secret <code id="anket-ingestSecret"></code></p>
+      </details>
+      <details>
+        <summary>Moderation log</summary>
+        <ul id="anket-moderationLog"></ul>
+      </details>
+    </div>
+    <div id="anket-pinned" class="pinned-items" hidden>
+      <h2>Pinned</h2>
+      <div id="anket-itemsPinned"></div>
+    </div>
+    <nav id="anket-questions" hidden></nav>
+    <h2 id="anket-questionTitleText" hidden></h2>
     <form id="anket-pollForm" class="pure-form">
       <fieldset>
         <legend>Create an option for this poll</legend>
         <input id="anket-itemText" type="text" class="pure-u-1 pure-u-sm-3-4" placeholder="Option text" />
+        <select id="anket-itemSection" class="pure-u-1 pure-u-sm-3-4" hidden></select>
         <button type="submit" class="pure-u-3-4 pure-u-sm-5-24 pure-button pure-button-primary">
           Create
         </button>
+        <label for="anket-itemImage" class="attach-image">
+          Attach an image
+          <input id="anket-itemImage" type="file" accept="image/png,image/jpeg,image/gif,image/webp" />
+        </label>
+        <div id="anket-similarItems" class="similar-items" hidden>
+          <small>Did you mean one of these? You can vote them instead.</small>
+          <ul></ul>
+        </div>
       </fieldset>
     </form>
+    <details>
+      <summary>My Votes</summary>
+      <button id="anket-undo" class="pure-button" hidden>Undo Last Vote</button>
+      <ul id="anket-userVotes"></ul>
+    </details>
   </div>
 </div>
+<div id="anket-sections" class="pure-g" hidden></div>
 <div class="pure-g">
   <div class="pure-u-1 pure-u-xl-1-3">
     <h2 class="text-center">Top Voted Items</h2>
@@ -165,5 +1460,16 @@ var anket_HTML = {
   </div>
   <div class="option-content"></div>
 </div>
+`,
+
+    "participant": `
+<td class="participant-name"></td>
+<td class="participant-items"></td>
+<td><input class="participant-weight" type="number" min="1" max="10" /></td>
+<td class="participant-actions">
+  <a class="participant-shadowBan" href="#"></a>
+  <a class="participant-kick" href="#">Kick</a>
+  <a class="participant-ban" href="#">Ban</a>
+</td>
 `,
 };
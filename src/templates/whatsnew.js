@@ -0,0 +1,64 @@
+window.addEventListener("load", anket_checkVersion);
+
+var anket_VERSION_KEY = "anket_version";
+
+function anket_checkVersion() {
+    fetch(anket_url("/api/version"))
+        .then(function (response) { return response.json(); })
+        .then(function (info) {
+            var seen = window.localStorage.getItem(anket_VERSION_KEY);
+            window.localStorage.setItem(anket_VERSION_KEY, info.version);
+            // first visit; there is nothing "new" for this user
+            if (seen === null || seen == info.version) {
+                return;
+            }
+
+            var releases = [];
+            for (var i = 0; i < info.changelog.length; i++) {
+                if (info.changelog[i].version == seen) {
+                    break;
+                }
+                releases.push(info.changelog[i]);
+            }
+            if (releases.length > 0) {
+                anket_showWhatsNew(releases);
+            }
+        })
+        .catch(function () {
+            // not being able to show changelog shouldn't bother the user
+        });
+}
+
+function anket_showWhatsNew(releases) {
+    var dialog = document.createElement("dialog");
+    dialog.className = "whatsnew-dialog";
+    dialog.innerHTML = anket_WHATSNEW_HTML;
+
+    var list = dialog.querySelector(".whatsnew-releases");
+    releases.forEach(function (release) {
+        var title = document.createElement("h3");
+        title.innerText = release.version;
+        list.appendChild(title);
+
+        var changes = document.createElement("ul");
+        release.changes.forEach(function (change) {
+            var entry = document.createElement("li");
+            entry.innerText = change;
+            changes.appendChild(entry);
+        });
+        list.appendChild(changes);
+    });
+
+    dialog.querySelector("button").onclick = function () {
+        dialog.close();
+        dialog.remove();
+    };
+    document.body.appendChild(dialog);
+    dialog.showModal();
+}
+
+var anket_WHATSNEW_HTML = `
+<h2>What's New</h2>
+<div class="whatsnew-releases"></div>
+<button class="pure-button pure-button-primary">Got it</button>
+`;
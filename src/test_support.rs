@@ -0,0 +1,163 @@
+// helpers shared by unit tests of every module
+//
+// tests that create polls must run on `#[tokio::test(start_paused = true)]`; workers of polls
+// and `Polls` run on tokio timers, and paused time lets tests jump over minutes instantly
+use crate::models::{
+    AddItemPermit, DuplicateVoteCheck, Poll, PollEvent, PollSettings, Polls, RankingMethod,
+    RetentionPolicy, UserDetails, UserFeed, UserLookupMethod, VoteMode,
+};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+// polls without any change for this long are closed by their workers
+pub const INACTIVITY: Duration = Duration::from_secs(15 * 60);
+pub const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+// moves the paused clock forward; timers that fire in between run before this returns
+pub async fn pass(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+// details of a browser user; users of session based polls are told apart by their sessions
+pub fn user(ip: [u8; 4]) -> UserDetails {
+    UserDetails {
+        ip: IpAddr::from(ip),
+        id: None,
+    }
+}
+
+// settings that every test starts from; nothing is limited, nothing is hidden
+pub fn settings() -> PollSettings {
+    PollSettings {
+        title: "test poll".to_string(),
+        description: None,
+        user_lookup_method: UserLookupMethod::SessionBased,
+        add_item_permit: AddItemPermit::Anyone,
+        vote_mode: VoteMode::UpDown,
+        retention_policy: RetentionPolicy::Keep,
+        retention_days: 1,
+        vote_credits: 100,
+        hide_results_until_close: false,
+        ranking_method: RankingMethod::Score,
+        nudges: false,
+        allow_downvotes: true,
+        duplicate_vote_check: DuplicateVoteCheck::Off,
+        shuffle_items: false,
+        allow_ingestion: false,
+        show_authors: false,
+        moderate_items: false,
+        markdown: false,
+        min_item_length: 1,
+        max_item_length: 300,
+        edit_window_minutes: 0,
+        max_votes_per_user: 0,
+        max_items_per_user: 0,
+        max_items_total: 0,
+        broadcast_delay_ms: None,
+    }
+}
+
+pub struct PollBuilder {
+    settings: PollSettings,
+    owner: UserDetails,
+}
+
+impl Default for PollBuilder {
+    fn default() -> Self {
+        Self {
+            settings: settings(),
+            owner: user([127, 0, 0, 1]),
+        }
+    }
+}
+
+impl PollBuilder {
+    pub fn vote_mode(mut self, vote_mode: VoteMode) -> Self {
+        self.settings.vote_mode = vote_mode;
+        self
+    }
+    pub fn retention(mut self, retention_policy: RetentionPolicy) -> Self {
+        self.settings.retention_policy = retention_policy;
+        self
+    }
+    pub fn user_lookup(mut self, user_lookup_method: UserLookupMethod) -> Self {
+        self.settings.user_lookup_method = user_lookup_method;
+        self
+    }
+    // for the settings that don't have their own method
+    pub fn with(mut self, change: impl FnOnce(&mut PollSettings)) -> Self {
+        change(&mut self.settings);
+        self
+    }
+    pub fn build(self, polls: &Arc<Mutex<Polls>>) -> TestPoll {
+        let (owner_id, poll) = polls.lock().unwrap().add_poll(self.settings, self.owner);
+        let id = poll.lock().unwrap().get_id().to_owned();
+        TestPoll { id, owner_id, poll }
+    }
+}
+
+pub struct TestPoll {
+    pub id: String,
+    pub owner_id: Uuid,
+    pub poll: Arc<Mutex<Poll>>,
+}
+
+impl TestPoll {
+    // joins a new user from `ip`; events sent to them are collected by the returned inspector
+    pub fn join(&self, ip: [u8; 4]) -> (Uuid, Inspector) {
+        let (user_id, feed) = self.poll.lock().unwrap().join(user(ip)).unwrap();
+        (user_id, Inspector::new(feed))
+    }
+    // connects the owner; events sent to them are collected by the returned inspector
+    pub fn connect_owner(&self) -> Inspector {
+        let details = UserDetails {
+            ip: IpAddr::from([127, 0, 0, 1]),
+            id: Some(self.owner_id),
+        };
+        let (_, feed) = self.poll.lock().unwrap().join(details).unwrap();
+        Inspector::new(feed)
+    }
+}
+
+// connection of a user that never sends anything; keeps what a browser would have received
+pub struct Inspector {
+    feed: UserFeed,
+}
+
+impl Inspector {
+    pub fn new(feed: UserFeed) -> Self {
+        Self { feed }
+    }
+    // events of the latest update since the last call
+    pub fn drain(&mut self) -> Vec<PollEvent> {
+        self.feed
+            .try_next()
+            .into_iter()
+            .flatten()
+            .map(Arc::unwrap_or_clone)
+            .collect()
+    }
+    // whether poll closed the connections of this user
+    pub fn is_disconnected(&mut self) -> bool {
+        self.feed.is_closed()
+    }
+    // names of the events received since the last call, like `["Clock", "Question"]`
+    pub fn drain_kinds(&mut self) -> Vec<&'static str> {
+        self.drain()
+            .iter()
+            .map(|event| match event {
+                PollEvent::Clock(_) => "Clock",
+                PollEvent::Meta(_) => "Meta",
+                PollEvent::Question(_) => "Question",
+                PollEvent::PinnedItems(_) => "PinnedItems",
+                PollEvent::UserItems(_) => "UserItems",
+                PollEvent::PendingItems(_) => "PendingItems",
+                PollEvent::UserVotes(_) => "UserVotes",
+                PollEvent::ItemOverlay(_) => "ItemOverlay",
+                PollEvent::Nudge(_) => "Nudge",
+            })
+            .collect()
+    }
+}
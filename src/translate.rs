@@ -0,0 +1,75 @@
+use hyper::{client::HttpConnector, Body, Client, Method, Request, Uri};
+use thiserror::Error;
+
+// language codes like `en`, `pt-BR`
+const MAX_LANG_LENGTH: usize = 8;
+
+/// Client of a LibreTranslate compatible translation endpoint.
+pub struct Translator {
+    client: Client<HttpConnector>,
+    endpoint: Uri,
+}
+
+#[derive(serde::Serialize)]
+struct TranslateReq<'a> {
+    q: &'a str,
+    source: &'static str,
+    target: &'a str,
+    format: &'static str,
+}
+
+#[derive(serde::Deserialize)]
+struct TranslateResp {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+impl Translator {
+    pub fn new(endpoint: Uri) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint,
+        }
+    }
+
+    pub async fn translate(&self, text: &str, lang: &str) -> Result<String, TranslateError> {
+        let body = serde_json::to_vec(&TranslateReq {
+            q: text,
+            source: "auto",
+            target: lang,
+            format: "text",
+        })
+        .expect("request should serialize");
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.endpoint.clone())
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .expect("request parts are valid");
+
+        let response = self.client.request(request).await?;
+        if !response.status().is_success() {
+            return Err(TranslateError::Provider(response.status().as_u16()));
+        }
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let response: TranslateResp =
+            serde_json::from_slice(&body).map_err(|_| TranslateError::InvalidResponse)?;
+        Ok(response.translated_text)
+    }
+}
+
+pub fn is_valid_lang(lang: &str) -> bool {
+    !lang.is_empty()
+        && lang.len() <= MAX_LANG_LENGTH
+        && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+#[derive(Debug, Error)]
+pub enum TranslateError {
+    #[error("Translation service is not reachable.")]
+    Connection(#[from] hyper::Error),
+    #[error("Translation service failed with status {0}.")]
+    Provider(u16),
+    #[error("Translation service returned an unexpected response.")]
+    InvalidResponse,
+}
@@ -1,7 +1,9 @@
+use sha1::{Digest, Sha1};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::IpAddr;
 use std::str::FromStr;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 pub fn rand_string(length: usize) -> String {
@@ -98,9 +100,73 @@ impl<T> RingBuffer<T> {
     pub fn pop(&mut self) -> Option<T> {
         self.vec.pop_back()
     }
+    // removes the item that is pushed last
+    pub fn pop_latest(&mut self) -> Option<T> {
+        self.vec.pop_front()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.vec.clear();
+    }
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.vec.retain(f);
+    }
     pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
         self.vec.iter()
     }
+    pub fn iter_mut(&mut self) -> std::collections::vec_deque::IterMut<'_, T> {
+        self.vec.iter_mut()
+    }
+}
+
+// builds paths of this instance; anket may be served under a sub-path behind a proxy
+#[derive(Clone, Debug)]
+pub struct Urls {
+    // empty, or starts with a slash and doesn't end with one
+    base_path: String,
+}
+
+impl Urls {
+    pub fn new(base_path: String) -> Self {
+        Self { base_path }
+    }
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+    // `path` starts with a slash
+    pub fn path(&self, path: &str) -> String {
+        format!("{}{}", self.base_path, path)
+    }
+    pub fn poll(&self, poll_id: &str) -> String {
+        self.path(&format!("/p/{}", poll_id))
+    }
+    // root of this instance; cookies shared by every page use this
+    pub fn root(&self) -> String {
+        self.path("/")
+    }
+}
+
+/// `f64` wrapper with total ordering, so it can be used as a key of sorted collections.
+#[derive(Clone, Copy, Debug)]
+pub struct OrderedF64(pub f64);
+
+impl PartialEq for OrderedF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+impl Eq for OrderedF64 {}
+impl PartialOrd for OrderedF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for OrderedF64 {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
 }
 
 pub struct TouchTimed<T> {
@@ -132,9 +198,135 @@ pub fn forwarded_header_ip(header_value: &axum::http::header::HeaderValue) -> Op
     IpAddr::from_str(
         std::str::from_utf8(header_value.as_bytes())
             .ok()?
-            .split_once(',')?
-            .0
+            .split(',')
+            .next()?
             .trim(),
     )
     .ok()
 }
+
+// block size of SHA-1, in bytes
+const BLOCK_SIZE: usize = 64;
+
+pub fn hmac_sha1(secret: &[u8], body: &[u8]) -> [u8; 20] {
+    let mut key = [0u8; BLOCK_SIZE];
+    if secret.len() > BLOCK_SIZE {
+        key[..20].copy_from_slice(&Sha1::digest(secret));
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+
+    let mut inner = Sha1::new();
+    inner.update(key.map(|byte| byte ^ 0x36));
+    inner.update(body);
+    let mut outer = Sha1::new();
+    outer.update(key.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// compared in constant time; so signatures can't be guessed byte by byte
+pub fn constant_time_eq(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::HeaderValue;
+
+    #[test]
+    fn ring_buffer_drops_oldest_items() {
+        let mut buffer = RingBuffer::new(3);
+        for item in 1..=4 {
+            buffer.push(item);
+        }
+        // latest first
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [4, 3, 2]);
+        assert_eq!(buffer.pop(), Some(2));
+        assert_eq!(buffer.pop_latest(), Some(4));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn ring_buffer_retains_matching_items() {
+        let mut buffer = RingBuffer::new(5);
+        for item in 1..=5 {
+            buffer.push(item);
+        }
+        buffer.retain(|item| item % 2 == 0);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [4, 2]);
+        buffer.retain(|_| false);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop_latest(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn touch_timed_measures_since_last_update() {
+        let mut changed = TouchTimed::new(false);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(changed.elapsed(), Duration::from_secs(10));
+
+        changed.update(true);
+        assert!(*changed.value());
+        assert_eq!(changed.elapsed(), Duration::ZERO);
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert_eq!(changed.elapsed(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn forwarded_header_ip_is_the_client() {
+        let ip = |value: &'static str| forwarded_header_ip(&HeaderValue::from_static(value));
+        assert_eq!(ip("203.0.113.7"), Some(IpAddr::from([203, 0, 113, 7])));
+        assert_eq!(
+            ip("203.0.113.7, 10.0.0.1, 10.0.0.2"),
+            Some(IpAddr::from([203, 0, 113, 7]))
+        );
+        assert_eq!(
+            ip(" 2001:db8::1 ,10.0.0.1"),
+            "2001:db8::1".parse::<IpAddr>().ok()
+        );
+        assert_eq!(ip("unknown, 10.0.0.1"), None);
+        assert_eq!(ip(""), None);
+    }
+
+    #[test]
+    fn ordered_f64_orders_every_value() {
+        let mut values = [OrderedF64(1.5), OrderedF64(-0.5), OrderedF64(f64::NAN)];
+        values.sort();
+        assert_eq!(values[0], OrderedF64(-0.5));
+        assert_eq!(values[1], OrderedF64(1.5));
+        assert!(values[2].0.is_nan());
+    }
+
+    #[test]
+    fn urls_are_built_under_base_path() {
+        let root = Urls::new(String::new());
+        assert_eq!(root.poll("abc"), "/p/abc");
+        assert_eq!(root.root(), "/");
+
+        let nested = Urls::new("/anket".to_string());
+        assert_eq!(nested.poll("abc"), "/anket/p/abc");
+        assert_eq!(nested.root(), "/anket/");
+    }
+
+    #[test]
+    fn hmac_sha1_matches_rfc_2202() {
+        let signature = hmac_sha1(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            to_hex(&signature),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd") && !constant_time_eq("abc", "ab"));
+    }
+}
@@ -1,22 +1,40 @@
-use crate::{models, utils, AppState, SESSION_DURATION, SESSION_KEY};
+use crate::{
+    changelog, ingest, models, translate, utils, AppConfig, AppState, MIN_TITLE_LENGTH,
+    NODE_HEADER, NODE_KEY, RECENT_POLLS_COUNT, RECENT_POLLS_KEY, SESSION_DURATION, SESSION_KEY,
+};
+use anket_shared::protocol::{
+    negotiate_hello, ActionError, MessageId, OwnerMessage, Reply, UserMessage, UserResponse,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
 
 use axum::{
-    extract::{rejection, ws, ConnectInfo, Extension, Path, State},
-    http::{header, Request, StatusCode},
+    body::Bytes,
+    extract::{rejection, ws, ConnectInfo, Extension, FromRequest, Path, Query, State},
+    http::{header, HeaderMap, Request, StatusCode},
     middleware,
-    response::{Html, IntoResponse, Redirect, Response},
-    routing, Form,
+    response::{sse, Html, IntoResponse, Redirect, Response},
+    routing, Form, Json,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
+use flate2::write::ZlibEncoder;
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use minijinja::context;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
+    io::Write,
     net::SocketAddr,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, watch},
+    time::{Instant, MissedTickBehavior},
 };
-use tokio::sync::mpsc;
 use uuid::Uuid;
 
 // TODO transform this into tower middleware
@@ -36,9 +54,15 @@ pub async fn identify_user<B>(
             },
             None => socket_addr.ip(),
         };
-        let id = match cookies.get(SESSION_KEY) {
-            Some(cookie) => Uuid::from_str(cookie.value()).ok(),
-            None => None,
+        // API clients send the token they got instead of the session cookie
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let id = match (bearer, cookies.get(SESSION_KEY)) {
+            (Some(token), _) => Uuid::from_str(token.trim()).ok(),
+            (None, Some(cookie)) => Uuid::from_str(cookie.value()).ok(),
+            (None, None) => None,
         };
         models::UserDetails { ip, id }
     };
@@ -46,6 +70,40 @@ pub async fn identify_user<B>(
     next.run(request).await
 }
 
+// polls live in memory of the instance that created them; multi-instance deployments route
+// requests by this header or the poll cookie set by `remember_node`
+pub async fn node_header<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: middleware::Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    if let Some(node_id) = &state.config.node_id {
+        response.headers_mut().insert(
+            NODE_HEADER,
+            node_id
+                .parse()
+                .expect("node id is checked to be a valid header value"),
+        );
+    }
+    response
+}
+
+// requests of a poll should go to the instance that holds it
+fn remember_node(cookies: CookieJar, poll_id: &str, state: &AppState) -> CookieJar {
+    match &state.config.node_id {
+        Some(node_id) => cookies.add(
+            Cookie::build(NODE_KEY, node_id.clone())
+                .max_age(SESSION_DURATION)
+                .http_only(true)
+                .path(state.urls.poll(poll_id))
+                .secure(state.config.secure)
+                .finish(),
+        ),
+        None => cookies,
+    }
+}
+
 pub fn assets_router(state: AppState) -> routing::Router<AppState> {
     routing::Router::new()
         .route(
@@ -76,6 +134,20 @@ pub fn assets_router(state: AppState) -> routing::Router<AppState> {
                 )
             }),
         )
+        .route(
+            "/whatsnew.js",
+            routing::get(|State(state): State<AppState>| async move {
+                (
+                    [(header::CONTENT_TYPE, "text/javascript")],
+                    state
+                        .templates
+                        .get_template("whatsnew.js")
+                        .unwrap()
+                        .render(context!())
+                        .unwrap(),
+                )
+            }),
+        )
         .with_state(state)
 }
 
@@ -94,29 +166,176 @@ pub async fn handler_404(State(state): State<AppState>) -> Response {
         .into_response()
 }
 
-pub async fn anket_index() -> Response {
-    // TODO make an actual index page
-    Redirect::temporary("/p").into_response()
+pub async fn api_version() -> Response {
+    Json(changelog::version_info()).into_response()
 }
 
-pub async fn poll_index(State(state): State<AppState>) -> Response {
-    Html(
-        state
-            .templates
-            .get_template("poll-form.jinja")
-            .unwrap()
-            .render(context!())
-            .unwrap(),
+#[derive(Serialize, Clone, Debug)]
+struct ServerTime {
+    // unix timestamp in milliseconds
+    server_time: i64,
+}
+
+// clients calculate offset of their clocks by this
+pub async fn server_time() -> Response {
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(ServerTime {
+            server_time: models::unix_millis(cookie::time::OffsetDateTime::now_utc()),
+        }),
     )
+        .into_response()
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct ClientConfig {
+    // host of the request; clients that are served from elsewhere connect to this
+    host: Option<String>,
+    secure: bool,
+    base_path: String,
+    protocol_version: u32,
+    min_protocol_version: u32,
+    features: Vec<String>,
+    // limits of new polls, in characters; clients can check them before creating a poll
+    min_title_length: usize,
+    max_title_length: usize,
+    max_description_length: usize,
+}
+
+// settings of this instance that scripts need before they connect to a poll
+pub async fn client_config(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(str::to_owned);
+    Json(ClientConfig {
+        host,
+        secure: state.config.secure,
+        base_path: state.urls.base_path().to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        features: server_features(&state.config),
+        min_title_length: MIN_TITLE_LENGTH,
+        max_title_length: state.config.max_title_length,
+        max_description_length: state.config.max_description_length,
+    })
     .into_response()
 }
 
-fn poll_cookie(user_id: &Uuid, poll_id: &str, secure: bool) -> Cookie<'static> {
+fn recent_polls(cookies: &CookieJar) -> Vec<String> {
+    match cookies.get(RECENT_POLLS_KEY) {
+        Some(cookie) => cookie
+            .value()
+            .split(',')
+            .filter(|poll_id| !poll_id.is_empty())
+            .map(|poll_id| poll_id.to_owned())
+            .collect(),
+        None => vec![],
+    }
+}
+
+// moves given poll to the top of recently visited polls of this browser
+fn remember_poll(cookies: CookieJar, poll_id: &str, state: &AppState) -> CookieJar {
+    let mut poll_ids = recent_polls(&cookies);
+    poll_ids.retain(|id| id != poll_id);
+    poll_ids.insert(0, poll_id.to_owned());
+    poll_ids.truncate(RECENT_POLLS_COUNT);
+
+    cookies.add(
+        Cookie::build(RECENT_POLLS_KEY, poll_ids.join(","))
+            .max_age(SESSION_DURATION)
+            .http_only(true)
+            .path(state.urls.root())
+            .secure(state.config.secure)
+            .finish(),
+    )
+}
+
+fn render_index(state: &AppState, cookies: &CookieJar, error: Option<&str>) -> String {
+    let recent = state
+        .polls
+        .lock()
+        .unwrap()
+        .get_summaries(&recent_polls(cookies));
+    state
+        .templates
+        .get_template("index.jinja")
+        .unwrap()
+        .render(context!(
+            error => error,
+            recent_polls => recent,
+            announcement => state.config.announcement,
+            title_min => MIN_TITLE_LENGTH,
+            title_max => state.config.max_title_length,
+        ))
+        .unwrap()
+}
+
+pub async fn anket_index(State(state): State<AppState>, cookies: CookieJar) -> Response {
+    Html(render_index(&state, &cookies, None)).into_response()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JoinByCodeReq {
+    code: String,
+}
+
+pub async fn join_by_code(
+    State(state): State<AppState>,
+    cookies: CookieJar,
+    Query(query): Query<JoinByCodeReq>,
+) -> Response {
+    let code = query.code.trim();
+    if state.polls.lock().unwrap().get_poll(code).is_some() {
+        Redirect::to(&state.urls.poll(code)).into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Html(render_index(
+                &state,
+                &cookies,
+                Some("There is no poll with this code, it may have been closed."),
+            )),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Serialize, Clone, Debug, utoipa::ToSchema)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+fn render_poll_form(state: &AppState, error: Option<&str>, field_errors: &[FieldError]) -> String {
+    state
+        .templates
+        .get_template("poll-form.jinja")
+        .unwrap()
+        .render(context!(
+            error => error,
+            field_errors => field_errors,
+            title_min => MIN_TITLE_LENGTH,
+            title_max => state.config.max_title_length,
+            description_max => state.config.max_description_length,
+            retention_max => models::MAX_RETENTION_DAYS,
+            credits_max => models::MAX_VOTE_CREDITS,
+            edit_window_max => models::MAX_EDIT_WINDOW_MINUTES,
+            item_length_max => state.config.max_item_length,
+        ))
+        .unwrap()
+}
+
+pub async fn poll_index(State(state): State<AppState>) -> Response {
+    Html(render_poll_form(&state, None, &[])).into_response()
+}
+
+fn poll_cookie(user_id: &Uuid, poll_id: &str, state: &AppState) -> Cookie<'static> {
     Cookie::build(SESSION_KEY, user_id.to_string())
         .max_age(SESSION_DURATION)
         .http_only(false)
-        .path(format!("/p/{}", poll_id))
-        .secure(secure)
+        .path(state.urls.poll(poll_id))
+        .secure(state.config.secure)
         .finish()
 }
 
@@ -126,54 +345,188 @@ pub struct CreatePollReq {
     settings: models::PollSettings,
 }
 
+pub fn validate_poll_settings(
+    settings: &models::PollSettings,
+    config: &AppConfig,
+) -> Vec<FieldError> {
+    let mut errors = vec![];
+    let max_title_length = config.max_title_length;
+    let max_description_length = config.max_description_length;
+
+    let title_length = settings.title.chars().count();
+    if title_length < MIN_TITLE_LENGTH {
+        errors.push(FieldError {
+            field: "title",
+            message: format!(
+                "Poll title must be at least {} characters long.",
+                MIN_TITLE_LENGTH
+            ),
+        });
+    } else if title_length > max_title_length {
+        errors.push(FieldError {
+            field: "title",
+            message: format!(
+                "Poll title must be at most {} characters long.",
+                max_title_length
+            ),
+        });
+    }
+
+    let description_length = settings
+        .description
+        .as_deref()
+        .map_or(0, |description| description.trim().chars().count());
+    if description_length > max_description_length {
+        errors.push(FieldError {
+            field: "description",
+            message: format!(
+                "Poll description must be at most {} characters long.",
+                max_description_length
+            ),
+        });
+    }
+
+    if settings.retention_policy != models::RetentionPolicy::Purge
+        && !(1..=models::MAX_RETENTION_DAYS).contains(&settings.retention_days)
+    {
+        errors.push(FieldError {
+            field: "retention_days",
+            message: format!(
+                "Results can be kept between 1 and {} days.",
+                models::MAX_RETENTION_DAYS
+            ),
+        });
+    }
+
+    if settings.vote_mode == models::VoteMode::Quadratic
+        && !(1..=models::MAX_VOTE_CREDITS).contains(&settings.vote_credits)
+    {
+        errors.push(FieldError {
+            field: "vote_credits",
+            message: format!(
+                "Vote credits must be between 1 and {}.",
+                models::MAX_VOTE_CREDITS
+            ),
+        });
+    }
+
+    if settings.max_item_length > config.max_item_length {
+        errors.push(FieldError {
+            field: "max_item_length",
+            message: format!(
+                "Items can be at most {} characters long.",
+                config.max_item_length
+            ),
+        });
+    } else if settings.min_item_length == 0 || settings.min_item_length > settings.max_item_length {
+        errors.push(FieldError {
+            field: "min_item_length",
+            message: "Minimum item length must be between 1 and the maximum.".to_string(),
+        });
+    }
+
+    if settings.edit_window_minutes > models::MAX_EDIT_WINDOW_MINUTES {
+        errors.push(FieldError {
+            field: "edit_window_minutes",
+            message: format!(
+                "Items can be editable for at most {} minutes.",
+                models::MAX_EDIT_WINDOW_MINUTES
+            ),
+        });
+    }
+
+    if settings
+        .broadcast_delay_ms
+        .is_some_and(|delay| delay > models::MAX_BROADCAST_DELAY_MS)
+    {
+        errors.push(FieldError {
+            field: "broadcast_delay_ms",
+            message: format!(
+                "Changes can be delayed for at most {} milliseconds.",
+                models::MAX_BROADCAST_DELAY_MS
+            ),
+        });
+    }
+
+    if settings.vote_mode == models::VoteMode::Rating
+        && settings.ranking_method == models::RankingMethod::Hot
+    {
+        errors.push(FieldError {
+            field: "ranking_method",
+            message: "Hot ranking is not available on star rating polls.".to_string(),
+        });
+    }
+
+    if settings.vote_mode != models::VoteMode::UpDown
+        && settings.ranking_method == models::RankingMethod::Wilson
+    {
+        errors.push(FieldError {
+            field: "ranking_method",
+            message: "Confidence ranking is only available on upvote & downvote polls.".to_string(),
+        });
+    }
+
+    errors
+}
+
 pub async fn create_poll(
     State(state): State<AppState>,
     Extension(user): Extension<models::UserDetails>,
     cookies: CookieJar,
     form: Result<Form<CreatePollReq>, rejection::FormRejection>,
 ) -> Response {
-    let form_with_err = |msg: &str| {
+    let form_with_err = |msg: Option<&str>, field_errors: &[FieldError]| {
         (
             StatusCode::BAD_REQUEST,
-            Html(
-                state
-                    .templates
-                    .get_template("poll-form.jinja")
-                    .unwrap()
-                    .render(context!(error => msg))
-                    .unwrap(),
-            ),
+            Html(render_poll_form(&state, msg, field_errors)),
         )
             .into_response()
     };
 
     if let Err(err) = form {
-        return form_with_err(&err.to_string());
+        return form_with_err(Some(&err.to_string()), &[]);
     }
 
-    let Form(form) = form.expect("we checked that this form is valid");
-    if form.settings.title.len() < 3 {
-        return form_with_err("Poll title must be at least 3 characters long.");
+    let Form(mut form) = form.expect("we checked that this form is valid");
+    if form.settings.max_item_length == 0 {
+        form.settings.max_item_length = state.config.max_item_length;
+    }
+    let field_errors = validate_poll_settings(&form.settings, &state.config);
+    if !field_errors.is_empty() {
+        return form_with_err(None, &field_errors);
     }
 
     let (user_id, poll) = state.polls.lock().unwrap().add_poll(form.settings, user);
     let poll_id = poll.lock().unwrap().get_id().to_owned();
-    let cookies = cookies.add(poll_cookie(&user_id, &poll_id, state.config.secure));
+    let cookies = cookies.add(poll_cookie(&user_id, &poll_id, &state));
+    let cookies = remember_poll(cookies, &poll_id, &state);
+    let cookies = remember_node(cookies, &poll_id, &state);
 
-    (cookies, Redirect::to(&format!("/p/{}", poll_id))).into_response()
+    (cookies, Redirect::to(&state.urls.poll(&poll_id))).into_response()
 }
 
-pub async fn get_poll(State(state): State<AppState>, Path(poll_id): Path<String>) -> Response {
-    match state.polls.lock().unwrap().get_poll(&poll_id) {
-        Some(_) => Html(
-            state
-                .templates
-                .get_template("poll.jinja")
-                .unwrap()
-                .render(context!())
-                .unwrap(),
+pub async fn get_poll(
+    State(state): State<AppState>,
+    cookies: CookieJar,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    match poll {
+        Some(_) => (
+            remember_node(remember_poll(cookies, &poll_id, &state), &poll_id, &state),
+            Html(
+                state
+                    .templates
+                    .get_template("poll.jinja")
+                    .unwrap()
+                    .render(context!(
+                        poll_id => poll_id,
+                        translation => state.translator.is_some(),
+                    ))
+                    .unwrap(),
+            ),
         )
-        .into_response(),
+            .into_response(),
         None => (
             StatusCode::NOT_FOUND,
             Html(
@@ -191,24 +544,652 @@ pub async fn get_poll(State(state): State<AppState>, Path(poll_id): Path<String>
     }
 }
 
-pub async fn join_poll(
+pub async fn poll_insights(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let insights = poll.lock().unwrap().insights(&user);
+    match insights {
+        Ok(insights) => Json(insights).into_response(),
+        Err(err) => (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    }
+}
+
+pub async fn poll_export(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let export = poll
+        .lock()
+        .unwrap()
+        .export(&user, &mut models::Pseudonyms::default());
+    match export {
+        Ok(export) => Json(export).into_response(),
+        Err(err) => (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    }
+}
+
+// same pseudonyms as in exports; they're numbered by first appearance in the log
+pub async fn poll_audit_log(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let audit_log = poll
+        .lock()
+        .unwrap()
+        .audit_log(&user, &mut models::Pseudonyms::default());
+    match audit_log {
+        Ok(audit_log) => Json(audit_log).into_response(),
+        Err(err) => (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AttachmentRes {
+    id: Uuid,
+}
+
+// images are uploaded as raw request bodies; their ids are sent along with new items later
+pub async fn upload_attachment(
     State(state): State<AppState>,
     Extension(user): Extension<models::UserDetails>,
     Path(poll_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let result = poll.lock().unwrap().add_attachment(&user, body);
+    match result {
+        Ok(id) => Json(AttachmentRes { id }).into_response(),
+        Err(err @ models::AddAttachmentError::UnsupportedType) => {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, err.to_string()).into_response()
+        }
+        Err(err) => (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    }
+}
+
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    Path((poll_id, attachment_id)): Path<(String, Uuid)>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(attachment) = poll.and_then(|poll| poll.lock().unwrap().attachment(&attachment_id))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        [
+            (header::CONTENT_TYPE, attachment.image_type.content_type()),
+            // attachments never change; their ids are not used again
+            (
+                header::CACHE_CONTROL,
+                "private, max-age=31536000, immutable",
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        attachment.data,
+    )
+        .into_response()
+}
+
+// body of a request that is either a submitted HTML form or JSON
+pub enum FormOrJson<T> {
+    Form(T),
+    Json(T),
+}
+
+#[axum::async_trait]
+impl<T, S, B> FromRequest<S, B> for FormOrJson<T>
+where
+    Form<T>: FromRequest<S, B, Rejection = rejection::FormRejection>,
+    Json<T>: FromRequest<S, B, Rejection = rejection::JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+        if is_json {
+            let Json(body) = Json::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self::Json(body))
+        } else {
+            let Form(body) = Form::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self::Form(body))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddItemReq {
+    text: String,
+    // items are added to the first question if this is missing
+    #[serde(default)]
+    question_id: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AddItemRes {
+    item_id: usize,
+    // item is only listed after poll owner approves it
+    pending: bool,
+}
+
+// for clients without websockets; forms are redirected back to poll page, JSON gets the item id
+pub async fn add_poll_item(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    cookies: CookieJar,
+    Path(poll_id): Path<String>,
+    body: FormOrJson<AddItemReq>,
+) -> Response {
+    let (is_form, req) = match body {
+        FormOrJson::Form(req) => (true, req),
+        FormOrJson::Json(req) => (false, req),
+    };
+    // referenced polls are locked here; so this must be done before locking this poll
+    let references = models::resolve_references(&state.polls, &req.text);
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // new users get a session cookie; so their next requests are made as the same user
+    let user_id = match poll.lock().unwrap().enter(user) {
+        Ok(user_id) => user_id,
+        Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    };
+    let cookies = cookies.add(poll_cookie(&user_id, &poll_id, &state));
+    let mut poll = poll.lock().unwrap();
+    let item_id = match poll.add_item(user_id, req.question_id, req.text, references) {
+        Ok(item_id) => item_id,
+        Err(err) => return (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()).into_response(),
+    };
+    if is_form {
+        (cookies, Redirect::to(&state.urls.poll(&poll_id))).into_response()
+    } else {
+        let pending = poll.is_pending(item_id);
+        (cookies, Json(AddItemRes { item_id, pending })).into_response()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoteItemReq {
+    // zero takes the vote back
+    vote: isize,
+}
+
+pub async fn vote_poll_item(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    cookies: CookieJar,
+    Path((poll_id, item_id)): Path<(String, usize)>,
+    body: FormOrJson<VoteItemReq>,
+) -> Response {
+    let (is_form, req) = match body {
+        FormOrJson::Form(req) => (true, req),
+        FormOrJson::Json(req) => (false, req),
+    };
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // new users get a session cookie; so their next requests are made as the same user
+    let user_id = match poll.lock().unwrap().enter(user) {
+        Ok(user_id) => user_id,
+        Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    };
+    let cookies = cookies.add(poll_cookie(&user_id, &poll_id, &state));
+    let result = poll.lock().unwrap().vote_item(user_id, item_id, req.vote);
+    match result {
+        Ok(()) if is_form => (cookies, Redirect::to(&state.urls.poll(&poll_id))).into_response(),
+        Ok(()) => (cookies, StatusCode::NO_CONTENT).into_response(),
+        Err(err) => (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()).into_response(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PollResultReq {
+    // first question of the poll by default
+    #[serde(default)]
+    question: usize,
+}
+
+pub async fn poll_result(
+    State(state): State<AppState>,
+    Path(poll_id): Path<String>,
+    Query(query): Query<PollResultReq>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let result = poll.lock().unwrap().result(query.question);
+    match result {
+        Ok(result) => Json(result).into_response(),
+        Err(err @ models::PollResultError::QuestionNotFound) => {
+            (StatusCode::NOT_FOUND, err.to_string()).into_response()
+        }
+        Err(err) => (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    }
+}
+
+// state of the poll without a session, for dashboards and overlays; pages on other origins can
+// fetch it too
+pub async fn poll_public_state(
+    State(state): State<AppState>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let poll_state = poll.lock().unwrap().public_state();
+    (
+        [(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+        Json(poll_state),
+    )
+        .into_response()
+}
+
+// actions of external system users; signed with the ingestion secret of poll
+pub async fn poll_ingest(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(poll_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let signature = headers
+        .get(ingest::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let verified = poll
+        .lock()
+        .unwrap()
+        .ingest_secret()
+        .is_some_and(|secret| ingest::verify(secret.as_bytes(), &body, signature));
+    if !verified {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let request: ingest::IngestRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    // referenced polls are locked here; so this must be done before locking this poll
+    let references = match &request.action {
+        ingest::IngestAction::AddItem { text, .. } => {
+            models::resolve_references(&state.polls, text)
+        }
+        ingest::IngestAction::VoteItem { .. } => Vec::new(),
+    };
+    let mut poll = poll.lock().unwrap();
+    let user_id = match poll.external_user(&request.source, &request.user, addr.ip()) {
+        Ok(user_id) => user_id,
+        Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    };
+    let result = match request.action {
+        ingest::IngestAction::AddItem { text, .. } if text.is_empty() => {
+            Err("Poll item text cannot be empty.".to_string())
+        }
+        ingest::IngestAction::AddItem { text, question_id } => poll
+            .add_item(user_id, question_id, text, references)
+            .map(|_| ())
+            .map_err(|err| err.to_string()),
+        ingest::IngestAction::VoteItem { item_id, vote } => poll
+            .vote_item(user_id, item_id, vote)
+            .map_err(|err| err.to_string()),
+    };
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => (StatusCode::UNPROCESSABLE_ENTITY, err).into_response(),
+    }
+}
+
+// badges are embedded into wikis etc.; they shouldn't get stale for long
+const BADGE_CACHE_CONTROL: &str = "public, max-age=60";
+// longer item texts are truncated on badges
+const BADGE_TEXT_LENGTH: usize = 40;
+
+fn get_badge(state: &AppState, poll_id: &str) -> Option<models::PollBadge> {
+    let poll = state.polls.lock().unwrap().get_poll(poll_id)?;
+    let badge = poll.lock().unwrap().badge();
+    Some(badge)
+}
+
+pub async fn poll_badge_json(
+    State(state): State<AppState>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    match get_badge(&state, &poll_id) {
+        Some(badge) => {
+            ([(header::CACHE_CONTROL, BADGE_CACHE_CONTROL)], Json(badge)).into_response()
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn poll_badge_svg(
+    State(state): State<AppState>,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let Some(badge) = get_badge(&state, &poll_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let label = if badge.closed {
+        "decision"
+    } else {
+        "top option"
+    };
+    let value = match &badge.top_item {
+        Some(item) => {
+            let text: String = if item.text.chars().count() > BADGE_TEXT_LENGTH {
+                item.text
+                    .chars()
+                    .take(BADGE_TEXT_LENGTH - 1)
+                    .chain(['…'])
+                    .collect()
+            } else {
+                item.text.clone()
+            };
+            format!("{}, {} votes", text, item.vote_count)
+        }
+        None if badge.results_hidden => "hidden until close".to_string(),
+        None => "no options yet".to_string(),
+    };
+    // rough width of characters of the badge font
+    let text_width = |text: &str| text.chars().count() * 7 + 12;
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, BADGE_CACHE_CONTROL),
+        ],
+        state
+            .templates
+            .get_template("badge.svg.jinja")
+            .unwrap()
+            .render(context!(
+                title => badge.title,
+                label => label,
+                value => value,
+                label_width => text_width(label),
+                value_width => text_width(&value),
+            ))
+            .unwrap(),
+    )
+        .into_response()
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct FeedEntry {
+    id: usize,
+    title: String,
+    updated: String,
+    content: Option<String>,
+}
+
+fn rfc3339(at: &cookie::time::OffsetDateTime) -> String {
+    at.format(&cookie::time::format_description::well_known::Rfc3339)
+        .expect("UTC timestamps are always formattable")
+}
+
+pub async fn poll_feed(
+    State(state): State<AppState>,
+    headers: header::HeaderMap,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (title, activities) = {
+        let poll = poll.lock().unwrap();
+        (poll.summary().title, poll.activities())
+    };
+
+    let entries: Vec<FeedEntry> = activities
+        .iter()
+        .map(|activity| {
+            let (title, content) = match &activity.kind {
+                models::ActivityKind::Created => ("Poll created".to_string(), None),
+                models::ActivityKind::ItemAdded { item_id, text } => {
+                    (format!("Item #{} added", item_id), Some(text.clone()))
+                }
+                models::ActivityKind::VotesReset => ("Votes reset".to_string(), None),
+                models::ActivityKind::Closed => ("Poll closed".to_string(), None),
+            };
+            FeedEntry {
+                id: activity.id,
+                title,
+                updated: rfc3339(&activity.at),
+                content,
+            }
+        })
+        .collect();
+
+    let scheme = if state.config.secure { "https" } else { "http" };
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+
+    (
+        [(header::CONTENT_TYPE, "application/atom+xml")],
+        state
+            .templates
+            .get_template("feed.atom.jinja")
+            .unwrap()
+            .render(context!(
+                poll_id => poll_id,
+                poll_url => format!("{}://{}{}", scheme, host, state.urls.poll(&poll_id)),
+                title => title,
+                updated => entries.first().map(|entry| entry.updated.clone()),
+                entries => entries,
+            ))
+            .unwrap(),
+    )
+        .into_response()
+}
+
+// how changes of questions are sent on websocket; clients choose it when they connect
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    // whole question on every change
+    #[default]
+    Full,
+    // `QuestionDelta` after the whole question is sent once
+    Delta,
+}
+
+// how messages are encoded on websocket, both ways
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    // text messages
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    // binary messages; text messages of the client are still read as JSON
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    fn message(self, value: &impl Serialize) -> ws::Message {
+        match self {
+            Encoding::Json => {
+                ws::Message::Text(serde_json::to_string(value).expect("messages should serialize"))
+            }
+            // field names are kept and ids are written as strings; so messages decode to the same
+            // objects as JSON ones
+            Encoding::MessagePack => {
+                let mut bytes = Vec::new();
+                let mut serializer = rmp_serde::Serializer::new(&mut bytes)
+                    .with_struct_map()
+                    .with_human_readable();
+                value
+                    .serialize(&mut serializer)
+                    .expect("messages should serialize");
+                ws::Message::Binary(bytes)
+            }
+        }
+    }
+
+    // `None` for messages that don't carry anything, like pings; invalid ones are `Null`, so
+    // they're answered with an error
+    fn decode(self, message: &ws::Message) -> Option<serde_json::Value> {
+        let value = match (message, self) {
+            (ws::Message::Text(text), _) => serde_json::from_str(text).ok(),
+            (ws::Message::Binary(bytes), Encoding::MessagePack) => {
+                rmp_serde::from_slice(bytes).ok()
+            }
+            _ => return None,
+        };
+        Some(value.unwrap_or_default())
+    }
+}
+
+// messages shorter than this many bytes aren't worth compressing
+const COMPRESSION_MIN_SIZE: usize = 1024;
+
+// clients ask for compression; it's only used when `ANKET_WS_COMPRESSION` allows it
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    // zlib; compressed messages are binary frames, and they start with 0x78 unlike MessagePack
+    // messages
+    Deflate,
+}
+
+impl Compression {
+    fn apply(self, message: ws::Message) -> ws::Message {
+        let data = match (&message, self) {
+            (ws::Message::Text(text), Compression::Deflate) => text.as_bytes(),
+            (ws::Message::Binary(bytes), Compression::Deflate) => bytes.as_slice(),
+            _ => return message,
+        };
+        if data.len() < COMPRESSION_MIN_SIZE {
+            return message;
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder
+            .write_all(data)
+            .expect("writing to memory doesn't fail");
+        ws::Message::Binary(encoder.finish().expect("writing to memory doesn't fail"))
+    }
+}
+
+// options of a websocket connection, given in its URL
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct JoinPollReq {
+    #[serde(default)]
+    updates: UpdateMode,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    view: models::StateView,
+}
+
+impl JoinPollReq {
+    fn message(&self, value: &impl Serialize) -> ws::Message {
+        self.compression.apply(self.encoding.message(value))
+    }
+}
+
+// `<user id>.<signature>`; clients that can't send cookies or headers on websockets join as the
+// user with this, in `?token=`. It's only valid for this poll; `secret` is `AppState.token_secret`
+pub fn ws_token(secret: &str, poll_id: &str, user_id: &Uuid) -> String {
+    let signed = format!("{}/{}", poll_id, user_id);
+    let signature = utils::hmac_sha1(secret.as_bytes(), signed.as_bytes());
+    format!("{}.{}", user_id, utils::to_hex(&signature))
+}
+
+// user id of the token; `None` if it isn't signed with `secret` for this poll
+fn verify_ws_token(secret: &str, poll_id: &str, token: &str) -> Option<Uuid> {
+    let (user_id, _) = token.split_once('.')?;
+    let user_id = Uuid::from_str(user_id).ok()?;
+    utils::constant_time_eq(&ws_token(secret, poll_id, &user_id), token).then_some(user_id)
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WsAuthReq {
+    // from `ws_token`; used instead of the session cookie
+    token: Option<String>,
+}
+
+pub async fn join_poll(
+    State(state): State<AppState>,
+    Extension(mut user): Extension<models::UserDetails>,
+    Path(poll_id): Path<String>,
+    Query(mut query): Query<JoinPollReq>,
+    Query(auth): Query<WsAuthReq>,
     ws: ws::WebSocketUpgrade,
 ) -> Response {
+    if !state.config.ws_compression {
+        query.compression = Compression::None;
+    }
+    if let Some(token) = auth.token {
+        match verify_ws_token(&state.token_secret, &poll_id, &token) {
+            Some(user_id) => user.id = Some(user_id),
+            None => return (StatusCode::UNAUTHORIZED, "Invalid token.").into_response(),
+        }
+    }
     let poll = state.polls.lock().unwrap().get_poll(&poll_id);
     match poll {
         Some(poll) => {
-            let (user_sender, user_receiver) = mpsc::unbounded_channel();
-            let user_id = poll.lock().unwrap().join(user, user_sender);
+            // connection gets everything until it sends `Subscribe`
+            let (subscribe, subscription) = watch::channel(models::Subscription {
+                view: query.view,
+                ..Default::default()
+            });
+            let (user_id, feed) = match poll.lock().unwrap().join_subscribed(user, subscription) {
+                Ok(joined) => joined,
+                Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+            };
 
             // TODO consider using `ws.on_failed_upgrade`?
-            let mut response =
-                ws.on_upgrade(move |socket| events_handler(socket, user_id, poll, user_receiver));
+            let app_state = state.clone();
+            let mut response = ws.on_upgrade(move |socket| {
+                events_handler(socket, user_id, app_state, poll, feed, subscribe, query)
+            });
             response.headers_mut().append(
                 header::SET_COOKIE,
-                poll_cookie(&user_id, &poll_id, state.config.secure)
+                poll_cookie(&user_id, &poll_id, &state)
                     .encoded()
                     .to_string()
                     .parse()
@@ -220,37 +1201,135 @@ pub async fn join_poll(
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(tag = "type", content = "content")]
-pub enum UserMessage {
-    AddItem { text: String },
-    VoteItem { item_id: usize, vote: isize },
+// same events as the websocket, for clients behind proxies that break websockets; actions are
+// sent with plain HTTP requests
+pub async fn poll_events(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    cookies: CookieJar,
+    Path(poll_id): Path<String>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (user_id, feed) = match poll.lock().unwrap().join(user) {
+        Ok(joined) => joined,
+        Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+    };
+    // poll stops updating the user once this stream ends and the feed is dropped along with it
+    let events = futures_util::stream::unfold(feed, |mut feed| async move {
+        let events = feed.next().await?;
+        let events = events
+            .into_iter()
+            .map(|event| sse::Event::default().json_data(&*event));
+        Some((futures_util::stream::iter(events), feed))
+    })
+    .flatten();
+    (
+        cookies.add(poll_cookie(&user_id, &poll_id, &state)),
+        sse::Sse::new(events).keep_alive(sse::KeepAlive::default()),
+    )
+        .into_response()
 }
 
+// long polling requests are answered after this long even if poll doesn't change
+const LONG_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(tag = "type", content = "content")]
-pub enum UserResponse {
-    ActionResponse(String),
-    PollStateUpdate(models::PollState),
+pub struct PollStateReq {
+    // version that client has; request waits until a newer one is available
+    #[serde(default)]
+    since: Option<u64>,
 }
 
-impl From<UserResponse> for ws::Message {
-    fn from(val: UserResponse) -> Self {
-        ws::Message::Text(serde_json::to_string(&val).expect("PollState should serialize"))
+#[derive(Serialize, Clone, Debug)]
+pub struct PollStateRes {
+    version: u64,
+    state: models::PollState,
+}
+
+// for clients that can't keep a connection open; they ask again with the version they got
+pub async fn poll_state(
+    State(state): State<AppState>,
+    Extension(user): Extension<models::UserDetails>,
+    cookies: CookieJar,
+    Path(poll_id): Path<String>,
+    Query(query): Query<PollStateReq>,
+) -> Response {
+    let poll = state.polls.lock().unwrap().get_poll(&poll_id);
+    let Some(poll) = poll else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (user_id, mut versions) = {
+        let mut poll = poll.lock().unwrap();
+        match poll.enter(user) {
+            Ok(user_id) => (user_id, poll.watch_version()),
+            Err(err) => return (StatusCode::FORBIDDEN, err.to_string()).into_response(),
+        }
+    };
+    if let Some(since) = query.since {
+        // current state is sent after the timeout, or if poll is dropped in the meantime
+        let _ = tokio::time::timeout(
+            LONG_POLL_TIMEOUT,
+            versions.wait_for(|version| *version > since),
+        )
+        .await;
+    }
+    let poll = poll.lock().unwrap();
+    let res = PollStateRes {
+        version: poll.version(),
+        state: poll.get_state(&user_id),
+    };
+    (
+        cookies.add(poll_cookie(&user_id, &poll_id, &state)),
+        Json(res),
+    )
+        .into_response()
+}
+
+// optional parts of the protocol that this server supports; sent to clients in `Hello`
+fn server_features(config: &AppConfig) -> Vec<String> {
+    let mut features = vec![
+        "delta",
+        "resync",
+        "message-ids",
+        "msgpack",
+        "list-items",
+        "subscriptions",
+        "shared-view",
+    ];
+    if config.ws_compression {
+        features.push("compression");
     }
+    if config.ws_ping_interval.is_some() {
+        features.push("heartbeat");
+    }
+    features.into_iter().map(str::to_owned).collect()
 }
 
+// messages that can wait for the socket; senders wait when it's full, and updates of the poll
+// that come in the meantime are sent together as the latest one
+const WS_QUEUE_SIZE: usize = 32;
+// connections whose socket doesn't take messages for this long are closed
+const WS_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
 fn websocket_worker(
     mut sender: futures_util::stream::SplitSink<ws::WebSocket, ws::Message>,
 ) -> (
     tokio::task::JoinHandle<Result<(), axum::Error>>,
-    mpsc::UnboundedSender<ws::Message>,
+    mpsc::Sender<ws::Message>,
 ) {
-    let (task_sender, mut task_receiver) = mpsc::unbounded_channel();
+    let (task_sender, mut task_receiver) = mpsc::channel(WS_QUEUE_SIZE);
 
     let task = tokio::spawn(async move {
         while let Some(message) = task_receiver.recv().await {
-            sender.send(message).await?
+            // nothing can be sent after closing the connection
+            let close = matches!(message, ws::Message::Close(_));
+            sender.send(message).await?;
+            if close {
+                break;
+            }
         }
         Ok(())
     });
@@ -258,23 +1337,258 @@ fn websocket_worker(
     (task, task_sender)
 }
 
+async fn translate_item(
+    poll: &Mutex<models::Poll>,
+    translator: Option<&translate::Translator>,
+    user_id: Uuid,
+    item_id: usize,
+    lang: String,
+) -> UserResponse {
+    let Some(translator) = translator else {
+        return ActionError::new("TranslationUnavailable", "Translation is not available.").into();
+    };
+    if !translate::is_valid_lang(&lang) {
+        return ActionError::new("InvalidLanguage", "Invalid language code.")
+            .input("lang")
+            .into();
+    }
+    let text = {
+        let poll = poll.lock().unwrap();
+        let Some(text) = poll.item_text(user_id, item_id) else {
+            return ActionError::new("ItemNotFound", "No such item exists with this item ID.")
+                .into();
+        };
+        if let Some(text) = poll.cached_translation(item_id, &lang) {
+            return UserResponse::TranslateItem {
+                item_id,
+                lang,
+                text,
+            };
+        }
+        text
+    };
+
+    // poll is not locked while waiting for the translation service
+    match translator.translate(&text, &lang).await {
+        Ok(text) => {
+            poll.lock()
+                .unwrap()
+                .cache_translation(item_id, lang.clone(), text.clone());
+            UserResponse::TranslateItem {
+                item_id,
+                lang,
+                text,
+            }
+        }
+        Err(err) => ActionError::new("TranslationFailed", &err.to_string()).into(),
+    }
+}
+
+// privileged actions are checked against poll owner here; methods of poll check it as well
+fn owner_command(poll: &Mutex<models::Poll>, user_id: Uuid, command: OwnerMessage) -> UserResponse {
+    let mut poll = poll.lock().unwrap();
+    if !poll.is_owned_by(&user_id) {
+        return ActionError::new("NotOwner", "You have to be owner of this poll to do this.")
+            .into();
+    }
+    let name = command.name();
+    let result = match command {
+        OwnerMessage::AddQuestion { title, vote_mode } => {
+            if title.trim().is_empty() {
+                Err(ActionError::new("EmptyText", "Question title cannot be empty.").input("title"))
+            } else {
+                poll.add_question(user_id, title, vote_mode)
+                    .map(|_| ())
+                    .map_err(ActionError::from_error)
+            }
+        }
+        OwnerMessage::SetSections { sections } => poll
+            .set_sections(user_id, sections)
+            .map_err(|err| ActionError::from_error(err).input("sections")),
+        OwnerMessage::DeleteItem { item_id } => poll
+            .delete_item(user_id, item_id)
+            .map_err(ActionError::from_error),
+        OwnerMessage::ApproveItem { item_id } => poll
+            .approve_item(user_id, item_id)
+            .map_err(ActionError::from_error),
+        OwnerMessage::RejectItem { item_id, reason } => poll
+            .reject_item(user_id, item_id, reason)
+            .map_err(ActionError::from_error),
+        OwnerMessage::DismissReports { item_id } => poll
+            .dismiss_reports(user_id, item_id)
+            .map_err(ActionError::from_error),
+        OwnerMessage::PinItem { item_id, pinned } => poll
+            .pin_item(user_id, item_id, pinned)
+            .map_err(ActionError::from_error),
+        OwnerMessage::MergeItems {
+            source_id,
+            target_id,
+        } => poll
+            .merge_items(user_id, source_id, target_id)
+            .map_err(ActionError::from_error),
+        OwnerMessage::ClosePoll => poll.close_by(user_id).map_err(ActionError::from_error),
+        OwnerMessage::ResetVotes => poll.reset_votes(user_id).map_err(ActionError::from_error),
+        OwnerMessage::SetUserWeight {
+            participant_id,
+            weight,
+        } => poll
+            .set_user_weight(user_id, participant_id, weight)
+            .map_err(ActionError::from_error),
+        OwnerMessage::ShadowBan {
+            participant_id,
+            banned,
+        } => poll
+            .shadow_ban(user_id, participant_id, banned)
+            .map_err(ActionError::from_error),
+        OwnerMessage::KickUser { participant_id } => poll
+            .kick_user(user_id, participant_id)
+            .map_err(ActionError::from_error),
+        OwnerMessage::BanUser {
+            participant_id,
+            remove_content,
+        } => poll
+            .ban_user(user_id, participant_id, remove_content)
+            .map_err(ActionError::from_error),
+    };
+    match result {
+        Ok(()) => UserResponse::OwnerDone {
+            command: name.to_string(),
+        },
+        Err(err) => err.into(),
+    }
+}
+
+// turns poll events into messages of a websocket connection; keeps what's sent for deltas
+struct ConnectionUpdates {
+    mode: UpdateMode,
+    // tick of the last broadcast
+    version: u64,
+    // `Question` events that this client last got; deltas are made against these
+    questions: HashMap<usize, Arc<models::PollEvent>>,
+}
+
+impl ConnectionUpdates {
+    fn new(mode: UpdateMode) -> Self {
+        Self {
+            mode,
+            version: 0,
+            questions: HashMap::new(),
+        }
+    }
+
+    // `whole` sends questions whole on delta mode too, for clients that lost their state
+    fn message(
+        &mut self,
+        event: Arc<models::PollEvent>,
+        whole: bool,
+        options: &JoinPollReq,
+    ) -> ws::Message {
+        match &*event {
+            models::PollEvent::Clock(clock) => self.version = clock.tick,
+            models::PollEvent::Question(question) if self.mode == UpdateMode::Delta => {
+                let delta = self
+                    .questions
+                    .get(&question.id)
+                    .filter(|_| !whole)
+                    .and_then(|previous| match &**previous {
+                        models::PollEvent::Question(previous) => question.delta_since(previous),
+                        _ => None,
+                    });
+                self.questions.insert(question.id, event.clone());
+                if let Some(delta) = delta {
+                    return options.message(&UserResponse::QuestionDelta {
+                        version: self.version,
+                        delta,
+                    });
+                }
+            }
+            _ => {}
+        }
+        options.message(&*event)
+    }
+}
+
 async fn events_handler(
     socket: ws::WebSocket,
     user_id: Uuid,
+    state: AppState,
     poll: Arc<Mutex<models::Poll>>,
-    mut user_receiver: mpsc::UnboundedReceiver<models::PollState>,
+    mut feed: models::UserFeed,
+    subscribe: watch::Sender<models::Subscription>,
+    options: JoinPollReq,
 ) {
     let (ws_sender, mut ws_receiver) = socket.split();
     let (ws_task, ws_sender) = websocket_worker(ws_sender);
+    let polls = state.polls.clone();
+    let translator = state.translator.clone();
+
+    // before any event of the poll; so clients know what to expect
+    let features = server_features(&state.config);
+    let _ = ws_sender.try_send(options.message(&UserResponse::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        features: features.clone(),
+    }));
 
+    // pings sent since the client was last heard from; anything it sends resets this
+    let missed_pongs = Arc::new(AtomicU32::new(0));
+    let heartbeat_task = {
+        let ws_sender = ws_sender.clone();
+        let missed_pongs = missed_pongs.clone();
+        tokio::spawn(async move {
+            let Some(period) = state.config.ws_ping_interval else {
+                return std::future::pending().await;
+            };
+            let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                // dead connections aren't noticed otherwise until a send fails
+                if missed_pongs.fetch_add(1, Ordering::Relaxed) >= state.config.ws_max_missed_pongs
+                {
+                    return;
+                }
+                // queue is only full when the socket is slow already; ping is skipped, but it's
+                // still counted as missed
+                if let Err(mpsc::error::TrySendError::Closed(_)) =
+                    ws_sender.try_send(ws::Message::Ping(Vec::new()))
+                {
+                    return;
+                }
+            }
+        })
+    };
+
+    // whole state for `Resync` and `Subscribe`; it's sent in order with the other events of the
+    // poll, and broadcasts filter the others by the subscription already
+    // only the latest one waits to be sent; it has everything the ones before it had
+    let (resync_sender, mut resync_receiver) = watch::channel(Vec::new());
+    let resync = move |events: Vec<models::PollEvent>, subscription: &models::Subscription| {
+        let events = events
+            .into_iter()
+            .filter_map(|event| subscription.filter(Arc::new(event)))
+            .collect();
+        resync_sender.send_replace(events);
+    };
     let poll_task = {
         let ws_sender = ws_sender.clone();
         tokio::spawn(async move {
-            while let Some(state) = user_receiver.recv().await {
-                let msg = UserResponse::PollStateUpdate(state);
-                let send = ws_sender.send(msg.into());
-                if send.is_err() {
-                    break;
+            let mut updates = ConnectionUpdates::new(options.updates);
+            loop {
+                let (events, whole) = tokio::select! {
+                    events = feed.next() => match events {
+                        Some(events) => (events, false),
+                        None => break,
+                    },
+                    Ok(()) = resync_receiver.changed() => {
+                        (resync_receiver.borrow_and_update().clone(), true)
+                    }
+                };
+                for event in events {
+                    let msg = updates.message(event, whole, &options);
+                    if ws_sender.send_timeout(msg, WS_SEND_TIMEOUT).await.is_err() {
+                        return;
+                    }
                 }
             }
         })
@@ -282,35 +1596,251 @@ async fn events_handler(
 
     let user_task = tokio::spawn(async move {
         while let Some(wsmsg) = ws_receiver.next().await {
-            if let Ok(ws::Message::Text(text)) = wsmsg {
-                let response = match serde_json::from_str::<UserMessage>(&text) {
+            // pings of the client are answered by the websocket itself; they count here too
+            if wsmsg.is_ok() {
+                missed_pongs.store(0, Ordering::Relaxed);
+            }
+            let message = wsmsg
+                .as_ref()
+                .ok()
+                .and_then(|message| options.encoding.decode(message));
+            if let Some(message) = message {
+                // id is echoed even if the rest of the message is invalid
+                let id = message
+                    .get("id")
+                    .and_then(|id| MessageId::deserialize(id).ok());
+                let response = match UserMessage::deserialize(message) {
                     Ok(msg) => match msg {
-                        UserMessage::AddItem { text } => {
+                        UserMessage::Hello {
+                            protocol_version,
+                            features: client_features,
+                        } => match negotiate_hello(protocol_version, &client_features, &features) {
+                            Some(hello) => Some(hello),
+                            None => {
+                                let frame = ws::CloseFrame {
+                                    code: ws::close_code::PROTOCOL,
+                                    reason: format!(
+                                        "protocol version {} is not supported, at least {} is needed",
+                                        protocol_version, MIN_PROTOCOL_VERSION
+                                    )
+                                    .into(),
+                                };
+                                let _ = ws_sender
+                                    .send_timeout(ws::Message::Close(Some(frame)), WS_SEND_TIMEOUT)
+                                    .await;
+                                continue;
+                            }
+                        },
+                        UserMessage::AddItem {
+                            text,
+                            question_id,
+                            attachment,
+                            section,
+                        } => {
+                            if text.is_empty() {
+                                Some(
+                                    ActionError::new(
+                                        "EmptyText",
+                                        "Poll item text cannot be empty.",
+                                    )
+                                    .input("text")
+                                    .into(),
+                                )
+                            } else {
+                                // referenced polls are locked here; so this must be done before
+                                // locking the poll of this user
+                                let references = models::resolve_references(&polls, &text);
+                                let extras = models::ItemExtras {
+                                    attachment,
+                                    section,
+                                };
+                                let mut poll = poll.lock().unwrap();
+                                match poll.add_item_with(
+                                    user_id,
+                                    question_id,
+                                    text,
+                                    references,
+                                    extras,
+                                ) {
+                                    Ok(item_id) if poll.is_pending(item_id) => {
+                                        Some(UserResponse::ItemPending { item_id })
+                                    }
+                                    Ok(item_id) => Some(UserResponse::ItemAdded { item_id }),
+                                    Err(err) => {
+                                        Some(ActionError::from_error(err).input("text").into())
+                                    }
+                                }
+                            }
+                        }
+                        UserMessage::SuggestSimilar { text, question_id } => {
+                            let items =
+                                poll.lock()
+                                    .unwrap()
+                                    .similar_items(user_id, question_id, &text);
+                            Some(UserResponse::SimilarItems { text, items })
+                        }
+                        UserMessage::VoteItem { item_id, vote } => Some(
+                            match poll.lock().unwrap().vote_item(user_id, item_id, vote) {
+                                Ok(()) => UserResponse::VoteRecorded {
+                                    item_id,
+                                    value: vote,
+                                },
+                                Err(err) => ActionError::from_error(err).input("vote").into(),
+                            },
+                        ),
+                        UserMessage::ReactItem { item_id, emoji } => poll
+                            .lock()
+                            .unwrap()
+                            .react_item(user_id, item_id, &emoji)
+                            .err()
+                            .map(|err| ActionError::from_error(err).input("emoji").into()),
+                        UserMessage::EditItem { item_id, text } => {
                             if text.is_empty() {
-                                Some(UserResponse::ActionResponse(
-                                    "Poll item text cannot be empty.".to_string(),
-                                ))
+                                Some(
+                                    ActionError::new(
+                                        "EmptyText",
+                                        "Poll item text cannot be empty.",
+                                    )
+                                    .input("text")
+                                    .into(),
+                                )
                             } else {
                                 poll.lock()
                                     .unwrap()
-                                    .add_item(user_id, text)
+                                    .edit_item(user_id, item_id, text)
                                     .err()
-                                    .map(|err| UserResponse::ActionResponse(err.to_string()))
+                                    .map(|err| ActionError::from_error(err).input("text").into())
                             }
                         }
-                        UserMessage::VoteItem { item_id, vote } => poll
+                        UserMessage::Owner(command) => Some(owner_command(&poll, user_id, command)),
+                        UserMessage::DeleteItem { item_id } => poll
                             .lock()
                             .unwrap()
-                            .vote_item(user_id, item_id, vote)
+                            .delete_item(user_id, item_id)
                             .err()
-                            .map(|err| UserResponse::ActionResponse(err.to_string())),
+                            .map(|err| ActionError::from_error(err).into()),
+                        UserMessage::ReportItem { item_id, reason } => Some(
+                            match poll.lock().unwrap().report_item(user_id, item_id, reason) {
+                                Ok(()) => UserResponse::ItemReported { item_id },
+                                Err(err) => ActionError::from_error(err).input("reason").into(),
+                            },
+                        ),
+                        UserMessage::CommentItem {
+                            item_id,
+                            reply_to,
+                            text,
+                        } => {
+                            if text.trim().is_empty() {
+                                Some(
+                                    ActionError::new("EmptyText", "Comment text cannot be empty.")
+                                        .input("text")
+                                        .into(),
+                                )
+                            } else {
+                                let mut poll = poll.lock().unwrap();
+                                // commenter sees their comment without asking again
+                                match poll.comment_item(user_id, item_id, reply_to, text) {
+                                    Ok(()) => {
+                                        poll.comments(user_id, item_id).ok().map(|comments| {
+                                            UserResponse::Comments { item_id, comments }
+                                        })
+                                    }
+                                    Err(err) => {
+                                        Some(ActionError::from_error(err).input("text").into())
+                                    }
+                                }
+                            }
+                        }
+                        UserMessage::Resync { have_version } => {
+                            match poll.lock().unwrap().resync(&user_id, have_version) {
+                                Some(events) => {
+                                    resync(events, &subscribe.borrow());
+                                    None
+                                }
+                                None => Some(UserResponse::Synced {
+                                    version: have_version,
+                                }),
+                            }
+                        }
+                        UserMessage::GetState => {
+                            let poll = poll.lock().unwrap();
+                            Some(UserResponse::State {
+                                version: poll.version(),
+                                state: poll.get_state(&user_id),
+                            })
+                        }
+                        UserMessage::Subscribe(subscription) => {
+                            let subscription = models::Subscription {
+                                view: options.view,
+                                ..subscription
+                            };
+                            // sections that weren't sent to this connection may be needed now
+                            let events = poll.lock().unwrap().state_events(&user_id);
+                            resync(events, &subscription);
+                            subscribe.send_replace(subscription);
+                            None
+                        }
+                        UserMessage::ListItems {
+                            question_id,
+                            offset,
+                            limit,
+                            order,
+                        } => Some(
+                            match poll.lock().unwrap().list_items(
+                                &user_id,
+                                question_id,
+                                offset,
+                                limit,
+                                order,
+                            ) {
+                                Ok(page) => UserResponse::Items(page),
+                                Err(err) => ActionError::from_error(err).into(),
+                            },
+                        ),
+                        UserMessage::GetComments { item_id } => {
+                            Some(match poll.lock().unwrap().comments(user_id, item_id) {
+                                Ok(comments) => UserResponse::Comments { item_id, comments },
+                                Err(err) => ActionError::from_error(err).into(),
+                            })
+                        }
+                        UserMessage::SetName { name } => poll
+                            .lock()
+                            .unwrap()
+                            .set_name(user_id, &name)
+                            .err()
+                            .map(|err| ActionError::from_error(err).input("name").into()),
+                        UserMessage::UndoLastAction => poll
+                            .lock()
+                            .unwrap()
+                            .undo_last_action(user_id)
+                            .err()
+                            .map(|err| match err {
+                                // vote is changed back; its errors are told as they are
+                                models::UndoError::Vote(err) => ActionError::from_error(err).into(),
+                                err => ActionError::from_error(err).into(),
+                            }),
+                        UserMessage::TranslateItem { item_id, lang } => Some(
+                            translate_item(&poll, translator.as_deref(), user_id, item_id, lang)
+                                .await,
+                        ),
                     },
-                    Err(_) => Some(UserResponse::ActionResponse(
-                        "Failed to deserialize client message.".to_string(),
-                    )),
+                    Err(_) => Some(
+                        ActionError::new("InvalidMessage", "Failed to deserialize client message.")
+                            .into(),
+                    ),
                 };
-                if let Some(resp) = response {
-                    if ws_sender.send(resp.into()).is_err() {
+                // messages with an id are always answered; so clients know when they're done
+                let response = match response {
+                    None if id.is_some() => Some(UserResponse::Ack),
+                    response => response,
+                };
+                if let Some(response) = response {
+                    let reply = Reply { id, response };
+                    if ws_sender
+                        .send_timeout(options.message(&reply), WS_SEND_TIMEOUT)
+                        .await
+                        .is_err()
+                    {
                         break;
                     }
                 }
@@ -324,22 +1854,66 @@ async fn events_handler(
         }
     });
 
-    let poll_handle = poll_task.abort_handle();
-    let user_handle = user_task.abort_handle();
-    let ws_handle = ws_task.abort_handle();
+    let handles = [
+        poll_task.abort_handle(),
+        user_task.abort_handle(),
+        ws_task.abort_handle(),
+        heartbeat_task.abort_handle(),
+    ];
 
+    // connection is over when any of them ends
     tokio::select! {
-        _ = poll_task => {
-            user_handle.abort();
-            ws_handle.abort();
-        }
-        _ = user_task => {
-            poll_handle.abort();
-            ws_handle.abort();
-        }
-        _ = ws_task => {
-            poll_handle.abort();
-            user_handle.abort();
+        _ = poll_task => {}
+        _ = user_task => {}
+        _ = ws_task => {}
+        _ = heartbeat_task => {}
+    }
+    for handle in handles {
+        handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret of this instance";
+
+    #[test]
+    fn ws_tokens_only_work_for_their_poll_and_user() {
+        let user_id = Uuid::new_v4();
+        let token = ws_token(SECRET, "poll", &user_id);
+        assert_eq!(verify_ws_token(SECRET, "poll", &token), Some(user_id));
+        assert_eq!(verify_ws_token(SECRET, "other poll", &token), None);
+        assert_eq!(verify_ws_token("other secret", "poll", &token), None);
+
+        let (_, signature) = token.split_once('.').unwrap();
+        let other_user = format!("{}.{}", Uuid::new_v4(), signature);
+        assert_eq!(verify_ws_token(SECRET, "poll", &other_user), None);
+
+        let mut tampered = token.clone();
+        let last = if tampered.pop() == Some('0') {
+            '1'
+        } else {
+            '0'
+        };
+        tampered.push(last);
+        assert_eq!(verify_ws_token(SECRET, "poll", &tampered), None);
+    }
+
+    #[test]
+    fn malformed_ws_tokens_are_rejected() {
+        let user_id = Uuid::new_v4();
+        let token = ws_token(SECRET, "poll", &user_id);
+        let (_, signature) = token.split_once('.').unwrap();
+        for token in [
+            String::new(),
+            user_id.to_string(),
+            format!("{}{}", user_id, signature),
+            format!("not-a-uuid.{}", signature),
+            format!("{}.", user_id),
+        ] {
+            assert_eq!(verify_ws_token(SECRET, "poll", &token), None, "{:?}", token);
         }
     }
 }